@@ -1,14 +1,20 @@
 use crate::{
+    evaluation::accumulator::EvalAccumulator,
     r#move::{move_segment::*, Move},
     search::zorb_set_precomputed::ZORB_SET,
     shared::{
         board_utils::{get_coords_from_index, get_file, index_from_coords, reverse_position_orientation},
         constants::*,
-        piece_type::{get_piece_char, get_piece_type_from_char, PieceType},
+        piece_type::{get_piece_char, Colour, PieceType},
     }, PAWN_ZORB,
 };
 
-use super::{bitboard::Bitboard, position::MoveSegmentArray, king_position_analysis::{analyze_king_position, KingPositionAnalysis}};
+use super::{
+    bitboard::Bitboard,
+    fen::{FenError, FenFields},
+    king_position_analysis::{analyze_king_position, KingPositionAnalysis},
+    position::MoveSegmentArray,
+};
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct BoardRep {
@@ -30,6 +36,9 @@ pub struct BoardRep {
     pub ep_index: u8,
     pub zorb_key: u64,
     pub king_pawn_zorb: u64,
+    pub eval_accumulator: EvalAccumulator,
+    // Piece on each square, see mailbox_code
+    pub(crate) mailbox: [u8; 64],
 }
 
 impl BoardRep {
@@ -39,6 +48,32 @@ impl BoardRep {
         castling_segment: String,
         ep_segment: String,
     ) -> Self {
+        match Self::parse(&position_segment, &turn_segment, &castling_segment, &ep_segment) {
+            Ok(board) => board.with_hashes(),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    // Parses and validates a full FEN, see FenError for everything that is rejected
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields = FenFields::split(fen)?;
+        Self::try_from_fields(&fields)
+    }
+
+    pub(crate) fn try_from_fields(fields: &FenFields) -> Result<Self, FenError> {
+        let board = Self::parse(fields.placement, fields.turn, fields.castling, fields.ep)?;
+        board.validate()?;
+        Ok(board.with_hashes())
+    }
+
+    // Only checks the syntax of each field, the returned board has no hashes or accumulator yet as those
+    // assume a legal position
+    fn parse(
+        position_segment: &str,
+        turn_segment: &str,
+        castling_segment: &str,
+        ep_segment: &str,
+    ) -> Result<Self, FenError> {
         let mut occupancy = 0;
         let mut white_occupancy = 0;
         let mut black_occupancy = 0;
@@ -54,119 +89,144 @@ impl BoardRep {
         let mut black_queen_side_castling = false;
         let mut black_king_side_castling = false;
 
-        let mut position_index = 63;
-        let mut rank_length = 0; // The number of spaces we've worked through in the current rank
-        for i in 0..position_segment.len() {
-            // Get char
-            let char = position_segment.chars().nth(i).unwrap();
-
-            // Check if is a shift digit
-            if char.is_ascii_digit() {
-                let digit = (char as i16 - 0x30) as i8;
-                position_index -= digit;
-                rank_length += digit;
-                continue;
-            }
-
-            if char == '/' {
-                position_index -= 8 - rank_length;
-                rank_length = 0;
+        let mut rank_count = 0;
+        for rank_segment in position_segment.split('/') {
+            rank_count += 1;
+            if rank_count > 8 {
                 continue;
             }
+            let rank = 8 - rank_count as u8;
+            let mut file = 0; // The number of spaces we've worked through in the current rank
+            for char in rank_segment.chars() {
+                // Check if is a shift digit
+                if let Some(digit) = char.to_digit(10) {
+                    if digit == 0 || file + digit as u8 > 8 {
+                        return Err(FenError::InvalidRank(rank + 1));
+                    }
+                    file += digit as u8;
+                    continue;
+                }
 
-            let piece_type = get_piece_type_from_char(char);
+                if file >= 8 {
+                    return Err(FenError::InvalidRank(rank + 1));
+                }
+                let piece_type = match char.to_ascii_lowercase() {
+                    'p' => PieceType::Pawn,
+                    'n' => PieceType::Knight,
+                    'b' => PieceType::Bishop,
+                    'r' => PieceType::Rook,
+                    'q' => PieceType::Queen,
+                    'k' => PieceType::King,
+                    _ => return Err(FenError::InvalidPiece(char)),
+                };
+                let position_index = rank * 8 + (7 - file);
 
-            let piece_is_black = char.is_ascii_lowercase();
-            let mut colour_board_to_change = if piece_is_black {
-                black_occupancy
-            } else {
-                white_occupancy
-            };
+                let piece_is_black = char.is_ascii_lowercase();
+                let mut colour_board_to_change = if piece_is_black {
+                    black_occupancy
+                } else {
+                    white_occupancy
+                };
 
-            match piece_type {
-                PieceType::Pawn => {
-                    (pawn_bitboard, colour_board_to_change, occupancy) = flip_piece_i8(
-                        position_index,
-                        pawn_bitboard,
-                        colour_board_to_change,
-                        occupancy,
-                    )
-                }
-                PieceType::Knight => {
-                    (knight_bitboard, colour_board_to_change, occupancy) = flip_piece_i8(
-                        position_index,
-                        knight_bitboard,
-                        colour_board_to_change,
-                        occupancy,
-                    )
-                }
-                PieceType::Bishop => {
-                    (bishop_bitboard, colour_board_to_change, occupancy) = flip_piece_i8(
-                        position_index,
-                        bishop_bitboard,
-                        colour_board_to_change,
-                        occupancy,
-                    )
-                }
-                PieceType::Rook => {
-                    (rook_bitboard, colour_board_to_change, occupancy) = flip_piece_i8(
-                        position_index,
-                        rook_bitboard,
-                        colour_board_to_change,
-                        occupancy,
-                    )
-                }
-                PieceType::Queen => {
-                    (queen_bitboard, colour_board_to_change, occupancy) = flip_piece_i8(
-                        position_index,
-                        queen_bitboard,
-                        colour_board_to_change,
-                        occupancy,
-                    )
-                }
-                PieceType::King => {
-                    if piece_is_black {
-                        black_king_position = position_index as u8;
-                    } else {
-                        white_king_position = position_index as u8;
+                match piece_type {
+                    PieceType::Pawn => {
+                        (pawn_bitboard, colour_board_to_change, occupancy) = flip_piece(
+                            position_index,
+                            pawn_bitboard,
+                            colour_board_to_change,
+                            occupancy,
+                        )
+                    }
+                    PieceType::Knight => {
+                        (knight_bitboard, colour_board_to_change, occupancy) = flip_piece(
+                            position_index,
+                            knight_bitboard,
+                            colour_board_to_change,
+                            occupancy,
+                        )
+                    }
+                    PieceType::Bishop => {
+                        (bishop_bitboard, colour_board_to_change, occupancy) = flip_piece(
+                            position_index,
+                            bishop_bitboard,
+                            colour_board_to_change,
+                            occupancy,
+                        )
+                    }
+                    PieceType::Rook => {
+                        (rook_bitboard, colour_board_to_change, occupancy) = flip_piece(
+                            position_index,
+                            rook_bitboard,
+                            colour_board_to_change,
+                            occupancy,
+                        )
+                    }
+                    PieceType::Queen => {
+                        (queen_bitboard, colour_board_to_change, occupancy) = flip_piece(
+                            position_index,
+                            queen_bitboard,
+                            colour_board_to_change,
+                            occupancy,
+                        )
+                    }
+                    _ => {
+                        if piece_is_black {
+                            black_king_position = position_index;
+                        } else {
+                            white_king_position = position_index;
+                        }
+                        colour_board_to_change = colour_board_to_change.flip(position_index);
+                        occupancy = occupancy.flip(position_index);
                     }
-                    colour_board_to_change = colour_board_to_change.flip(position_index as u8);
-                    occupancy = occupancy.flip(position_index as u8);
                 }
-                _ => panic!("Unknown piece type {:?}", piece_type),
-            }
 
-            if piece_is_black {
-                black_occupancy = colour_board_to_change;
-            } else {
-                white_occupancy = colour_board_to_change;
-            }
+                if piece_is_black {
+                    black_occupancy = colour_board_to_change;
+                } else {
+                    white_occupancy = colour_board_to_change;
+                }
 
-            position_index -= 1;
-            rank_length += 1;
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::InvalidRank(rank + 1));
+            }
+        }
+        if rank_count != 8 {
+            return Err(FenError::WrongRankCount(rank_count));
         }
 
-        let black_turn = turn_segment.eq_ignore_ascii_case("b");
+        let black_turn = match turn_segment {
+            "w" => false,
+            "b" => true,
+            _ => return Err(FenError::InvalidSideToMove(turn_segment.into())),
+        };
 
-        if !castling_segment.eq_ignore_ascii_case("-") {
-            if castling_segment.contains("K") {
-                white_king_side_castling = true;
-            }
-            if castling_segment.contains("Q") {
-                white_queen_side_castling = true;
-            }
-            if castling_segment.contains("k") {
-                black_king_side_castling = true;
-            }
-            if castling_segment.contains("q") {
-                black_queen_side_castling = true;
+        if castling_segment != "-" {
+            for char in castling_segment.chars() {
+                let right = match char {
+                    'K' => &mut white_king_side_castling,
+                    'Q' => &mut white_queen_side_castling,
+                    'k' => &mut black_king_side_castling,
+                    'q' => &mut black_queen_side_castling,
+                    _ => return Err(FenError::InvalidCastling(castling_segment.into())),
+                };
+                // Each right can only be given once
+                if *right {
+                    return Err(FenError::InvalidCastling(castling_segment.into()));
+                }
+                *right = true;
             }
         }
 
-        let ep_index = if ep_segment.eq("-") {
+        let ep_index = if ep_segment == "-" {
             u8::MAX
         } else {
-            index_from_coords(&ep_segment)
+            let mut chars = ep_segment.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some('a'..='h'), Some('1'..='8'), None) => index_from_coords(ep_segment),
+                _ => return Err(FenError::InvalidEnPassant(ep_segment.into())),
+            }
         };
 
         let mut r = BoardRep {
@@ -187,15 +247,56 @@ impl BoardRep {
             black_king_side_castling,
             ep_index,
             zorb_key: 0,
-            king_pawn_zorb: 0
+            king_pawn_zorb: 0,
+            eval_accumulator: EvalAccumulator::default(),
+            mailbox: [0; 64],
         };
 
-        r.zorb_key = ZORB_SET.hash(r);
-        r.king_pawn_zorb = PAWN_ZORB.hash(r);
-        r
+        r.mailbox = r.mailbox_from_bitboards();
+        Ok(r)
+    }
+
+    fn with_hashes(mut self) -> Self {
+        self.zorb_key = ZORB_SET.hash(self);
+        self.king_pawn_zorb = PAWN_ZORB.hash(self);
+        self.eval_accumulator = EvalAccumulator::from_board(&self);
+        self
     }
 
     pub fn get_piece_type_at_index(&self, index: u8) -> PieceType {
+        PieceType::from_u8(self.mailbox[index as usize] & MAILBOX_PIECE_MASK)
+    }
+
+    pub fn piece_at(&self, index: u8) -> Option<(PieceType, Colour)> {
+        let code = self.mailbox[index as usize];
+        if code == 0 {
+            return None;
+        }
+        Some((
+            PieceType::from_u8(code & MAILBOX_PIECE_MASK),
+            Colour::from_is_black(code & MAILBOX_BLACK != 0),
+        ))
+    }
+
+    // True when every square of the mailbox agrees with the piece and colour bitboards
+    pub fn mailbox_is_consistent(&self) -> bool {
+        self.mailbox == self.mailbox_from_bitboards()
+    }
+
+    fn mailbox_from_bitboards(&self) -> [u8; 64] {
+        let mut mailbox = [0; 64];
+        for index in 0..64 {
+            if self.occupancy.occupied(index) {
+                mailbox[index as usize] = mailbox_code(
+                    self.piece_type_from_bitboards(index),
+                    self.black_occupancy.occupied(index),
+                );
+            }
+        }
+        mailbox
+    }
+
+    fn piece_type_from_bitboards(&self, index: u8) -> PieceType {
         if index == self.white_king_position || index == self.black_king_position {
             return PieceType::King;
         }
@@ -306,7 +407,21 @@ impl BoardRep {
 
     pub fn zorb_key_after_move(&self, m: Move) -> (u64, MoveSegmentArray) {
         let segments = self.generate_move_segments(&m);
-        (ZORB_SET.apply_segments(self.zorb_key, segments), segments)
+        let rights = (
+            self.white_queen_side_castling,
+            self.white_king_side_castling,
+            self.black_queen_side_castling,
+            self.black_king_side_castling,
+        );
+        let (wqc, wkc, bqc, bkc) = segments
+            .iter()
+            .filter(|segment| segment.segment_type == MoveSegmentType::ClearCastling)
+            .fold(rights, |(wqc, wkc, bqc, bkc), segment| {
+                modify_castling(segment.index, wqc, wkc, bqc, bkc)
+            });
+        let castling_shift =
+            ZORB_SET.castling(rights.0, rights.1, rights.2, rights.3) ^ ZORB_SET.castling(wqc, wkc, bqc, bkc);
+        (ZORB_SET.apply_segments(self.zorb_key, segments) ^ castling_shift, segments)
     }
 
     fn generate_move_segments(&self, m: &Move) -> MoveSegmentArray {
@@ -314,6 +429,7 @@ impl BoardRep {
 
         let from_index = m.from();
         let to_index = m.to();
+        let piece_type = self.get_piece_type_at_index(from_index);
         let mut segment_index = 0;
 
         if self.ep_index != u8::MAX {
@@ -418,7 +534,7 @@ impl BoardRep {
             segments[segment_index] = MoveSegment::new(
                 MoveSegmentType::Pickup,
                 from_index,
-                m.piece_type(),
+                piece_type,
                 self.black_turn,
             ); // pickup piece
             segment_index += 1;
@@ -427,7 +543,7 @@ impl BoardRep {
                 let (captured_piece_type, captured_index) = if m.flags() == MF_EP_CAPTURE {
                     (
                         PieceType::Pawn,
-                        if m.is_black() {
+                        if self.black_turn {
                             self.ep_index + 8
                         } else {
                             self.ep_index - 8
@@ -458,7 +574,7 @@ impl BoardRep {
             segments[segment_index] = MoveSegment::new(
                 MoveSegmentType::Place,
                 to_index,
-                m.piece_type(),
+                piece_type,
                 self.black_turn,
             ); // place new piece
             segment_index += 1;
@@ -467,18 +583,18 @@ impl BoardRep {
                 segments[segment_index] = MoveSegment::new(
                     MoveSegmentType::DoublePawnPush,
                     (from_index + to_index) / 2,
-                    m.piece_type(),
+                    piece_type,
                     self.black_turn,
                 ); // place new piece
                 segment_index += 1;
             }
 
             // Rook or King move clear castling for that piece
-            if m.piece_type() == PieceType::Rook || m.piece_type() == PieceType::King {
+            if piece_type == PieceType::Rook || piece_type == PieceType::King {
                 segments[segment_index] = MoveSegment::new(
                     MoveSegmentType::ClearCastling,
                     from_index,
-                    m.piece_type(),
+                    piece_type,
                     self.black_turn,
                 ); // place new piece
                 segment_index += 1;
@@ -506,10 +622,19 @@ impl BoardRep {
         let mut ep_index = u8::MAX;
         let zorb_key = new_zorb_key;
         let mut king_pawn_zorb = self.king_pawn_zorb;
+        let mut eval_accumulator = self.eval_accumulator;
+        let mut mailbox = self.mailbox;
 
         for segment in segments {
             match segment.segment_type {
                 MoveSegmentType::Pickup | MoveSegmentType::Place => {
+                    if segment.segment_type == MoveSegmentType::Pickup {
+                        eval_accumulator.remove_piece(segment.piece_type, segment.index, segment.black_piece);
+                        mailbox[segment.index as usize] = 0;
+                    } else {
+                        eval_accumulator.add_piece(segment.piece_type, segment.index, segment.black_piece);
+                        mailbox[segment.index as usize] = mailbox_code(segment.piece_type, segment.black_piece);
+                    }
                     if segment.black_piece {
                         match segment.piece_type {
                             PieceType::None => {
@@ -649,7 +774,7 @@ impl BoardRep {
         }
 
         assert!(white_king_position != 255 && black_king_position != 255, "Invalid king ({white_king_position},{black_king_position}) after {segments:?}");
-        BoardRep {
+        let board = BoardRep {
             black_turn: !self.black_turn,
             occupancy,
             white_occupancy,
@@ -668,7 +793,11 @@ impl BoardRep {
             ep_index,
             zorb_key,
             king_pawn_zorb,
-        }
+            eval_accumulator,
+            mailbox,
+        };
+        debug_assert!(board.mailbox_is_consistent(), "Mailbox out of sync after {segments:?}");
+        board
     }
 
     pub(crate) fn get_white_king_analysis(&self) -> KingPositionAnalysis {
@@ -718,6 +847,28 @@ impl BoardRep {
             self.black_occupancy
         }
     }
+
+    // Neither side can ever deliver mate: bare kings, a single minor piece, or only bishops that all stand on
+    // the same colour squares
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.pawn_bitboard | self.rook_bitboard | self.queen_bitboard != 0 {
+            return false;
+        }
+        if (self.knight_bitboard | self.bishop_bitboard).count_ones() <= 1 {
+            return true;
+        }
+        self.knight_bitboard == 0
+            && (self.bishop_bitboard & DARK_SQUARES == 0 || self.bishop_bitboard & !DARK_SQUARES == 0)
+    }
+}
+
+const DARK_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
+const MAILBOX_PIECE_MASK: u8 = 0b0111;
+const MAILBOX_BLACK: u8 = 0b1000;
+
+// 0 is an empty square, otherwise the piece type with the colour in the 4th bit
+fn mailbox_code(piece_type: PieceType, is_black: bool) -> u8 {
+    piece_type as u8 | if is_black { MAILBOX_BLACK } else { 0 }
 }
 
 impl Default for BoardRep {
@@ -741,23 +892,18 @@ impl Default for BoardRep {
             black_turn: false,
             zorb_key: 0,
             king_pawn_zorb: 0,
+            eval_accumulator: EvalAccumulator::default(),
+            mailbox: [0; 64],
         };
+        board.mailbox = board.mailbox_from_bitboards();
         board.zorb_key = ZORB_SET.hash(board);
         board.king_pawn_zorb = PAWN_ZORB.hash(board);
+        board.eval_accumulator = EvalAccumulator::from_board(&board);
 
         board
     }
 }
 
-fn flip_piece_i8(
-    index: i8,
-    piece_bitboard: u64,
-    colour_bitboard: u64,
-    all_bitboard: u64,
-) -> (u64, u64, u64) {
-    flip_piece(index as u8, piece_bitboard, colour_bitboard, all_bitboard)
-}
-
 fn flip_piece(
     index: u8,
     piece_bitboard: u64,
@@ -844,7 +990,7 @@ mod test {
     #[test]
     pub fn generate_move_segments_start_pos_e4() {
         let position = BoardRep::default();
-        let m = Move::new(11, 27, MF_DOUBLE_PAWN_PUSH, PieceType::Pawn, false, 0,0);
+        let m = Move::new(11, 27, MF_DOUBLE_PAWN_PUSH);
 
         let segments = position.generate_move_segments(&m);
 
@@ -871,7 +1017,7 @@ mod test {
         let position = BoardRep::from_fen(
             "rnbqk2r/pppp1ppp/5n2/2b1p3/2B1P3/2NP4/PPP2PPP/R1BQK1NR b KQkq -".to_string(),
         );
-        let m = Move::new(59, 57, MF_KING_CASTLING, PieceType::King, true, 0,0);
+        let m = Move::new(59, 57, MF_KING_CASTLING);
 
         let segments = position.generate_move_segments(&m);
 
@@ -904,7 +1050,7 @@ mod test {
         let position = BoardRep::from_fen(
             "rnbqk2r/pppp1ppp/5n2/2b1p3/2B1P3/2NP4/PPP2PPP/R1BQK1NR w KQkq -".to_string(),
         );
-        let m = Move::new(7, 6, 0b0, PieceType::Rook, true, 0,0);
+        let m = Move::new(7, 6, 0b0);
 
         let segments = position.generate_move_segments(&m);
 
@@ -929,7 +1075,7 @@ mod test {
     #[test]
     pub fn generate_move_segments_white_captures_black_rook_clearing_kingside_castling() {
         let position = BoardRep::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq -".to_string());
-        let m = Move::new(63, 7, MF_CAPTURE, PieceType::Rook, false, 2,0);
+        let m = Move::new(63, 7, MF_CAPTURE);
 
         let segments = position.generate_move_segments(&m);
 
@@ -963,26 +1109,100 @@ mod test {
         let position = BoardRep::from_fen(
             "rnbqkbnr/pppp1ppp/4p3/8/2B5/4P3/PPPP1PPP/RNBQK1NR b KQkq -".to_string(),
         );
-        let m = Move::new(56, 48, 0b0, PieceType::King, true, 0,0);
+        let m = Move::new(59, 51, 0b0);
 
         let segments = position.generate_move_segments(&m);
 
         assert_eq!(
             segments[0],
-            MoveSegment::new(MoveSegmentType::Pickup, 56, PieceType::King, true)
+            MoveSegment::new(MoveSegmentType::Pickup, 59, PieceType::King, true)
         );
 
         assert_eq!(
             segments[1],
-            MoveSegment::new(MoveSegmentType::Place, 48, PieceType::King, true)
+            MoveSegment::new(MoveSegmentType::Place, 51, PieceType::King, true)
         );
 
         assert_eq!(
             segments[2],
-            MoveSegment::new(MoveSegmentType::ClearCastling, 56, PieceType::King, true)
+            MoveSegment::new(MoveSegmentType::ClearCastling, 59, PieceType::King, true)
         );
 
         assert_eq!(segments[3], MoveSegment::default());
         assert_eq!(segments[4], MoveSegment::default());
     }
+
+    #[test]
+    pub fn insufficient_material_cases() {
+        assert!(BoardRep::from_fen("8/8/4k3/8/8/3K4/8/8 w - -".into()).is_insufficient_material());
+        assert!(BoardRep::from_fen("8/8/4k3/8/8/3KN3/8/8 w - -".into()).is_insufficient_material());
+        assert!(BoardRep::from_fen("8/8/4kb2/8/8/3K4/8/8 w - -".into()).is_insufficient_material());
+        assert!(!BoardRep::from_fen("8/8/4kb2/8/8/3KN3/8/8 w - -".into()).is_insufficient_material());
+        assert!(!BoardRep::from_fen("8/8/4k3/8/8/3KP3/8/8 w - -".into()).is_insufficient_material());
+        assert!(BoardRep::from_fen("8/8/4kb2/8/8/3KB3/8/8 w - -".into()).is_insufficient_material());
+        assert!(BoardRep::from_fen("B7/1B6/4k3/8/8/3K4/8/7B w - -".into()).is_insufficient_material());
+        assert!(!BoardRep::from_fen("8/8/4k1b1/8/8/3KB3/8/8 w - -".into()).is_insufficient_material());
+        assert!(!BoardRep::from_fen("8/8/4kb2/8/8/3KBN2/8/8 w - -".into()).is_insufficient_material());
+    }
+
+    #[test]
+    pub fn piece_at_reads_the_mailbox() {
+        let board = BoardRep::default();
+        assert_eq!(board.piece_at(3), Some((PieceType::King, Colour::White)));
+        assert_eq!(board.piece_at(52), Some((PieceType::Pawn, Colour::Black)));
+        assert_eq!(board.piece_at(60), Some((PieceType::Queen, Colour::Black)));
+        assert_eq!(board.piece_at(27), None);
+        assert_eq!(board.get_piece_type_at_index(27), PieceType::None);
+        assert!(board.mailbox_is_consistent());
+    }
+
+    #[test]
+    pub fn zorb_key_after_move_matches_a_full_hash() {
+        use crate::{move_generation::generate_moves_for_board, r#move::move_list::MoveList};
+
+        let fens = [
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kq -",
+            "4k3/8/8/8/8/8/8/4K2R w - -",
+        ];
+        for fen in fens {
+            let board = BoardRep::from_fen(fen.into());
+            let mut moves = MoveList::new();
+            generate_moves_for_board(board, &mut moves);
+            for m in moves.iter().map(|s| s.m) {
+                let (zorb, segments) = board.zorb_key_after_move(m);
+                let after = board.apply_segments(segments, zorb);
+                assert_eq!(zorb, ZORB_SET.hash(after), "{fen} {m:?}");
+
+                let mut replies = MoveList::new();
+                generate_moves_for_board(after, &mut replies);
+                for reply in replies.iter().map(|s| s.m) {
+                    let (zorb, segments) = after.zorb_key_after_move(reply);
+                    assert_eq!(zorb, ZORB_SET.hash(after.apply_segments(segments, zorb)), "{fen} {m:?} {reply:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn mailbox_follows_captures_castling_en_passant_and_promotion() {
+        let mut board =
+            BoardRep::from_fen("r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -".into());
+        let moves = [
+            Move::new(index_from_coords("a2"), index_from_coords("a4"), MF_DOUBLE_PAWN_PUSH),
+            Move::new(index_from_coords("b4"), index_from_coords("a3"), MF_EP_CAPTURE),
+            Move::new(index_from_coords("b7"), index_from_coords("a8"), MF_QUEEN_CAPTURE_PROMOTION),
+            Move::new(index_from_coords("e8"), index_from_coords("g8"), MF_KING_CASTLING),
+            Move::new(index_from_coords("e1"), index_from_coords("c1"), MF_QUEEN_CASTLING),
+        ];
+        for m in moves {
+            let (zorb, segments) = board.zorb_key_after_move(m);
+            board = board.apply_segments(segments, zorb);
+            assert!(board.mailbox_is_consistent(), "{m:?} -> {}", board.to_fen());
+        }
+        assert_eq!(board.piece_at(index_from_coords("a8")), Some((PieceType::Queen, Colour::White)));
+        assert_eq!(board.piece_at(index_from_coords("a4")), None);
+        assert_eq!(board.piece_at(index_from_coords("g8")), Some((PieceType::King, Colour::Black)));
+        assert_eq!(board.piece_at(index_from_coords("d1")), Some((PieceType::Rook, Colour::White)));
+    }
 }
@@ -0,0 +1,279 @@
+use std::{error::Error, fmt::Display, str::SplitWhitespace};
+
+use crate::shared::{
+    board_utils::{get_coords_from_index, get_rank},
+    piece_type::Colour,
+};
+
+use super::{bitboard::Bitboard, board_rep::BoardRep};
+
+const BACK_RANKS: u64 = 0xFF000000000000FF;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    TooManyFields,
+    WrongRankCount(usize),
+    // Rank (1-8) that doesn't describe exactly 8 squares
+    InvalidRank(u8),
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfMoveClock(String),
+    InvalidFullMoveNumber(String),
+    HalfMoveClockAfterDoublePush(u8),
+    KingCount(Colour, u32),
+    TooManyPieces(Colour),
+    PawnOnBackRank(String),
+    CastlingWithoutKingAndRook(char),
+    ImplausibleEnPassant(String),
+    OpponentInCheck,
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {field}"),
+            FenError::TooManyFields => write!(f, "FEN has more than 6 fields"),
+            FenError::WrongRankCount(count) => write!(f, "Expected 8 ranks but found {count}"),
+            FenError::InvalidRank(rank) => write!(f, "Rank {rank} doesn't have 8 squares"),
+            FenError::InvalidPiece(c) => write!(f, "Unknown piece '{c}'"),
+            FenError::InvalidSideToMove(s) => write!(f, "Invalid side to move '{s}'"),
+            FenError::InvalidCastling(s) => write!(f, "Invalid castling rights '{s}'"),
+            FenError::InvalidEnPassant(s) => write!(f, "Invalid en passant square '{s}'"),
+            FenError::InvalidHalfMoveClock(s) => write!(f, "Invalid half move clock '{s}'"),
+            FenError::InvalidFullMoveNumber(s) => write!(f, "Invalid full move number '{s}'"),
+            FenError::HalfMoveClockAfterDoublePush(half_moves) => write!(
+                f,
+                "Half move clock is {half_moves} but an en passant square means a pawn just moved"
+            ),
+            FenError::KingCount(colour, count) => {
+                write!(f, "{colour:?} has {count} kings")
+            }
+            FenError::TooManyPieces(colour) => write!(f, "{colour:?} has too many pieces"),
+            FenError::PawnOnBackRank(square) => write!(f, "Pawn on back rank at {square}"),
+            FenError::CastlingWithoutKingAndRook(c) => write!(
+                f,
+                "Castling right '{c}' without the king and rook on their starting squares"
+            ),
+            FenError::ImplausibleEnPassant(square) => write!(
+                f,
+                "En passant square {square} doesn't follow a double pawn push"
+            ),
+            FenError::OpponentInCheck => write!(f, "The side not to move is in check"),
+        }
+    }
+}
+
+impl Error for FenError {}
+
+// The whitespace separated fields of a FEN, the move counters are optional and default to the start of a game
+pub(crate) struct FenFields<'a> {
+    pub placement: &'a str,
+    pub turn: &'a str,
+    pub castling: &'a str,
+    pub ep: &'a str,
+    pub half_moves: u8,
+    pub full_moves: u32,
+}
+
+impl<'a> FenFields<'a> {
+    pub fn split(fen: &'a str) -> Result<Self, FenError> {
+        let mut segments = fen.split_whitespace();
+        let placement = next_field(&mut segments, "piece placement")?;
+        let turn = next_field(&mut segments, "side to move")?;
+        let castling = next_field(&mut segments, "castling rights")?;
+        let ep = next_field(&mut segments, "en passant square")?;
+
+        let half_moves = match segments.next() {
+            Some(s) => s
+                .parse::<u8>()
+                .map_err(|_| FenError::InvalidHalfMoveClock(s.into()))?,
+            None => 0,
+        };
+        let full_moves = match segments.next() {
+            Some(s) => match s.parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(FenError::InvalidFullMoveNumber(s.into())),
+            },
+            None => 1,
+        };
+        if segments.next().is_some() {
+            return Err(FenError::TooManyFields);
+        }
+        if ep != "-" && half_moves != 0 {
+            return Err(FenError::HalfMoveClockAfterDoublePush(half_moves));
+        }
+
+        Ok(Self {
+            placement,
+            turn,
+            castling,
+            ep,
+            half_moves,
+            full_moves,
+        })
+    }
+}
+
+fn next_field<'a>(
+    segments: &mut SplitWhitespace<'a>,
+    name: &'static str,
+) -> Result<&'a str, FenError> {
+    segments.next().ok_or(FenError::MissingField(name))
+}
+
+impl BoardRep {
+    // Checks that the position could have come from a legal game, as far as can be told without its history
+    pub fn validate(&self) -> Result<(), FenError> {
+        let non_king = self.pawn_bitboard
+            | self.knight_bitboard
+            | self.bishop_bitboard
+            | self.rook_bitboard
+            | self.queen_bitboard;
+        for colour in [Colour::White, Colour::Black] {
+            let kings = (self.colour_occupancy(colour) & !non_king).count_ones();
+            if kings != 1 {
+                return Err(FenError::KingCount(colour, kings));
+            }
+        }
+
+        let back_rank_pawns = self.pawn_bitboard & BACK_RANKS;
+        if back_rank_pawns != 0 {
+            return Err(FenError::PawnOnBackRank(get_coords_from_index(
+                back_rank_pawns.trailing_zeros() as u8,
+            )));
+        }
+
+        for colour in [Colour::White, Colour::Black] {
+            let occupancy = self.colour_occupancy(colour);
+            let count = |bitboard: u64| (bitboard & occupancy).count_ones();
+            let pawns = count(self.pawn_bitboard);
+            // Anything beyond the starting set has to have been promoted from a pawn
+            let promoted = count(self.knight_bitboard).saturating_sub(2)
+                + count(self.bishop_bitboard).saturating_sub(2)
+                + count(self.rook_bitboard).saturating_sub(2)
+                + count(self.queen_bitboard).saturating_sub(1);
+            if pawns > 8 || occupancy.count_ones() > 16 || promoted > 8 - pawns {
+                return Err(FenError::TooManyPieces(colour));
+            }
+        }
+
+        let castling_rights = [
+            ('K', self.white_king_side_castling, 3, 0, Colour::White),
+            ('Q', self.white_queen_side_castling, 3, 7, Colour::White),
+            ('k', self.black_king_side_castling, 59, 56, Colour::Black),
+            ('q', self.black_queen_side_castling, 59, 63, Colour::Black),
+        ];
+        for (c, has_right, king_index, rook_index, colour) in castling_rights {
+            let king_position = if colour.is_black() {
+                self.black_king_position
+            } else {
+                self.white_king_position
+            };
+            let rook_in_place =
+                (self.rook_bitboard & self.colour_occupancy(colour)).occupied(rook_index);
+            if has_right && (king_position != king_index || !rook_in_place) {
+                return Err(FenError::CastlingWithoutKingAndRook(c));
+            }
+        }
+
+        if self.ep_index != u8::MAX && !self.is_plausible_ep_index() {
+            return Err(FenError::ImplausibleEnPassant(get_coords_from_index(
+                self.ep_index,
+            )));
+        }
+
+        let opponent_in_check = if self.black_turn {
+            self.get_white_king_analysis().check
+        } else {
+            self.get_black_king_analysis().check
+        };
+        if opponent_in_check {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        Ok(())
+    }
+
+    fn colour_occupancy(&self, colour: Colour) -> u64 {
+        if colour.is_black() {
+            self.black_occupancy
+        } else {
+            self.white_occupancy
+        }
+    }
+
+    // The side not to move must have just pushed a pawn two squares, passing over the en passant square
+    fn is_plausible_ep_index(&self) -> bool {
+        let (ep_rank, pushed_from, pushed_to, pusher) = if self.black_turn {
+            (2, 1, 3, Colour::White)
+        } else {
+            (5, 6, 4, Colour::Black)
+        };
+        if get_rank(self.ep_index) != ep_rank {
+            return false;
+        }
+        let file_offset = self.ep_index % 8;
+        let pushed_from = pushed_from * 8 + file_offset;
+        let pushed_to = pushed_to * 8 + file_offset;
+
+        (self.pawn_bitboard & self.colour_occupancy(pusher)).occupied(pushed_to)
+            && !self.occupancy.occupied(self.ep_index)
+            && !self.occupancy.occupied(pushed_from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{board::board_rep::BoardRep, shared::piece_type::Colour};
+
+    use super::FenError;
+
+    #[test]
+    pub fn try_from_fen_accepts_legal_positions() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+            "8/8/4k3/8/8/3K4/8/7R b - - 12 40",
+        ] {
+            let board = BoardRep::try_from_fen(fen).unwrap();
+            assert!(board == BoardRep::from_fen(fen.into()), "{fen}");
+        }
+    }
+
+    #[test]
+    pub fn try_from_fen_rejects_invalid_positions() {
+        let cases = [
+            ("", FenError::MissingField("piece placement")),
+            ("8/8/8/8/8/8/8/8 w", FenError::MissingField("castling rights")),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::WrongRankCount(7)),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::InvalidRank(1)),
+            ("4k3/8/8/8/8/8/8/4X3 w - - 0 1", FenError::InvalidPiece('X')),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidSideToMove("x".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w KK - 0 1", FenError::InvalidCastling("KK".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9 0 1", FenError::InvalidEnPassant("e9".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::InvalidHalfMoveClock("x".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenError::InvalidFullMoveNumber("0".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x", FenError::TooManyFields),
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::KingCount(Colour::Black, 0)),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::KingCount(Colour::White, 2)),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", FenError::PawnOnBackRank("a1".into())),
+            ("4k3/pppppppp/p7/8/8/8/8/4K3 w - - 0 1", FenError::TooManyPieces(Colour::Black)),
+            (
+                "4k3/8/8/8/8/8/PPPPPPPP/QNBQKBNR w - - 0 1",
+                FenError::TooManyPieces(Colour::White),
+            ),
+            ("4k3/8/8/8/8/8/8/4K2R w Q - 0 1", FenError::CastlingWithoutKingAndRook('Q')),
+            ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", FenError::ImplausibleEnPassant("e3".into())),
+            ("4k3/8/8/8/8/8/8/4K3 b - e3 0 1", FenError::ImplausibleEnPassant("e3".into())),
+            ("4k3/8/8/8/4P3/8/8/4K3 b - e3 3 1", FenError::HalfMoveClockAfterDoublePush(3)),
+            ("4k3/8/8/8/8/8/8/r3K3 b - - 0 1", FenError::OpponentInCheck),
+        ];
+        for (fen, expected) in cases {
+            assert_eq!(BoardRep::try_from_fen(fen).err(), Some(expected), "{fen}");
+        }
+    }
+}
@@ -2,5 +2,6 @@ pub mod position;
 pub mod bitboard;
 pub mod king_position_analysis;
 pub mod board_rep;
+pub mod fen;
 pub mod see;
 pub mod attack_and_defend_lookups;
\ No newline at end of file
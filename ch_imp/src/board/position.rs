@@ -1,21 +1,20 @@
 use crate::{
-    evaluation::{self},
+    evaluation::{self, pawn_structure::PawnStructureCache},
     r#move::move_segment::MoveSegment,
     search::zorb_set_precomputed::ZORB_SET,
 };
 use std::fmt::Debug;
 
-use super::board_rep::BoardRep;
+use super::{board_rep::BoardRep, fen::FenError};
 
 pub type MoveSegmentArray = [MoveSegment; 6];
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Default, PartialEq)]
 pub struct Position {
     pub board: BoardRep,
     pub white_in_check: bool,
     pub black_in_check: bool,
     pub double_check: bool,
-    pub eval: i16,
 }
 
 impl Position {
@@ -41,6 +40,10 @@ impl Position {
         Position::new(position_segment, turn_segment, castling_segment, ep_segment)
     }
 
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        Ok(Self::build(BoardRep::try_from_fen(fen)?))
+    }
+
     pub(crate) fn apply_segments(
         &self,
         move_segments: [MoveSegment; 6],
@@ -51,7 +54,7 @@ impl Position {
         Self::build(board)
     }
 
-    fn build(board: BoardRep) -> Self {
+    pub(crate) fn build(board: BoardRep) -> Self {
         if board.black_king_position == 255 || board.white_king_position == 255 {
             panic!(
                 "Invalid king position {} / {}",
@@ -60,9 +63,6 @@ impl Position {
         }
         let white_king_analysis = board.get_white_king_analysis();
         let black_king_analysis = board.get_black_king_analysis();
-        let mut eval = if board.black_turn { -1 } else { 1 };
-
-        eval *= evaluation::calculate(board, white_king_analysis.check,black_king_analysis.check,black_king_analysis.pins, white_king_analysis.pins);
 
         Self {
             board,
@@ -73,7 +73,26 @@ impl Position {
             } else {
                 white_king_analysis.double_check
             },
-            eval,
+        }
+    }
+
+    // Static evaluation from the perspective of the side to move. Not stored on the position as most positions
+    // created during search (and all of those created by perft or move generation) never need it
+    pub fn evaluate(&self, pawn_cache: &mut PawnStructureCache) -> i16 {
+        let white_king_analysis = self.board.get_white_king_analysis();
+        let black_king_analysis = self.board.get_black_king_analysis();
+        let eval = evaluation::calculate(
+            self.board,
+            self.white_in_check,
+            self.black_in_check,
+            black_king_analysis.pins,
+            white_king_analysis.pins,
+            pawn_cache,
+        );
+        if self.board.black_turn {
+            -eval
+        } else {
+            eval
         }
     }
 
@@ -91,18 +110,3 @@ impl Debug for Position {
             .finish()
     }
 }
-
-impl Default for Position {
-    fn default() -> Self {
-        let board = BoardRep::default();
-        let eval = evaluation::calculate(board,  false, false, Vec::new(), Vec::new());
-
-        Self {
-            board,
-            black_in_check: false,
-            white_in_check: false,
-            double_check: false,
-            eval,
-        }
-    }
-}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use crate::{
+    match_state::game_state::MatchResultState,
+    pgn::{reader::read_pgn_games, PgnError, PgnGame},
+    r#move::Move,
+    search::polyglot::polyglot_key,
+    shared::{
+        board_utils::get_file,
+        piece_type::{Colour, PieceType},
+    },
+};
+
+use super::BookEntry;
+
+// How a move did in the games it was played in, from the point of view of the side that played it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    // In half points, so a win is 2 and a draw 1
+    pub fn score(&self) -> u32 {
+        self.wins * 2 + self.draws
+    }
+}
+
+// Collects move statistics from games and turns them into Polyglot book entries
+pub struct BookBuilder {
+    // Moves played in fewer games than this are left out of the book
+    pub min_games: u32,
+    // Only the first `max_ply` moves of each game are used
+    pub max_ply: usize,
+    // Only moves played by this side
+    pub side: Option<Colour>,
+    // Only moves played by this player, matched against the White and Black tags
+    pub player: Option<String>,
+    // Games with any other result are skipped
+    pub results: Vec<MatchResultState>,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        Self {
+            min_games: 1,
+            max_ply: 40,
+            side: None,
+            player: None,
+            results: vec![
+                MatchResultState::WhiteVictory,
+                MatchResultState::Draw,
+                MatchResultState::BlackVictory,
+            ],
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+}
+
+impl BookBuilder {
+    // Games used so far
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    // Returns the number of games that passed the filters and the number skipped because they couldn't be read or
+    // replayed. Bad games don't stop the rest of the file being used
+    pub fn add_pgn(&mut self, pgn: &str) -> (usize, usize) {
+        let mut added = 0;
+        let mut skipped = 0;
+        for game in read_pgn_games(pgn) {
+            match game.and_then(|game| self.add_game(&game)) {
+                Ok(true) => added += 1,
+                Ok(false) => {}
+                Err(_) => skipped += 1,
+            }
+        }
+        (added, skipped)
+    }
+
+    // Replays the mainline, returns false if the game was filtered out
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<bool, PgnError> {
+        if !self.results.contains(&game.result) {
+            return Ok(false);
+        }
+        let mut sides = match self.side {
+            Some(colour) => vec![colour],
+            None => vec![Colour::White, Colour::Black],
+        };
+        if let Some(player) = &self.player {
+            sides.retain(|colour| {
+                let tag = if *colour == Colour::White { "White" } else { "Black" };
+                game.tag(tag) == Some(player.as_str())
+            });
+        }
+        if sides.is_empty() {
+            return Ok(false);
+        }
+
+        let mut game_state = game.start_state()?;
+        for pgn_move in game.mainline.moves.iter().take(self.max_ply) {
+            let colour = if game_state.position.board.black_turn {
+                Colour::Black
+            } else {
+                Colour::White
+            };
+            if sides.contains(&colour) {
+                let key = polyglot_key(&game_state.position.board);
+                let stats = self.stats.entry((key, encode_move(pgn_move.m))).or_default();
+                stats.games += 1;
+                match (game.result, colour) {
+                    (MatchResultState::Draw, _) => stats.draws += 1,
+                    (MatchResultState::WhiteVictory, Colour::White) | (MatchResultState::BlackVictory, Colour::Black) => {
+                        stats.wins += 1
+                    }
+                    (MatchResultState::WhiteVictory, _) | (MatchResultState::BlackVictory, _) => stats.losses += 1,
+                    (MatchResultState::Active, _) => {}
+                }
+            }
+            game_state = game_state.make(pgn_move.m);
+        }
+        self.games += 1;
+        Ok(true)
+    }
+
+    pub fn stats(&self, key: u64, raw_move: u16) -> Option<MoveStats> {
+        self.stats.get(&(key, raw_move)).copied()
+    }
+
+    // Sorted by key and then by weight, best first, as Polyglot expects. Weights are the score in half points,
+    // scaled down for a position when they don't fit
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let mut positions: HashMap<u64, Vec<(u16, MoveStats)>> = HashMap::new();
+        for (&(key, raw_move), &stats) in &self.stats {
+            if stats.games >= self.min_games {
+                positions.entry(key).or_default().push((raw_move, stats));
+            }
+        }
+
+        let mut r = Vec::new();
+        for (key, moves) in positions {
+            let max_score = moves.iter().map(|(_, stats)| stats.score()).max().unwrap_or(0);
+            let scale = (max_score as f64 / u16::MAX as f64).max(1.0);
+            for (raw_move, stats) in moves {
+                let weight = match stats.score() {
+                    0 => 0,
+                    score => ((score as f64 / scale) as u16).max(1),
+                };
+                r.push(BookEntry {
+                    key,
+                    raw_move,
+                    weight,
+                    learn: 0,
+                });
+            }
+        }
+        r.sort_by(|a, b| {
+            a.key
+                .cmp(&b.key)
+                .then(b.weight.cmp(&a.weight))
+                .then(a.raw_move.cmp(&b.raw_move))
+        });
+        r
+    }
+
+    // The book in Polyglot .bin format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries().iter().flat_map(|entry| entry.to_bytes()).collect()
+    }
+}
+
+// Polyglot moves are to file, to rank, from file, from rank and the promotion piece in 3 bits each. Castling is
+// written as the king taking its own rook
+pub fn encode_move(m: Move) -> u16 {
+    let square = |index: u8| ((index / 8) as u16) << 3 | get_file(index) as u16;
+    let to = if m.is_king_castling() {
+        m.to() - 1
+    } else if m.is_castling() {
+        m.to() + 2
+    } else {
+        m.to()
+    };
+    let promotion = match m.promotion_piece_type() {
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        _ => 0,
+    };
+    promotion << 12 | square(m.from()) << 6 | square(to)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode_move, BookBuilder};
+    use crate::{
+        book::{BookSelection, PolyglotBook},
+        match_state::game_state::{GameState, MatchResultState},
+        search::polyglot::polyglot_key,
+        shared::piece_type::Colour,
+    };
+
+    const GAMES: &str = concat!(
+        "[White \"chimp\"]\n[Black \"Other\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0\n\n",
+        "[White \"Other\"]\n[Black \"chimp\"]\n[Result \"1/2-1/2\"]\n\n1. e4 c5 2. Nf3 d6 1/2-1/2\n\n",
+        "[White \"chimp\"]\n[Black \"Other\"]\n[Result \"0-1\"]\n\n1. d4 d5 2. c4 0-1\n\n",
+        "[White \"chimp\"]\n[Black \"Other\"]\n[Result \"*\"]\n\n1. c4 *\n",
+    );
+
+    fn raw(game_state: &GameState, uci: &str) -> u16 {
+        encode_move(game_state.parse_uci_move(uci).unwrap())
+    }
+
+    #[test]
+    pub fn encode_move_uses_polyglot_squares_and_castling() {
+        let game_state = GameState::new("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1".into());
+        assert_eq!(raw(&game_state, "e1g1"), raw(&game_state, "e1f1") + 2);
+        assert_eq!(raw(&game_state, "e1g1"), 4 << 6 | 7);
+        assert_eq!(raw(&game_state, "e1c1"), 4 << 6);
+        assert_eq!(raw(&game_state, "b7b8q"), 4 << 12 | (6 << 3 | 1) << 6 | (7 << 3 | 1));
+        assert_eq!(raw(&GameState::default(), "g1f3"), 6 << 6 | 2 << 3 | 5);
+    }
+
+    #[test]
+    pub fn builds_stats_and_weights_from_results() {
+        let start = GameState::default();
+        let mut builder = BookBuilder::default();
+        assert_eq!(builder.add_pgn(GAMES), (3, 0));
+
+        let key = polyglot_key(&start.position.board);
+        let e4 = builder.stats(key, raw(&start, "e2e4")).unwrap();
+        assert_eq!((e4.games, e4.wins, e4.draws, e4.losses), (2, 1, 1, 0));
+        let d4 = builder.stats(key, raw(&start, "d2d4")).unwrap();
+        assert_eq!((d4.games, d4.losses), (1, 1));
+        assert!(builder.stats(key, raw(&start, "c2c4")).is_none());
+
+        // The written book reads back with the best scoring move first
+        let book = PolyglotBook::from_bytes(&builder.to_bytes()).unwrap();
+        let moves: Vec<(String, u16)> = book.moves(&start).into_iter().map(|(m, w)| (m.uci(), w)).collect();
+        assert_eq!(moves, vec![("e2e4".to_string(), 3), ("d2d4".to_string(), 0)]);
+        let after_e4 = start.make(start.parse_uci_move("e2e4").unwrap());
+        assert_eq!(book.moves(&after_e4).len(), 2);
+        let mut rng = rand::thread_rng();
+        assert_eq!(book.choose(&start, BookSelection::WeightedRandom, &mut rng).unwrap().uci(), "e2e4");
+    }
+
+    #[test]
+    pub fn bad_games_are_skipped() {
+        let bad = "[Result \"1-0\"]\n\n1. e4 e5 2. Ke3 1-0\n\n[Result \"1-0\"]\n\n1. e4 & 1-0\n\n";
+        let mut builder = BookBuilder::default();
+        assert_eq!(builder.add_pgn(&format!("{bad}{GAMES}")), (3, 2));
+        assert_eq!(builder.games(), 3);
+        let start = GameState::default();
+        let e4 = builder.stats(polyglot_key(&start.position.board), raw(&start, "e2e4")).unwrap();
+        assert_eq!(e4.games, 2);
+    }
+
+    #[test]
+    pub fn filters() {
+        let start = GameState::default();
+        let key = polyglot_key(&start.position.board);
+
+        let mut builder = BookBuilder {
+            min_games: 2,
+            ..Default::default()
+        };
+        builder.add_pgn(GAMES);
+        let entries = builder.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].key, entries[0].raw_move), (key, raw(&start, "e2e4")));
+
+        let mut builder = BookBuilder {
+            max_ply: 1,
+            side: Some(Colour::Black),
+            ..Default::default()
+        };
+        builder.add_pgn(GAMES);
+        assert!(builder.entries().is_empty());
+
+        let mut builder = BookBuilder {
+            player: Some("chimp".into()),
+            results: vec![MatchResultState::WhiteVictory, MatchResultState::Draw],
+            ..Default::default()
+        };
+        assert_eq!(builder.add_pgn(GAMES), (2, 0));
+        // chimp's moves only, so white's moves from the drawn game are left out
+        assert_eq!(builder.stats(key, raw(&start, "e2e4")).unwrap().games, 1);
+        let after_e4 = start.make(start.parse_uci_move("e2e4").unwrap());
+        let c5 = builder.stats(polyglot_key(&after_e4.position.board), raw(&after_e4, "c7c5")).unwrap();
+        assert_eq!((c5.games, c5.draws), (1, 1));
+        assert!(builder.stats(key, raw(&start, "d2d4")).is_none());
+    }
+}
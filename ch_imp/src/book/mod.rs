@@ -0,0 +1,251 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use rand::Rng;
+
+use crate::{
+    match_state::game_state::GameState,
+    move_generation::generate_moves_for_board,
+    r#move::{move_list::MoveList, Move},
+    search::polyglot::polyglot_key,
+    shared::{board_utils::get_index_from_file_and_rank, piece_type::PieceType},
+};
+
+pub mod builder;
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookSelection {
+    // Always the move with the highest weight
+    BestMove,
+    // Moves are picked in proportion to their weights, so the engine doesn't play the same opening every game
+    WeightedRandom,
+}
+
+// One 16 byte record of a Polyglot .bin file, stored big endian on disk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut r = [0; ENTRY_SIZE];
+        r[0..8].copy_from_slice(&self.key.to_be_bytes());
+        r[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        r[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        r[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        r
+    }
+
+    // The move from the legal moves of `game_state` this entry stands for, if there is one
+    fn resolve(&self, game_state: &GameState, legal_moves: &MoveList) -> Option<Move> {
+        let m = self.raw_move as u32;
+        let (to_file, to_rank) = (m & 7, (m >> 3) & 7);
+        let (from_file, from_rank) = ((m >> 6) & 7, (m >> 9) & 7);
+        let promotion = match (m >> 12) & 7 {
+            0 => PieceType::None,
+            1 => PieceType::Knight,
+            2 => PieceType::Bishop,
+            3 => PieceType::Rook,
+            4 => PieceType::Queen,
+            _ => return None,
+        };
+
+        let from = get_index_from_file_and_rank(from_file as u8, from_rank as u8);
+        // Castling is written as the king taking its own rook
+        let is_king = game_state.position.board.get_piece_type_at_index(from) == PieceType::King;
+        let to_file = match (is_king, from_file, to_file) {
+            (true, 4, 7) if from_rank == to_rank => 6,
+            (true, 4, 0) if from_rank == to_rank => 2,
+            _ => to_file,
+        };
+        let to = get_index_from_file_and_rank(to_file as u8, to_rank as u8);
+
+        legal_moves
+            .iter()
+            .map(|s| s.m)
+            .find(|m| m.from() == from && m.to() == to && m.promotion_piece_type() == promotion)
+    }
+}
+
+// A Polyglot opening book, with the entries sorted by key so a position's moves are found by binary search
+pub struct PolyglotBook {
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{} bytes isn't a whole number of book entries", bytes.len()),
+            ));
+        }
+        let mut entries: Vec<BookEntry> = bytes.chunks_exact(ENTRY_SIZE).map(BookEntry::from_bytes).collect();
+        // Books should already be sorted, a stable sort keeps the order of each position's moves if they are
+        entries.sort_by_key(|entry| entry.key);
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    // The book's moves for this position with their weights. Entries that aren't legal here, from a corrupt book
+    // or a key collision, are left out
+    pub fn moves(&self, game_state: &GameState) -> Vec<(Move, u16)> {
+        let entries = self.entries(polyglot_key(&game_state.position.board));
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(game_state.position.board, &mut legal_moves);
+        entries
+            .iter()
+            .filter_map(|entry| Some((entry.resolve(game_state, &legal_moves)?, entry.weight)))
+            .collect()
+    }
+
+    // Moves with no weight are in the book to be avoided so they're never chosen
+    pub fn choose<R: Rng>(&self, game_state: &GameState, selection: BookSelection, rng: &mut R) -> Option<Move> {
+        let moves: Vec<(Move, u16)> = self.moves(game_state).into_iter().filter(|(_, weight)| *weight > 0).collect();
+        match selection {
+            BookSelection::BestMove => moves
+                .iter()
+                .rev()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(m, _)| *m),
+            BookSelection::WeightedRandom => {
+                let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = rng.gen_range(0..total);
+                for (m, weight) in moves {
+                    if pick < weight as u32 {
+                        return Some(m);
+                    }
+                    pick -= weight as u32;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{BookSelection, PolyglotBook};
+    use crate::{match_state::game_state::GameState, search::polyglot::polyglot_key};
+
+    // Polyglot moves are to file, to rank, from file, from rank and promotion in 3 bits each
+    fn raw_move(uci: &str) -> u16 {
+        let b = uci.as_bytes();
+        let square = |file: u8, rank: u8| ((rank - b'1') as u16) << 3 | (file - b'a') as u16;
+        let promotion = match b.get(4) {
+            Some(b'q') => 4,
+            Some(b'n') => 1,
+            _ => 0,
+        };
+        promotion << 12 | square(b[0], b[1]) << 6 | square(b[2], b[3])
+    }
+
+    pub(crate) fn book_bytes(entries: &[(&GameState, &str, u16)]) -> Vec<u8> {
+        let mut r = Vec::new();
+        for (game_state, uci, weight) in entries {
+            r.extend(polyglot_key(&game_state.position.board).to_be_bytes());
+            r.extend(raw_move(uci).to_be_bytes());
+            r.extend(weight.to_be_bytes());
+            r.extend(0u32.to_be_bytes());
+        }
+        r
+    }
+
+    #[test]
+    pub fn moves_are_resolved_against_the_legal_moves() {
+        let start = GameState::default();
+        let castling = GameState::new("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".into());
+        let promotion = GameState::new("8/P6k/8/8/8/8/8/K7 w - - 0 1".into());
+        let book = PolyglotBook::from_bytes(&book_bytes(&[
+            (&castling, "e1h1", 3),
+            (&start, "e2e4", 10),
+            (&start, "e2e5", 100),
+            (&start, "d2d4", 5),
+            (&promotion, "a7a8q", 1),
+            (&castling, "e1a1", 2),
+        ]))
+        .unwrap();
+        assert_eq!(book.len(), 6);
+
+        let moves: Vec<(String, u16)> = book.moves(&start).into_iter().map(|(m, w)| (m.uci(), w)).collect();
+        assert_eq!(moves, vec![("e2e4".to_string(), 10), ("d2d4".to_string(), 5)]);
+        let moves: Vec<String> = book.moves(&castling).into_iter().map(|(m, _)| m.uci()).collect();
+        assert_eq!(moves, vec!["e1g1", "e1c1"]);
+        assert!(book.moves(&castling)[0].0.is_king_castling());
+        assert_eq!(book.moves(&promotion)[0].0.uci(), "a7a8q");
+        assert!(book.moves(&start.make(start.parse_uci_move("e2e4").unwrap())).is_empty());
+    }
+
+    #[test]
+    pub fn choose_by_weight() {
+        let start = GameState::default();
+        let book = PolyglotBook::from_bytes(&book_bytes(&[
+            (&start, "e2e4", 1),
+            (&start, "d2d4", 3),
+            (&start, "c2c4", 3),
+            (&start, "g2g4", 0),
+        ]))
+        .unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(book.choose(&start, BookSelection::BestMove, &mut rng).unwrap().uci(), "d2d4");
+
+        let mut counts = [0; 3];
+        for _ in 0..700 {
+            match book.choose(&start, BookSelection::WeightedRandom, &mut rng).unwrap().uci().as_str() {
+                "e2e4" => counts[0] += 1,
+                "d2d4" => counts[1] += 1,
+                "c2c4" => counts[2] += 1,
+                uci => panic!("{uci} has no weight"),
+            }
+        }
+        assert!(counts[0] > 50 && counts[0] < 150, "{counts:?}");
+        assert!(counts[1] > 200 && counts[2] > 200, "{counts:?}");
+
+        let empty = PolyglotBook::from_bytes(&[]).unwrap();
+        assert!(empty.choose(&start, BookSelection::WeightedRandom, &mut rng).is_none());
+        assert!(PolyglotBook::from_bytes(&[0; 17]).is_err());
+    }
+}
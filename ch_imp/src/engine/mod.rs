@@ -4,61 +4,169 @@ use std::thread::{self, JoinHandle};
 use std::{str::SplitAsciiWhitespace, time::Duration, time::Instant};
 
 use crate::move_ordering::killer_store::KillerStore;
-use crate::shared::cache::{MovesCache, PositionCache};
+use crate::shared::cache::{EvalCache, MovesCache, PositionCache};
 
+use crate::board::{fen::FenError, position::Position};
+use crate::book::{BookSelection, PolyglotBook};
+use crate::evaluation::{bitbase, pawn_structure::PawnStructureCache};
+use crate::match_state::r#match::Match;
+use crate::pgn::{PgnEval, PgnGame, START_FEN};
+use crate::shared::memory_budget::MemoryBudget;
 use crate::shared::transposition_table::TranspositionTable;
+#[cfg(feature = "syzygy")]
+use crate::syzygy::SyzygyTablebase;
+use self::options::EngineOptions;
 use crate::{
-    match_state::game_state::{GameState, MatchResultState},
+    match_state::game_state::{GameState, MatchResultState, MoveParseError},
     r#move::Move,
 };
 
 pub mod move_orderer;
+pub mod options;
 pub mod perft;
 pub mod san;
 pub mod search;
+pub mod search_trace;
 
 const MAX_EXTENSIONS: i8 = 8;
 const WHITE_WIN_THRESHOLD: i32 = i32::MAX - 5;
 const BLACK_WIN_THRESHOLD: i32 = i32::MIN + 5;
 
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: i16,
+    pub pv: Vec<Move>,
+    // Score a draw was worth to the side to move at the root once contempt was applied
+    pub draw_score: i16,
+    // Permille of the transposition table written during this search
+    pub hashfull: u16,
+    pub nodes: u64,
+    // Positions whose score came from the Syzygy tables
+    pub tb_hits: u64,
+}
+
+impl SearchInfo {
+    // The score as a [%eval] from white's point of view, mates are counted in moves along the principal variation
+    pub fn pgn_eval(&self, black_turn: bool) -> PgnEval {
+        let score = if black_turn { -(self.score as i32) } else { self.score as i32 };
+        if self.score >= search::MATE_MAX_CUTOFF || self.score <= search::MATE_MIN_CUTOFF {
+            PgnEval::MateIn(score.signum() * ((self.pv.len() + 1) / 2) as i32)
+        } else {
+            PgnEval::Centipawns(score)
+        }
+    }
+}
+
+// Bytes held by each of the engine's caches
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryUsage {
+    pub transposition_table: usize,
+    pub position_cache: usize,
+    pub moves_cache: usize,
+    pub eval_cache: usize,
+    pub pawn_cache: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.transposition_table
+            + self.position_cache
+            + self.moves_cache
+            + self.eval_cache
+            + self.pawn_cache
+    }
+}
+
 pub struct ChimpEngine {
-    pub current_game_state: GameState,
-    moves: Vec<Move>,
+    // Every move the front end has given, the current position is the one searched from
+    pub game: Match,
+    book: Option<PolyglotBook>,
+    #[cfg(feature = "syzygy")]
+    syzygy: Option<SyzygyTablebase>,
+    // Largest number of pieces probed for in the current search, 0 when the search doesn't probe
+    #[cfg(feature = "syzygy")]
+    tb_pieces: u32,
+    // When the root is in the tables only the moves that keep its result are searched
+    root_moves: Vec<Move>,
+    tb_hits: u64,
     previous_best_line: Vec<Move>,
+    // Zobrist keys of the positions from the start of the game down to the node being searched, for repetitions
+    key_history: Vec<u64>,
     pub(super) transposition_table: TranspositionTable,
     pub position_cache: PositionCache,
-    pub killer_store: KillerStore
+    pub moves_cache: MovesCache,
+    pub eval_cache: EvalCache,
+    pawn_cache: PawnStructureCache,
+    memory_budget: MemoryBudget,
+    pub killer_store: KillerStore,
+    pub options: EngineOptions,
+    pub last_search_info: SearchInfo,
+    root_black_turn: bool,
+    root_draw_score: i16,
+    nodes: u64,
+    node_limit: Option<u64>,
+    #[cfg(feature = "search_trace")]
+    pub search_trace: search_trace::SearchTrace,
 }
 
 impl ChimpEngine {
     pub fn new() -> Self {
-        let current_game_state = GameState::default();
-        let moves = Vec::new();
+        let memory_budget = MemoryBudget::default();
         Self {
-            current_game_state,
-            moves,
+            game: Match::default(),
+            book: None,
+            #[cfg(feature = "syzygy")]
+            syzygy: None,
+            #[cfg(feature = "syzygy")]
+            tb_pieces: 0,
+            root_moves: Vec::new(),
+            tb_hits: 0,
             previous_best_line: Vec::new(),
-            transposition_table: TranspositionTable::new(),
-            position_cache: PositionCache::new(),
-            killer_store: KillerStore::default()
+            key_history: Vec::new(),
+            transposition_table: TranspositionTable::unallocated(memory_budget.transposition_table_mb),
+            position_cache: PositionCache::with_size_mb(memory_budget.position_cache_mb),
+            moves_cache: MovesCache::with_size_mb(memory_budget.moves_cache_mb),
+            eval_cache: EvalCache::with_size_mb(memory_budget.eval_cache_mb),
+            pawn_cache: PawnStructureCache::with_size_mb(memory_budget.pawn_cache_mb),
+            memory_budget,
+            killer_store: KillerStore::default(),
+            options: EngineOptions::default(),
+            last_search_info: SearchInfo::default(),
+            root_black_turn: false,
+            root_draw_score: 0,
+            nodes: 0,
+            node_limit: None,
+            #[cfg(feature = "search_trace")]
+            search_trace: search_trace::SearchTrace::default(),
         }
     }
 
     pub fn from_position(fen: String) -> Self {
-        let current_game_state = GameState::new(fen);
-        let moves = Vec::new();
-        Self {
-            current_game_state,
-            moves,
-            previous_best_line: Vec::new(),
-            transposition_table: TranspositionTable::new(),
-            position_cache: PositionCache::new(),
-            killer_store: KillerStore::default()
-        }
+        let mut engine = Self::new();
+        engine.load_fen(fen);
+        engine
+    }
+
+    // Sets up a new root position without touching the caches
+    pub fn load_fen(&mut self, fen: String) {
+        self.game = Match::new(GameState::new(fen));
+        self.previous_best_line.clear();
+    }
+
+    // For FENs from outside the engine, a rejected FEN leaves the current position as it was
+    pub fn try_load_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        self.game = Match::try_from_fen(fen)?;
+        self.previous_best_line.clear();
+        Ok(())
+    }
+
+    pub fn current_game_state(&self) -> GameState {
+        self.game.current_game_state()
     }
 
     pub fn black_turn(&self) -> bool {
-        self.current_game_state.position.board.black_turn
+        self.current_game_state().position.board.black_turn
     }
 
     pub fn position(&mut self, mut split_string: SplitAsciiWhitespace<'_>) {
@@ -86,21 +194,33 @@ impl ChimpEngine {
             }
         }
 
-        let mut move_index = 0;
-        while let Some(move_uci) = split_string.next() {
-            if move_index >= self.moves.len() {
-                self.add_move(move_uci);
-            } else if !self.moves[move_index].uci().eq(move_uci) {
-                self.add_move(move_uci);
+        // Moves already in the game are followed again, so only new or taken back moves change the record
+        self.reset_state();
+        for move_uci in split_string {
+            // Playing on after a bad move would leave us in a position the GUI doesn't know about
+            if let Err(e) = self.add_move(move_uci) {
+                error!(target:"app:chimp", "Ignoring the rest of the moves: {e}");
+                break;
             }
-            move_index += 1;
         }
+        // Whatever the GUI sent is the game, anything it took back stays as a variation
+        self.game.promote_to_mainline();
     }
 
     pub fn go(&mut self, wtime: i32, btime: i32, winc: i32, binc: i32) -> (Move, Option<Move>) {
+        if let Some(m) = self.book_move() {
+            info!("{}: book move {m:?}", self.game.ply());
+            self.previous_best_line.clear();
+            self.last_search_info = SearchInfo {
+                pv: vec![m],
+                ..Default::default()
+            };
+            return (m, None);
+        }
+
         let ms = if winc == -1 || binc == -1 {
             wtime
-        } else if self.current_game_state.position.board.black_turn {
+        } else if self.black_turn() {
             if btime < binc {
                 binc / 3 * 2
             } else {
@@ -113,10 +233,17 @@ impl ChimpEngine {
                 i32::max(winc - 50, i32::min(wtime / 10, winc + (wtime / 12)))
             }
         };
+        // The clock of the side that just moved is what they had left after their move
+        if winc != -1 && binc != -1 {
+            let clock = if self.black_turn() { wtime } else { btime };
+            if let Some(entry) = self.game.last_move_mut() {
+                entry.clock = Some(Duration::from_millis(clock.max(0) as u64));
+            }
+        }
         info!(
             "{}: go {} {wtime} {btime} {winc} {binc} => {ms:?}",
-            self.moves.len(),
-            if self.current_game_state.position.board.black_turn {
+            self.game.ply(),
+            if self.black_turn() {
                 "black"
             } else {
                 "white"
@@ -126,19 +253,62 @@ impl ChimpEngine {
             .checked_add(Duration::from_millis(ms as u64))
             .unwrap();
 
-        let previous_line = if self.previous_best_line.len() > 0
-            && self.moves.iter().last() == self.previous_best_line.iter().nth(0)
+        let previous_line = self.previous_line();
+        let cutoff = || Instant::now() > timeout;
+
+        let eval_result = self.iterative_deepening(&cutoff, previous_line);
+        self.finish_go(eval_result)
+    }
+
+    // Searches to a fixed depth without a time limit
+    pub fn go_depth(&mut self, depth: u8) -> (Move, Option<Move>) {
+        info!("{}: go depth {depth}", self.game.ply());
+        let previous_line = self.previous_line();
+        let eval_result = self.iterative_deepening_to_depth(&|| false, previous_line, depth);
+        self.finish_go(eval_result)
+    }
+
+    // Searches until `nodes` nodes have been visited. Unlike a time limit this stops at the same point on every
+    // run, so with the Deterministic option set the result is reproducible
+    pub fn go_nodes(&mut self, nodes: u64) -> (Move, Option<Move>) {
+        info!("{}: go nodes {nodes}", self.game.ply());
+        let previous_line = self.previous_line();
+        self.node_limit = Some(nodes);
+        let eval_result = self.iterative_deepening_to_depth(&|| false, previous_line, u8::MAX);
+        self.node_limit = None;
+        self.finish_go(eval_result)
+    }
+
+    // Only legal moves come out of the book, anything else in it for this position is skipped
+    fn book_move(&self) -> Option<Move> {
+        let book = self.book.as_ref().filter(|_| self.options.own_book)?;
+        let game_state = self.current_game_state();
+        if game_state.full_moves > self.options.book_depth {
+            return None;
+        }
+        // Deterministic runs can't pick at random
+        let selection = if self.options.best_book_move || self.options.deterministic {
+            BookSelection::BestMove
+        } else {
+            BookSelection::WeightedRandom
+        };
+        book.choose(&game_state, selection, &mut rand::thread_rng())
+    }
+
+    fn previous_line(&self) -> Vec<Move> {
+        // The previous line depends on earlier searches so deterministic searches start without one
+        if !self.options.deterministic
+            && self.previous_best_line.len() > 0
+            && self.game.last_move().map(|entry| entry.m) == self.previous_best_line.first().copied()
         {
             let num_priority_moves = self.previous_best_line.len();
             self.previous_best_line[1..num_priority_moves].to_vec()
         } else {
             Vec::new()
-        };
-
-        let cutoff = || Instant::now() > timeout;
-
-        let eval_result = self.iterative_deepening(&cutoff, previous_line);
+        }
+    }
 
+    fn finish_go(&mut self, eval_result: Vec<Move>) -> (Move, Option<Move>) {
         if (eval_result.len()) == 0 {
             return (Move::default(), None);
         }
@@ -202,14 +372,67 @@ impl ChimpEngine {
     //     (m, ponder)
     // }
 
+    // Forget everything learnt from the previous game while keeping the configured options and table sizes
+    pub fn new_game(&mut self) {
+        self.game = Match::default();
+        self.previous_best_line.clear();
+        self.last_search_info = SearchInfo::default();
+        self.clear_caches();
+        self.prepare();
+    }
+
+    // Does the set up that would otherwise happen in the first search needing it, where it would use up the clock
+    pub fn prepare(&self) {
+        bitbase::init();
+    }
+
+    pub(super) fn clear_caches(&mut self) {
+        self.transposition_table.clear();
+        self.position_cache.clear();
+        self.moves_cache.clear();
+        self.eval_cache.clear();
+        self.pawn_cache.clear();
+        self.killer_store = KillerStore::default();
+    }
+
+    // Static evaluation of `position` for the side to move, cached by zobrist key
+    pub fn evaluate(&mut self, position: &Position) -> i16 {
+        let zorb_key = position.board.zorb_key;
+        match self.eval_cache.lookup(zorb_key) {
+            Some(eval) => eval,
+            None => {
+                let eval = position.evaluate(&mut self.pawn_cache);
+                self.eval_cache.record(zorb_key, eval);
+                eval
+            }
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            transposition_table: self.transposition_table.memory_bytes(),
+            position_cache: self.position_cache.memory_bytes(),
+            moves_cache: self.moves_cache.memory_bytes(),
+            eval_cache: self.eval_cache.memory_bytes(),
+            pawn_cache: self.pawn_cache.memory_bytes(),
+        }
+    }
+
+    // Back to the start position, keeping the game's moves when it was played from there
     fn reset_state(&mut self) {
-        self.current_game_state = GameState::default();
+        if self.game.start_game_state().to_fen() != START_FEN {
+            self.game = Match::default();
+        }
+        self.game.jump_to_ply(0);
+    }
+
+    // The moves the engine has been given so far, for saving games played through a front end
+    pub fn pgn_game(&self) -> PgnGame {
+        self.game.to_pgn_game()
     }
 
-    fn add_move(&mut self, move_uci: &str) {
-        let m = self.current_game_state.move_from_uci(move_uci);
-        self.current_game_state = self.current_game_state.make(m);
-        self.moves.push(m);
+    fn add_move(&mut self, move_uci: &str) -> Result<(), MoveParseError> {
+        self.game.play_uci(move_uci)
     }
 
     // pub fn ponder_miss(&mut self) {
@@ -244,3 +467,196 @@ impl ChimpEngine {
     //     })
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    pub fn new_engine_allocates_nothing_until_it_searches() {
+        let mut engine = ChimpEngine::new();
+        assert_eq!(engine.memory_usage().total(), 0);
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        engine.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 1);
+
+        let usage = engine.memory_usage();
+        let budget = engine.memory_budget();
+        assert_eq!(usage.transposition_table, budget.transposition_table_mb * 1024 * 1024);
+        assert!(usage.position_cache <= budget.position_cache_mb * 1024 * 1024);
+        assert!(usage.position_cache > 0 && usage.moves_cache > 0);
+        assert!(usage.eval_cache > 0 && usage.pawn_cache > 0);
+    }
+
+    #[test]
+    pub fn caches_stay_within_their_share_of_the_budget() {
+        let mut engine = ChimpEngine::from_position(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into(),
+        );
+        assert!(engine.set_option("Hash", Some("1")));
+        assert!(engine.set_option("Cache", Some("4")));
+        engine.go_nodes(20000);
+
+        let usage = engine.memory_usage();
+        let budget = engine.memory_budget();
+        let mb = 1024 * 1024;
+        assert!(usage.moves_cache > 0 && usage.moves_cache <= budget.moves_cache_mb * mb);
+        assert!(usage.position_cache <= budget.position_cache_mb * mb);
+        assert!(usage.eval_cache <= budget.eval_cache_mb * mb);
+        assert!(usage.pawn_cache <= budget.pawn_cache_mb * mb);
+        assert!(usage.total() <= budget.total_mb() * mb);
+    }
+
+    #[test]
+    pub fn deterministic_searches_ignore_earlier_searches() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3".to_string();
+
+        let mut fresh = ChimpEngine::new();
+        assert!(fresh.set_option("Deterministic", Some("true")));
+        fresh.load_fen(fen.clone());
+        let fresh_result = fresh.go_nodes(3000);
+        let fresh_info = fresh.last_search_info.clone();
+
+        let mut used = ChimpEngine::new();
+        assert!(used.set_option("Deterministic", Some("true")));
+        used.go_depth(3);
+        used.load_fen(fen);
+        let used_result = used.go_nodes(3000);
+
+        assert_eq!(fresh_result, used_result);
+        assert_eq!(fresh_info.nodes, used.last_search_info.nodes);
+        assert_eq!(fresh_info.pv, used.last_search_info.pv);
+        assert_eq!(fresh_info.score, used.last_search_info.score);
+    }
+
+    #[test]
+    pub fn position_stops_at_the_first_bad_move() {
+        let mut engine = ChimpEngine::new();
+        engine.position("startpos moves e2e4 e7e5 e2e4 g1f3".split_ascii_whitespace());
+        assert_eq!(engine.game.ply(), 2);
+        assert_eq!(
+            engine.current_game_state().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+    }
+
+    #[test]
+    pub fn position_keeps_taken_back_moves_as_variations() {
+        let mut engine = ChimpEngine::new();
+        engine.position("startpos moves e2e4 e7e5 g1f3".split_ascii_whitespace());
+        engine.position("startpos moves e2e4 e7e5 b1c3 b8c6".split_ascii_whitespace());
+        assert_eq!(engine.game.ply(), 4);
+        assert!(engine.game.is_on_mainline());
+        assert_eq!(
+            engine.pgn_game().to_pgn().lines().last().unwrap(),
+            "1. e4 e5 2. Nc3 (2. Nf3) 2... Nc6 *"
+        );
+
+        engine.position("startpos".split_ascii_whitespace());
+        assert_eq!(engine.game.ply(), 0);
+        engine.load_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1".into());
+        engine.position("startpos moves d2d4".split_ascii_whitespace());
+        assert_eq!(engine.game.mainline().len(), 1);
+        assert_eq!(engine.game.start_game_state().to_fen(), START_FEN);
+    }
+
+    #[test]
+    pub fn pgn_game_records_moves_from_the_loaded_position() {
+        let mut engine = ChimpEngine::new();
+        engine.position("startpos moves e2e4 e7e5 g1f3".split_ascii_whitespace());
+        let game = engine.pgn_game();
+        assert_eq!(game.tag("FEN"), None);
+        assert!(game.to_pgn().ends_with("\n1. e4 e5 2. Nf3 *\n"));
+
+        engine.load_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1".into());
+        engine.add_move("e8d7").unwrap();
+        let game = engine.pgn_game();
+        assert_eq!(game.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1"));
+        assert_eq!(game.mainline.moves[0].san, "Kd7");
+    }
+
+    #[test]
+    pub fn pgn_eval_is_from_whites_point_of_view() {
+        let info = SearchInfo {
+            score: 35,
+            pv: vec![Move::default(); 3],
+            ..Default::default()
+        };
+        assert_eq!(info.pgn_eval(false), PgnEval::Centipawns(35));
+        assert_eq!(info.pgn_eval(true), PgnEval::Centipawns(-35));
+
+        let mate = SearchInfo { score: search::AB_MAX, ..info };
+        assert_eq!(mate.pgn_eval(false), PgnEval::MateIn(2));
+        assert_eq!(mate.pgn_eval(true), PgnEval::MateIn(-2));
+    }
+
+    #[test]
+    pub fn node_limit_stops_the_search() {
+        let mut engine = ChimpEngine::new();
+        let (bestmove, _) = engine.go_nodes(500);
+        assert!(!bestmove.is_empty());
+        // The counter is checked between nodes so it may overshoot by the node that was being expanded
+        assert!(engine.nodes >= 500 && engine.nodes < 600, "{}", engine.nodes);
+
+        engine.go_depth(2);
+        assert_eq!(engine.last_search_info.depth, 2);
+        assert!(engine.node_limit.is_none());
+    }
+
+    #[test]
+    pub fn evaluate_is_side_relative_and_cached() {
+        let mut engine = ChimpEngine::new();
+        let white = Position::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1".into());
+        let black = Position::from_fen("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1".into());
+
+        let white_eval = engine.evaluate(&white);
+        assert!(white_eval > 0);
+        assert!(engine.evaluate(&black) < 0);
+        assert_eq!(engine.eval_cache.misses, 2);
+
+        assert_eq!(engine.evaluate(&white), white_eval);
+        assert_eq!(engine.eval_cache.hits, 1);
+        assert_eq!(white.evaluate(&mut PawnStructureCache::with_size_mb(1)), white_eval);
+    }
+
+    #[test]
+    pub fn engines_keep_their_own_move_caches() {
+        let mut a = ChimpEngine::new();
+        let b = ChimpEngine::new();
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        a.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 2);
+
+        assert!(a.moves_cache.misses > 0);
+        assert_eq!(b.moves_cache.hits + b.moves_cache.misses, 0);
+        assert!(a.memory_usage().moves_cache > b.memory_usage().moves_cache);
+    }
+
+    #[test]
+    pub fn new_game_clears_caches_but_keeps_options() {
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("Hash", Some("16")));
+        assert!(engine.set_option("Contempt", Some("20")));
+        engine.position("startpos moves e2e4".split_ascii_whitespace());
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        engine.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 2);
+        assert!(engine.transposition_table.get_move(&engine.current_game_state().position.board).is_some());
+
+        engine.new_game();
+        assert_eq!(engine.current_game_state().to_fen(), GameState::default().to_fen());
+        assert_eq!(engine.game.ply(), 0);
+        assert!(engine.transposition_table.get_move(&engine.current_game_state().position.board).is_none());
+        assert_eq!(engine.moves_cache.hits + engine.moves_cache.misses, 0);
+        assert_eq!(engine.eval_cache.hits + engine.eval_cache.misses, 0);
+        assert_eq!(engine.hash_size_mb(), 16);
+        assert_eq!(engine.options.contempt, 20);
+        assert_eq!(
+            engine.memory_usage().total(),
+            engine.transposition_table.memory_bytes()
+                + engine.position_cache.memory_bytes()
+                + engine.moves_cache.memory_bytes()
+                + engine.eval_cache.memory_bytes()
+                + engine.pawn_cache.memory_bytes()
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::{io, path::Path};
+
+use log::{error, info};
+
+use crate::book::PolyglotBook;
+use crate::shared::{
+    memory_budget::{
+        MemoryBudget, DEFAULT_CACHE_MB, DEFAULT_HASH_MB, MAX_CACHE_MB, MAX_HASH_MB, MIN_CACHE_MB, MIN_HASH_MB,
+    },
+    transposition_table::TranspositionTable,
+};
+#[cfg(feature = "syzygy")]
+use crate::syzygy::SyzygyTablebase;
+
+use super::ChimpEngine;
+
+const CONTEMPT_MIN: i16 = -500;
+const CONTEMPT_MAX: i16 = 500;
+const BOOK_DEPTH_DEFAULT: u32 = 20;
+const BOOK_DEPTH_MAX: u32 = 255;
+#[cfg(feature = "syzygy")]
+const SYZYGY_PROBE_DEPTH_DEFAULT: u8 = 1;
+#[cfg(feature = "syzygy")]
+const SYZYGY_PROBE_DEPTH_MAX: u8 = 100;
+
+#[derive(Clone, Debug)]
+pub struct EngineOptions {
+    // Centipawns the engine is willing to give up to avoid a draw. Negative values make the engine seek draws
+    pub contempt: i16,
+    // When set contempt fades towards half its value as material comes off the board
+    pub contempt_phase_scaling: bool,
+    // File used by the Save Hash / Load Hash buttons
+    pub hash_file: String,
+    // Clears every cache before each search so the result only depends on the position, limits and options
+    pub deterministic: bool,
+    // Play moves from the book in BookFile instead of searching while it has any
+    pub own_book: bool,
+    pub book_file: String,
+    // Last full move the book is used for
+    pub book_depth: u32,
+    // Always play the book's highest weighted move rather than choosing by weight
+    pub best_book_move: bool,
+    // Directories holding Syzygy tables, separated like PATH
+    #[cfg(feature = "syzygy")]
+    pub syzygy_path: String,
+    // Positions with as many pieces as the largest tables are only probed this far from the leaves
+    #[cfg(feature = "syzygy")]
+    pub syzygy_probe_depth: u8,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            contempt: 0,
+            contempt_phase_scaling: false,
+            hash_file: String::new(),
+            deterministic: false,
+            own_book: false,
+            book_file: String::new(),
+            book_depth: BOOK_DEPTH_DEFAULT,
+            best_book_move: false,
+            #[cfg(feature = "syzygy")]
+            syzygy_path: String::new(),
+            #[cfg(feature = "syzygy")]
+            syzygy_probe_depth: SYZYGY_PROBE_DEPTH_DEFAULT,
+        }
+    }
+}
+
+impl EngineOptions {
+    pub fn uci_options() -> Vec<String> {
+        let options = vec![
+            format!("option name Contempt type spin default 0 min {CONTEMPT_MIN} max {CONTEMPT_MAX}"),
+            "option name ContemptPhaseScaling type check default false".into(),
+            format!("option name Hash type spin default {DEFAULT_HASH_MB} min {MIN_HASH_MB} max {MAX_HASH_MB}"),
+            format!("option name Cache type spin default {DEFAULT_CACHE_MB} min {MIN_CACHE_MB} max {MAX_CACHE_MB}"),
+            "option name Clear Hash type button".into(),
+            "option name HashFile type string default <empty>".into(),
+            "option name Save Hash type button".into(),
+            "option name Load Hash type button".into(),
+            "option name Deterministic type check default false".into(),
+            "option name OwnBook type check default false".into(),
+            "option name BookFile type string default <empty>".into(),
+            format!("option name BookDepth type spin default {BOOK_DEPTH_DEFAULT} min 1 max {BOOK_DEPTH_MAX}"),
+            "option name Best Book Move type check default false".into(),
+        ];
+        #[cfg(feature = "syzygy")]
+        let options = [
+            options,
+            vec![
+                "option name SyzygyPath type string default <empty>".into(),
+                format!(
+                    "option name SyzygyProbeDepth type spin default {SYZYGY_PROBE_DEPTH_DEFAULT} min 1 max {SYZYGY_PROBE_DEPTH_MAX}"
+                ),
+            ],
+        ]
+        .concat();
+        options
+    }
+}
+
+impl ChimpEngine {
+    pub fn hash_size_mb(&self) -> usize {
+        self.transposition_table.size_mb()
+    }
+
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.memory_budget
+    }
+
+    // Caches whose size doesn't change keep their contents, the others are reallocated when next used
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.transposition_table.resize(budget.transposition_table_mb);
+        self.position_cache.resize(budget.position_cache_mb);
+        self.moves_cache.resize(budget.moves_cache_mb);
+        self.eval_cache.resize(budget.eval_cache_mb);
+        self.pawn_cache.resize(budget.pawn_cache_mb);
+        self.memory_budget = budget;
+    }
+
+    pub fn save_hash<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.transposition_table.save(path)
+    }
+
+    // The file has to have been saved with the same Hash setting. On failure the current table is kept
+    pub fn load_hash<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.transposition_table = TranspositionTable::load(path, self.memory_budget.transposition_table_mb)?;
+        Ok(())
+    }
+
+    // On failure the engine is left without a book
+    pub fn load_book<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.book = None;
+        self.book = Some(PolyglotBook::load(path)?);
+        Ok(())
+    }
+
+    // On failure the engine is left without tablebases
+    #[cfg(feature = "syzygy")]
+    pub fn load_syzygy(&mut self, paths: &str) -> io::Result<()> {
+        self.syzygy = None;
+        let tablebase = SyzygyTablebase::load(paths)?;
+        info!("loaded syzygy tables for up to {} pieces", tablebase.max_pieces());
+        self.syzygy = Some(tablebase);
+        Ok(())
+    }
+
+    // Applies a UCI `setoption`. Returns false if the option is unknown or the value couldn't be parsed
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> bool {
+        info!("setoption {name} {value:?}");
+        match (name.to_ascii_lowercase().as_str(), value) {
+            ("contempt", Some(v)) => match v.parse::<i16>() {
+                Ok(c) => {
+                    self.options.contempt = c.clamp(CONTEMPT_MIN, CONTEMPT_MAX);
+                    true
+                }
+                Err(_) => false,
+            },
+            ("contemptphasescaling", Some(v)) => match v.parse::<bool>() {
+                Ok(b) => {
+                    self.options.contempt_phase_scaling = b;
+                    true
+                }
+                Err(_) => false,
+            },
+            ("deterministic", Some(v)) => match v.parse::<bool>() {
+                Ok(b) => {
+                    self.options.deterministic = b;
+                    true
+                }
+                Err(_) => false,
+            },
+            ("ownbook", Some(v)) => match v.parse::<bool>() {
+                Ok(b) => {
+                    self.options.own_book = b;
+                    true
+                }
+                Err(_) => false,
+            },
+            ("best book move", Some(v)) => match v.parse::<bool>() {
+                Ok(b) => {
+                    self.options.best_book_move = b;
+                    true
+                }
+                Err(_) => false,
+            },
+            ("bookdepth", Some(v)) => match v.parse::<u32>() {
+                Ok(depth) => {
+                    self.options.book_depth = depth.clamp(1, BOOK_DEPTH_MAX);
+                    true
+                }
+                Err(_) => false,
+            },
+            ("bookfile", v) => {
+                self.options.book_file = match v {
+                    Some("<empty>") | None => String::new(),
+                    Some(path) => path.to_string(),
+                };
+                if self.options.book_file.is_empty() {
+                    self.book = None;
+                    return true;
+                }
+                match self.load_book(self.options.book_file.clone()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        error!("failed to load book from '{}': {e}", self.options.book_file);
+                        false
+                    }
+                }
+            }
+            #[cfg(feature = "syzygy")]
+            ("syzygyprobedepth", Some(v)) => match v.parse::<u8>() {
+                Ok(depth) => {
+                    self.options.syzygy_probe_depth = depth.clamp(1, SYZYGY_PROBE_DEPTH_MAX);
+                    true
+                }
+                Err(_) => false,
+            },
+            #[cfg(feature = "syzygy")]
+            ("syzygypath", v) => {
+                self.options.syzygy_path = match v {
+                    Some("<empty>") | None => String::new(),
+                    Some(path) => path.to_string(),
+                };
+                if self.options.syzygy_path.is_empty() {
+                    self.syzygy = None;
+                    return true;
+                }
+                match self.load_syzygy(&self.options.syzygy_path.clone()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        error!("failed to load syzygy tables from '{}': {e}", self.options.syzygy_path);
+                        false
+                    }
+                }
+            }
+            ("hash", Some(v)) => match v.parse::<usize>() {
+                Ok(mb) => {
+                    self.set_memory_budget(MemoryBudget::new(mb, self.memory_budget.cache_mb()));
+                    true
+                }
+                Err(_) => false,
+            },
+            ("cache", Some(v)) => match v.parse::<usize>() {
+                Ok(mb) => {
+                    self.set_memory_budget(MemoryBudget::new(self.memory_budget.transposition_table_mb, mb));
+                    true
+                }
+                Err(_) => false,
+            },
+            ("clear hash", _) => {
+                self.transposition_table.clear();
+                true
+            }
+            ("hashfile", v) => {
+                self.options.hash_file = match v {
+                    Some("<empty>") | None => String::new(),
+                    Some(path) => path.to_string(),
+                };
+                true
+            }
+            ("save hash", _) => match self.save_hash(&self.options.hash_file) {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("failed to save hash to '{}': {e}", self.options.hash_file);
+                    false
+                }
+            },
+            ("load hash", _) => match self.load_hash(self.options.hash_file.clone()) {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("failed to load hash from '{}': {e}", self.options.hash_file);
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+// Splits the remainder of a `setoption name <name> value <value>` command. Option names may contain spaces
+pub fn parse_setoption(args: &str) -> Option<(String, Option<String>)> {
+    let args = args.trim();
+    let args = args.strip_prefix("name")?.trim_start();
+    match args.find(" value") {
+        Some(pos) => Some((
+            args[..pos].trim().to_string(),
+            Some(args[pos + " value".len()..].trim().to_string()),
+        )),
+        None => Some((args.trim().to_string(), None)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn parse_setoption_with_value() {
+        assert_eq!(
+            parse_setoption("name Contempt value 25"),
+            Some(("Contempt".into(), Some("25".into())))
+        );
+    }
+
+    #[test]
+    pub fn parse_setoption_button_with_spaces() {
+        assert_eq!(
+            parse_setoption("name Clear Hash"),
+            Some(("Clear Hash".into(), None))
+        );
+    }
+
+    #[test]
+    pub fn set_option_contempt_is_clamped() {
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("Contempt", Some("10000")));
+        assert_eq!(engine.options.contempt, CONTEMPT_MAX);
+        assert!(!engine.set_option("Contempt", Some("abc")));
+        assert!(!engine.set_option("NotAnOption", Some("1")));
+    }
+
+    #[test]
+    pub fn set_option_hash_and_cache_sizes() {
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("Hash", Some("16")));
+        assert_eq!(engine.hash_size_mb(), 16);
+        assert_eq!(engine.memory_budget().cache_mb(), DEFAULT_CACHE_MB);
+        assert!(engine.set_option("Cache", Some("32")));
+        assert_eq!(engine.memory_budget().cache_mb(), 32);
+        assert_eq!(engine.hash_size_mb(), 16);
+        assert_eq!(engine.memory_budget().total_mb(), 48);
+        assert!(engine.set_option("Hash", Some("0")));
+        assert_eq!(engine.hash_size_mb(), MIN_HASH_MB);
+        assert!(!engine.set_option("Hash", Some("big")));
+        assert!(!engine.set_option("Cache", Some("big")));
+        assert!(engine.set_option("Clear Hash", None));
+    }
+
+    #[test]
+    pub fn save_and_load_hash_options() {
+        let path = std::env::temp_dir().join("chimp_options_hash.bin");
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("Hash", Some("4")));
+        assert!(!engine.set_option("Save Hash", None));
+        assert!(engine.set_option("HashFile", Some(path.to_str().unwrap())));
+        assert!(engine.set_option("Save Hash", None));
+
+        // Tables of a different size than Hash allows are refused
+        let mut other = ChimpEngine::new();
+        assert!(other.set_option("HashFile", Some(path.to_str().unwrap())));
+        assert!(!other.set_option("Load Hash", None));
+        assert_eq!(other.hash_size_mb(), other.memory_budget().transposition_table_mb);
+        assert!(other.set_option("Hash", Some("4")));
+        assert!(other.set_option("Load Hash", None));
+        assert_eq!(other.hash_size_mb(), 4);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    pub fn book_options() {
+        use crate::{book::test::book_bytes, match_state::game_state::GameState};
+
+        let path = std::env::temp_dir().join("chimp_options_book.bin");
+        let start = GameState::default();
+        std::fs::write(&path, book_bytes(&[(&start, "e2e4", 1), (&start, "d2d4", 0)])).unwrap();
+
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("BookFile", Some(path.to_str().unwrap())));
+        // The book is only used once OwnBook is set
+        engine.position("startpos".split_ascii_whitespace());
+        engine.go_depth(1);
+        assert!(engine.last_search_info.depth > 0);
+        assert!(engine.set_option("OwnBook", Some("true")));
+        assert_eq!(engine.go(1000, 1000, 0, 0).0.uci(), "e2e4");
+        assert_eq!(engine.last_search_info.depth, 0);
+
+        // Out of book or past BookDepth the engine searches
+        engine.position("startpos moves e2e4".split_ascii_whitespace());
+        assert_ne!(engine.go(100, 100, -1, -1).0, Default::default());
+        assert!(engine.last_search_info.depth > 0);
+        assert!(engine.set_option("BookDepth", Some("0")));
+        assert_eq!(engine.options.book_depth, 1);
+        engine.load_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 2".into());
+        assert!(engine.book_move().is_none());
+
+        assert!(!engine.set_option("BookFile", Some("/nonexistent/book.bin")));
+        assert!(engine.book.is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "syzygy")]
+    #[test]
+    pub fn syzygy_options() {
+        let mut engine = ChimpEngine::new();
+        assert!(engine.set_option("SyzygyProbeDepth", Some("0")));
+        assert_eq!(engine.options.syzygy_probe_depth, 1);
+        assert!(engine.set_option("SyzygyProbeDepth", Some("250")));
+        assert_eq!(engine.options.syzygy_probe_depth, SYZYGY_PROBE_DEPTH_MAX);
+        assert!(!engine.set_option("SyzygyProbeDepth", Some("deep")));
+
+        assert!(!engine.set_option("SyzygyPath", Some("/nonexistent/syzygy")));
+        assert!(engine.syzygy.is_none());
+        assert!(engine.set_option("SyzygyPath", Some("<empty>")));
+        assert!(engine.options.syzygy_path.is_empty());
+
+        // Without tables the search runs as before
+        engine.load_fen("8/8/8/4k3/8/8/4K3/4Q3 w - - 0 1".into());
+        engine.go_depth(3);
+        assert_eq!(engine.last_search_info.tb_hits, 0);
+    }
+}
@@ -2,55 +2,78 @@ use std::time::Instant;
 
 use log::info;
 
-use crate::{match_state::game_state::GameState, r#move::Move, move_generation::generate_moves_for_board};
+use crate::{
+    match_state::game_state::GameState,
+    move_generation::generate_moves_for_board,
+    r#move::{move_list::MoveList, Move},
+};
 
 pub fn perft(name: String, fen: String, counts: Vec<usize>) {
     info!("--- {name} ---");
     println!("--- {name} ---");
     let origin_game_state = GameState::new(fen);
-    let mut top_level_states = Vec::new();
+    let mut root_moves = MoveList::new();
+    generate_moves_for_board(origin_game_state.position.board, &mut root_moves);
 
-    let start: Instant = Instant::now();
-    for m in generate_moves_for_board(origin_game_state.position.board) {
-        top_level_states.push((m, 0, vec![origin_game_state.make(m)]));
-    }
-    let duration = start.elapsed();
-    info!("0: {}/{} - {duration:?}", top_level_states.len(), counts[0]);
-    println!("0: {}/{} - {duration:?}", top_level_states.len(), counts[0]);
-    if top_level_states.len() != counts[0] {
-        print_move_counts(&top_level_states);
-        return;
-    }
-
-    for depth in 1..counts.len() {
+    for (depth, &expected) in counts.iter().enumerate() {
         let start: Instant = Instant::now();
-        let mut depth_count = 0;
-
-        for top_level_state in top_level_states.iter_mut() {
-            let mut new_edge_states = Vec::new();
-            for game_state in &top_level_state.2 {
-                for m in generate_moves_for_board(game_state.position.board) {
-                    new_edge_states.push(game_state.make(m));
-                }
-            }
-            depth_count += new_edge_states.len();
-            top_level_state.1 = new_edge_states.len();
-            top_level_state.2 = new_edge_states;
-        }
+        let move_counts: Vec<(Move, usize)> = root_moves
+            .iter()
+            .map(|m| (m.m, perft_count(&origin_game_state.make(m.m), depth)))
+            .collect();
+        let depth_count: usize = move_counts.iter().map(|(_, count)| count).sum();
 
         let duration = start.elapsed();
-        info!("{depth}: {}/{} - {duration:?}", depth_count, counts[depth]);
-        println!("{depth}: {}/{} - {duration:?}", depth_count, counts[depth]);
-        if depth_count != counts[depth] {
-            print_move_counts(&top_level_states);
+        info!("{depth}: {depth_count}/{expected} - {duration:?}");
+        println!("{depth}: {depth_count}/{expected} - {duration:?}");
+        if depth_count != expected {
+            print_move_counts(&move_counts);
             return;
         }
     }
 }
 
-fn print_move_counts(top_level_states: &Vec<(Move, usize, Vec<GameState>)>) {
-    for top_level_state in top_level_states {
-        info!("{}: {}", top_level_state.0.uci(), top_level_state.1);
-        println!("{}: {}", top_level_state.0.uci(), top_level_state.1);
+// Counts the positions `depth` plies below `game_state`. Walks depth first with one move list per ply on the
+// stack, so nothing is allocated per node
+pub fn perft_count(game_state: &GameState, depth: usize) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut moves = MoveList::new();
+    generate_moves_for_board(game_state.position.board, &mut moves);
+    if depth == 1 {
+        return moves.len();
+    }
+
+    moves
+        .iter()
+        .map(|m| perft_count(&game_state.make(m.m), depth - 1))
+        .sum()
+}
+
+fn print_move_counts(move_counts: &Vec<(Move, usize)>) {
+    for (m, count) in move_counts {
+        info!("{}: {}", m.uci(), count);
+        println!("{}: {}", m.uci(), count);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::match_state::game_state::GameState;
+
+    use super::perft_count;
+
+    #[test]
+    pub fn perft_count_matches_known_node_counts() {
+        let start_position = GameState::default();
+        assert_eq!(perft_count(&start_position, 1), 20);
+        assert_eq!(perft_count(&start_position, 3), 8902);
+
+        let kiwipete = GameState::new(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into(),
+        );
+        assert_eq!(perft_count(&kiwipete, 2), 2039);
     }
 }
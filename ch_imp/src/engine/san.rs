@@ -1,151 +1,259 @@
-use std::f32::consts::E;
+use std::{error::Error, fmt::Display};
 
 use crate::{
+    board::board_rep::BoardRep,
     match_state::game_state::GameState,
     move_generation::generate_moves_for_board,
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::{
-        board_utils::{char_from_file, get_coords_from_index, get_file, get_rank},
-        piece_type::{get_piece_char, PieceType},
+        board_utils::{
+            char_from_file, get_coords_from_index, get_file, get_rank, try_index_from_coords,
+        },
+        piece_type::PieceType,
     },
 };
 
-pub fn build_san(moves: Vec<Move>, starting_fen: String) -> String {
-    let mut r = String::default();
-
-    let mut game_state = GameState::new(starting_fen);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    Malformed(String),
+    InvalidPromotion(char),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
 
-    for m in moves {
-        r += &format!(" {}", game_state.to_san_lichess(m));
-        game_state = game_state.make(m);
+impl Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanError::Malformed(s) => write!(f, "'{s}' is not a SAN move"),
+            SanError::InvalidPromotion(c) => write!(f, "Can't promote to '{c}'"),
+            SanError::IllegalMove(s) => write!(f, "{s} is not legal in this position"),
+            SanError::AmbiguousMove(s) => write!(f, "{s} matches more than one legal move"),
+        }
     }
-
-    r
 }
 
-impl GameState {
-    pub fn to_san(&self, m: Move) -> String {
-        let piece_type = m.piece_type();
-        let piece_letter = get_piece_char(m.piece_type(), false);
-        let from_file = get_file(m.from());
-        let from_rank = get_rank(m.from()) + 1;
-
-        let mut r = if !piece_letter.eq(&'P') {
-            format!("{}", piece_letter)
-        } else {
-            "".into()
-        };
+impl Error for SanError {}
 
-        if m.is_castling() {
-            if m.is_king_castling() {
-                return "O-O".into();
-            } else {
-                return "O-O-O".into();
-            }
-        }
+// The symbols written for each piece, so the same generator can give English, figurine or localized SAN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SanPieces {
+    pub knight: char,
+    pub bishop: char,
+    pub rook: char,
+    pub queen: char,
+    pub king: char,
+}
 
-        let mut duplicate_piece_type = false;
-        let mut duplicate_files = false;
-        let mut duplicate_pawns = false;
+impl SanPieces {
+    pub const ENGLISH: SanPieces = SanPieces {
+        knight: 'N',
+        bishop: 'B',
+        rook: 'R',
+        queen: 'Q',
+        king: 'K',
+    };
+    pub const FIGURINE: SanPieces = SanPieces {
+        knight: '♘',
+        bishop: '♗',
+        rook: '♖',
+        queen: '♕',
+        king: '♔',
+    };
 
-        for c_m in generate_moves_for_board(self.position.board) {
-            let cm_to = c_m.to();
-            if cm_to == m.to() {
-                let cm_from = c_m.from();
-                if cm_from == m.from() {
-                    continue;
-                }
-                if cm_from == from_file {
-                    duplicate_files = true;
-                }
-                let cm_piece = self.position.board.get_piece_type_at_index(cm_from);
-                if cm_piece == piece_type {
-                    duplicate_piece_type = true;
-                    if piece_type == PieceType::Pawn {
-                        duplicate_pawns = true;
-                    }
-                }
-            }
+    // None for pawns, which never get a symbol
+    fn symbol(&self, piece_type: PieceType) -> Option<char> {
+        match piece_type {
+            PieceType::Knight => Some(self.knight),
+            PieceType::Bishop => Some(self.bishop),
+            PieceType::Rook => Some(self.rook),
+            PieceType::Queen => Some(self.queen),
+            PieceType::King => Some(self.king),
+            _ => None,
         }
+    }
+}
+
+// Everything a SAN move says about the move it describes, optional parts left as None
+struct SanPattern {
+    piece_type: PieceType,
+    from_file: Option<u8>,
+    from_rank: Option<u8>,
+    capture: bool,
+    to: u8,
+    promotion: PieceType,
+}
 
-        // println!("df : {duplicate_files}\ndp : {duplicate_pawns}\ndpt : {duplicate_piece_type}\n");
+impl SanPattern {
+    // `body` is the move with any check or annotation suffix already removed
+    fn parse(body: &str, san: &str) -> Result<Self, SanError> {
+        let malformed = || SanError::Malformed(san.into());
+        let (piece_type, rest) = match body.chars().next() {
+            Some('N') => (PieceType::Knight, &body[1..]),
+            Some('B') => (PieceType::Bishop, &body[1..]),
+            Some('R') => (PieceType::Rook, &body[1..]),
+            Some('Q') => (PieceType::Queen, &body[1..]),
+            Some('K') => (PieceType::King, &body[1..]),
+            Some('P') => (PieceType::Pawn, &body[1..]),
+            _ => (PieceType::Pawn, body),
+        };
 
-        match (duplicate_files, duplicate_pawns, duplicate_piece_type) {
-            (true, false, true) => r = format!("{r}{}", char_from_file(from_file)),
-            (false, true, true) => r = format!("{r}{}", char_from_file(from_file)),
-            (false, false, true) => r = format!("{r}{}", char_from_file(from_file)),
-            _ => {}
+        // Promotions are written both as e8=Q and e8Q
+        let (rest, promotion) = match rest.chars().last() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let promotion = match c {
+                    'N' => PieceType::Knight,
+                    'B' => PieceType::Bishop,
+                    'R' => PieceType::Rook,
+                    'Q' => PieceType::Queen,
+                    'K' | 'P' => return Err(SanError::InvalidPromotion(c)),
+                    _ => return Err(malformed()),
+                };
+                let rest = &rest[..rest.len() - 1];
+                (rest.strip_suffix('=').unwrap_or(rest), promotion)
+            }
+            _ => (rest, PieceType::None),
         };
 
-        if m.is_capture() {
-            r = format!("{r}x");
+        if rest.len() < 2 {
+            return Err(malformed());
         }
+        let (prefix, to) = rest.split_at(rest.len() - 2);
+        let to = try_index_from_coords(to).ok_or_else(malformed)?;
+        let (prefix, capture) = match prefix.strip_suffix('x') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
 
-        if m.is_promotion() {
-            return m.uci();
+        // Disambiguation is a file, a rank or both, in that order
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in prefix.chars() {
+            match (c, from_file, from_rank) {
+                ('a'..='h', None, None) => from_file = Some(c as u8 - b'a'),
+                ('1'..='8', _, None) => from_rank = Some(c as u8 - b'1'),
+                _ => return Err(malformed()),
+            }
         }
 
-        r = format!("{r}{}", get_coords_from_index(m.to()));
+        Ok(Self {
+            piece_type,
+            from_file,
+            from_rank,
+            capture,
+            to,
+            promotion,
+        })
+    }
 
-        let ngs = self.make(m);
-        if (m.is_black() && ngs.position.white_in_check)
-            || (!m.is_black() && ngs.position.black_in_check)
+    fn matches(&self, game_state: &GameState, m: Move) -> bool {
+        game_state.position.board.get_piece_type_at_index(m.from()) == self.piece_type
+            && m.to() == self.to
+            && m.promotion_piece_type() == self.promotion
+            && (!self.capture || m.is_capture())
+            && self.from_file.is_none_or(|file| get_file(m.from()) == file)
+            && self.from_rank.is_none_or(|rank| get_rank(m.from()) == rank)
+    }
+}
+
+// Just enough of the from square to tell `m` apart from the other legal moves of the same piece type to the
+// same square: the file if that is unique, otherwise the rank, otherwise both
+fn disambiguation(board: &BoardRep, m: Move) -> String {
+    let piece_type = board.get_piece_type_at_index(m.from());
+    let from_file = get_file(m.from());
+    let from_rank = get_rank(m.from());
+
+    let mut legal_moves = MoveList::new();
+    generate_moves_for_board(*board, &mut legal_moves);
+    let (mut ambiguous, mut shared_file, mut shared_rank) = (false, false, false);
+    for other in legal_moves.iter().map(|s| s.m) {
+        if other.to() == m.to()
+            && other.from() != m.from()
+            && board.get_piece_type_at_index(other.from()) == piece_type
         {
-            r = format!("{r}+");
+            ambiguous = true;
+            shared_file |= get_file(other.from()) == from_file;
+            shared_rank |= get_rank(other.from()) == from_rank;
         }
-        r
     }
 
-    pub fn to_san_lichess(&self, m: Move) -> String {
-        let piece_type = m.piece_type();
-        let piece_letter = get_piece_char(m.piece_type(), false);
-
-        let mut r = if !piece_letter.eq(&'P') {
-            format!("{}", piece_letter)
-        } else {
-            "".into()
-        };
+    match (ambiguous, shared_file, shared_rank) {
+        (false, _, _) => String::new(),
+        (true, false, _) => char_from_file(from_file).to_string(),
+        (true, true, false) => (from_rank + 1).to_string(),
+        (true, true, true) => get_coords_from_index(m.from()),
+    }
+}
 
-        if m.is_castling() {
-            if m.is_king_castling() {
-                return "O-O".into();
-            } else {
-                return "O-O-O".into();
-            }
+impl GameState {
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        // Check, mate and annotation suffixes don't change which move is meant
+        let body = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        if body.is_empty() || !body.is_ascii() {
+            return Err(SanError::Malformed(san.into()));
         }
 
-        let mut moves_targeting_square = Vec::new();
-        for c_m in generate_moves_for_board(self.position.board) {
-            let cm_to = c_m.to();
-            let cm_from = c_m.from();
-            let cm_piece = self.position.board.get_piece_type_at_index(cm_from);
-            if cm_to == m.to() && (cm_piece == piece_type || piece_type == PieceType::Pawn) {
-                moves_targeting_square.push(c_m);
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(self.position.board, &mut legal_moves);
+        let legal_moves = legal_moves.iter().map(|s| s.m);
+
+        let mut candidates: Vec<Move> = match body {
+            "O-O" | "0-0" => legal_moves.filter(|m| m.is_king_castling()).collect(),
+            "O-O-O" | "0-0-0" => legal_moves
+                .filter(|m| m.is_castling() && !m.is_king_castling())
+                .collect(),
+            _ => {
+                let pattern = SanPattern::parse(body, san)?;
+                legal_moves.filter(|&m| pattern.matches(self, m)).collect()
             }
-        }
+        };
 
-        let from_file = char_from_file(get_file(m.from()));
-        r = format!("{r}{from_file}");
-        if moves_targeting_square.len() > 1 && piece_type != PieceType::Pawn {
-            let from_rank = get_rank(m.from()) + 1;
-            r = format!("{r}{from_rank}");
+        match candidates.len() {
+            0 => Err(SanError::IllegalMove(san.into())),
+            1 => Ok(candidates.remove(0)),
+            _ => Err(SanError::AmbiguousMove(san.into())),
         }
+    }
 
-        if m.is_capture() {
-            r = format!("{r}x");
-        }
+    pub fn to_san(&self, m: Move) -> String {
+        self.to_san_with(m, &SanPieces::ENGLISH)
+    }
 
-        if m.is_promotion() {
-            return m.uci();
-        }
+    pub fn to_san_with(&self, m: Move, pieces: &SanPieces) -> String {
+        let board = self.position.board;
+        let mut r = String::new();
 
-        r = format!("{r}{}", get_coords_from_index(m.to()));
+        if m.is_castling() {
+            r.push_str(if m.is_king_castling() { "O-O" } else { "O-O-O" });
+        } else {
+            match pieces.symbol(board.get_piece_type_at_index(m.from())) {
+                Some(symbol) => {
+                    r.push(symbol);
+                    r.push_str(&disambiguation(&board, m));
+                }
+                // Pawn captures are the only pawn moves that name the from file
+                None if m.is_capture() => r.push(char_from_file(get_file(m.from()))),
+                None => {}
+            }
+            if m.is_capture() {
+                r.push('x');
+            }
+            r.push_str(&get_coords_from_index(m.to()));
+            if let Some(symbol) = pieces.symbol(m.promotion_piece_type()) {
+                r.push('=');
+                r.push(symbol);
+            }
+        }
 
         let ngs = self.make(m);
-        if (m.is_black() && ngs.position.white_in_check)
-            || (!m.is_black() && ngs.position.black_in_check)
-        {
-            r = format!("{r}+");
+        let gives_check = if board.black_turn {
+            ngs.position.white_in_check
+        } else {
+            ngs.position.black_in_check
+        };
+        if gives_check {
+            let mut replies = MoveList::new();
+            generate_moves_for_board(ngs.position.board, &mut replies);
+            r.push(if replies.is_empty() { '#' } else { '+' });
         }
         r
     }
@@ -166,15 +274,7 @@ mod test {
     #[test]
     fn simple_situation_startpos_e4() {
         let game_state = GameState::default();
-        let m = Move::new(
-            index_from_coords("e2"),
-            index_from_coords("e4"),
-            MF_DOUBLE_PAWN_PUSH,
-            PieceType::Pawn,
-            false,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("e2"), index_from_coords("e4"), MF_DOUBLE_PAWN_PUSH);
 
         assert_eq!(game_state.to_san(m), "e4");
     }
@@ -182,15 +282,7 @@ mod test {
     #[test]
     fn simple_situation_startpos_knight_to_f3() {
         let game_state = GameState::default();
-        let m = Move::new(
-            index_from_coords("g1"),
-            index_from_coords("f3"),
-            0b0,
-            PieceType::Knight,
-            false,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("g1"), index_from_coords("f3"), 0b0);
 
         assert_eq!(game_state.to_san(m), "Nf3");
     }
@@ -199,15 +291,7 @@ mod test {
     fn capture_with_multiple_possible_attack_pieces() {
         let game_state =
             GameState::new("3r2k1/p2r1p1p/1p2p1p1/q4n2/3P4/PQ5P/1P1RNPP1/3R2K1 b - -".into());
-        let m = Move::new(
-            index_from_coords("f5"),
-            index_from_coords("d4"),
-            MF_CAPTURE,
-            PieceType::Knight,
-            true,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("f5"), index_from_coords("d4"), MF_CAPTURE);
         assert_eq!(game_state.to_san(m), "Nxd4");
     }
 
@@ -215,15 +299,7 @@ mod test {
     fn show_the_move_is_check() {
         let game_state =
             GameState::new("1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("d6"),
-            index_from_coords("d1"),
-            0b0,
-            PieceType::Queen,
-            true,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("d6"), index_from_coords("d1"), 0b0);
         assert_eq!(game_state.to_san(m), "Qd1+");
     }
 
@@ -231,15 +307,7 @@ mod test {
     fn do_not_include_from_rank_if_piece_is_pawn() {
         let game_state =
             GameState::new("2q1rr1k/3bbnnp/p2p1pp1/2pPp3/PpP1P1P1/1P2BNNP/2BQ1PRK/7R b - -".into());
-        let m = Move::new(
-            index_from_coords("f6"),
-            index_from_coords("f5"),
-            0b0,
-            PieceType::Pawn,
-            true,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("f6"), index_from_coords("f5"), 0b0);
         assert_eq!(game_state.to_san(m), "f5");
     }
 
@@ -247,15 +315,7 @@ mod test {
     fn case_0() {
         let game_state =
             GameState::new("1k1r1r2/p1p5/Bpnbb3/3p2pp/3P4/P1N1NPP1/1PP4P/2KR1R2 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c3"),
-            index_from_coords("d5"),
-            MF_CAPTURE,
-            PieceType::Knight,
-            false,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("c3"), index_from_coords("d5"), MF_CAPTURE);
         assert_eq!(game_state.to_san(m), "Ncxd5");
     }
 
@@ -263,15 +323,7 @@ mod test {
     fn case_1() {
         let game_state =
             GameState::new("1qrr3k/6p1/1p1pp2p/pNn5/Pn1bP1PP/5Q2/1PP1N3/1K1R2R1 w - -".into());
-        let m = Move::new(
-            index_from_coords("e2"),
-            index_from_coords("d4"),
-            MF_CAPTURE,
-            PieceType::Knight,
-            false,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("e2"), index_from_coords("d4"), MF_CAPTURE);
         assert_eq!(game_state.to_san(m), "Nexd4");
     }
 
@@ -279,15 +331,160 @@ mod test {
     fn case_2() {
         let game_state =
             GameState::new("r3r1k1/pp2q3/2b1pp2/6pN/Pn1P4/6R1/1P3PP1/3QRBK1 w - -".into());
-        let m = Move::new(
-            index_from_coords("f2"),
-            index_from_coords("f4"),
-            MF_DOUBLE_PAWN_PUSH,
-            PieceType::Pawn,
-            false,
-            0,
-            0
-        );
+        let m = Move::new(index_from_coords("f2"), index_from_coords("f4"), MF_DOUBLE_PAWN_PUSH);
         assert_eq!(game_state.to_san(m), "f4");
     }
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    // (fen, uci, expected SAN)
+    const SAN_CORPUS: [(&str, &str, &str); 24] = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4", "e4"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", "Nf3"),
+        (KIWIPETE, "e1g1", "O-O"),
+        (KIWIPETE, "e1c1", "O-O-O"),
+        (KIWIPETE, "e5f7", "Nxf7"),
+        (KIWIPETE, "d5e6", "dxe6"),
+        (KIWIPETE, "g2h3", "gxh3"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "O-O-O"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "a8a1", "Rxa1+"),
+        ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1", "O-O+"),
+        ("1k1r1r2/p1p5/Bpnbb3/3p2pp/3P4/P1N1NPP1/1PP4P/2KR1R2 w - - 0 1", "c3d5", "Ncxd5"),
+        ("1k1r1r2/p1p5/Bpnbb3/3p2pp/3P4/P1N1NPP1/1PP4P/2KR1R2 w - - 0 1", "d1e1", "Rde1"),
+        ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", "c4f7", "Bxf7+"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3", "R5a3"),
+        ("k7/8/1Q3Q2/8/8/8/1Q3Q2/K7 w - - 0 1", "b2d4", "Qb2d4"),
+        ("k7/8/1Q3Q2/8/8/8/1Q3Q2/K7 w - - 0 1", "f2f3", "Q2f3#"),
+        ("k7/8/1Q3Q2/8/8/8/1Q3Q2/K7 w - - 0 1", "b6c6", "Qbc6#"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+        ("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8", "Ra8#"),
+        ("r6k/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q", "bxa8=Q+"),
+        ("r6k/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n", "b8=N"),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", "d7c8q", "dxc8=Q"),
+        ("1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1", "d6d1", "Qd1+"),
+    ];
+
+    #[test]
+    fn to_san_matches_corpus() {
+        for (fen, uci, expected) in SAN_CORPUS {
+            let game_state = GameState::new(fen.into());
+            let m = game_state.parse_uci_move(uci).unwrap();
+            assert_eq!(game_state.to_san(m), expected, "{fen} {uci}");
+        }
+    }
+
+    #[test]
+    fn to_san_round_trips_every_legal_move() {
+        for (fen, _, _) in SAN_CORPUS {
+            let game_state = GameState::new(fen.into());
+            let mut legal_moves = MoveList::new();
+            generate_moves_for_board(game_state.position.board, &mut legal_moves);
+            for m in legal_moves.iter().map(|s| s.m) {
+                let san = game_state.to_san(m);
+                assert_eq!(game_state.parse_san(&san), Ok(m), "{fen} {san}");
+            }
+        }
+    }
+
+    #[test]
+    fn to_san_with_figurine_and_localized_pieces() {
+        let game_state = GameState::new(KIWIPETE.into());
+        let german = SanPieces {
+            knight: 'S',
+            bishop: 'L',
+            rook: 'T',
+            queen: 'D',
+            king: 'K',
+        };
+        let m = game_state.parse_uci_move("e5f7").unwrap();
+        assert_eq!(game_state.to_san_with(m, &SanPieces::FIGURINE), "♘xf7");
+        assert_eq!(game_state.to_san_with(m, &german), "Sxf7");
+        let m = game_state.parse_uci_move("f3f6").unwrap();
+        assert_eq!(game_state.to_san_with(m, &german), "Dxf6");
+        let m = game_state.parse_uci_move("e1g1").unwrap();
+        assert_eq!(game_state.to_san_with(m, &SanPieces::FIGURINE), "O-O");
+
+        let game_state = GameState::new("r6k/1P6/8/8/8/8/8/4K3 w - - 0 1".into());
+        let m = game_state.parse_uci_move("b7a8q").unwrap();
+        assert_eq!(game_state.to_san_with(m, &german), "bxa8=D+");
+    }
+
+    fn parsed_uci(game_state: &GameState, san: &str) -> String {
+        game_state.parse_san(san).unwrap().uci()
+    }
+
+    #[test]
+    fn parse_san_reads_piece_pawn_and_annotated_moves() {
+        let game_state = GameState::default();
+        assert_eq!(parsed_uci(&game_state, "e4"), "e2e4");
+        assert_eq!(parsed_uci(&game_state, "Nf3"), "g1f3");
+        assert_eq!(parsed_uci(&game_state, "Nf3!?"), "g1f3");
+        assert_eq!(parsed_uci(&game_state, " Nc3+ "), "b1c3");
+
+        let game_state =
+            GameState::new("1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1".into());
+        assert_eq!(parsed_uci(&game_state, "Qd1#"), "d6d1");
+        assert_eq!(parsed_uci(&game_state, "Qa3"), "d6a3");
+    }
+
+    #[test]
+    fn parse_san_uses_file_rank_and_square_disambiguation() {
+        let game_state =
+            GameState::new("1k1r1r2/p1p5/Bpnbb3/3p2pp/3P4/P1N1NPP1/1PP4P/2KR1R2 w - - 0 1".into());
+        assert_eq!(parsed_uci(&game_state, "Ncxd5"), "c3d5");
+        assert_eq!(parsed_uci(&game_state, "Nexd5"), "e3d5");
+
+        let game_state = GameState::new("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1".into());
+        assert_eq!(parsed_uci(&game_state, "R1a3"), "a1a3");
+        assert_eq!(parsed_uci(&game_state, "R5a3"), "a5a3");
+
+        let game_state = GameState::new("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1".into());
+        assert_eq!(parsed_uci(&game_state, "Qa1b2"), "a1b2");
+        assert_eq!(parsed_uci(&game_state, "Qcb2"), "c1b2");
+        assert_eq!(parsed_uci(&game_state, "Q3b2"), "a3b2");
+    }
+
+    #[test]
+    fn parse_san_reads_promotions_and_castling() {
+        let game_state = GameState::new("r6k/1P6/8/8/8/8/8/4K3 w - - 0 1".into());
+        assert_eq!(parsed_uci(&game_state, "b8=Q+"), "b7b8q");
+        assert_eq!(parsed_uci(&game_state, "b8N"), "b7b8n");
+        assert_eq!(parsed_uci(&game_state, "bxa8=R"), "b7a8r");
+
+        let game_state = GameState::new(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into(),
+        );
+        assert_eq!(parsed_uci(&game_state, "O-O"), "e1g1");
+        assert_eq!(parsed_uci(&game_state, "0-0-0"), "e1c1");
+        let game_state = game_state.make(game_state.parse_san("a3").unwrap());
+        assert_eq!(parsed_uci(&game_state, "O-O-O"), "e8c8");
+        assert_eq!(parsed_uci(&game_state, "0-0+"), "e8g8");
+    }
+
+    #[test]
+    fn parse_san_rejects_malformed_illegal_and_ambiguous_moves() {
+        let game_state = GameState::new("r6k/1P6/8/8/8/8/8/4K3 w - - 0 1".into());
+        let cases = [
+            ("", SanError::Malformed("".into())),
+            ("+", SanError::Malformed("+".into())),
+            ("Nf9", SanError::Malformed("Nf9".into())),
+            ("Kz1", SanError::Malformed("Kz1".into())),
+            ("K1ee2", SanError::Malformed("K1ee2".into())),
+            ("b8=K", SanError::InvalidPromotion('K')),
+            ("b8", SanError::IllegalMove("b8".into())),
+            ("Ke3", SanError::IllegalMove("Ke3".into())),
+            ("Kxe2", SanError::IllegalMove("Kxe2".into())),
+            ("O-O", SanError::IllegalMove("O-O".into())),
+        ];
+        for (san, expected) in cases {
+            assert_eq!(game_state.parse_san(san), Err(expected), "{san}");
+        }
+
+        let game_state = GameState::new("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1".into());
+        assert_eq!(game_state.parse_san("Ra3"), Err(SanError::AmbiguousMove("Ra3".into())));
+        let game_state = GameState::new("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1".into());
+        assert_eq!(game_state.parse_san("Qab2"), Err(SanError::AmbiguousMove("Qab2".into())));
+        assert_eq!(game_state.parse_san("Q1b2"), Err(SanError::AmbiguousMove("Q1b2".into())));
+    }
 }
@@ -6,8 +6,8 @@ use crate::{
     board::board_rep::BoardRep,
     match_state::game_state::{self, GameState},
     move_generation::generate_moves_for_board,
-    move_ordering::move_orderer::{MoveOrderer, MOVE_CACHE},
-    r#move::Move,
+    move_ordering::move_orderer::MoveOrderer,
+    r#move::{move_list::MoveList, scored_move::ScoredMove, Move},
     shared::{
         board_utils::{get_rank, index_from_coords},
         constants::MF_KNIGHT_CAPTURE_PROMOTION,
@@ -15,6 +15,10 @@ use crate::{
         transposition_table::NodeType,
     }, evaluation::calculate_game_phase,
 };
+#[cfg(feature = "syzygy")]
+use crate::syzygy::Wdl;
+
+use super::search_trace::TraceTTResult;
 
 const MAX_EXTENSIONS: u8 = 12;
 pub const AB_MIN: i16 = -32766;
@@ -23,12 +27,16 @@ pub const AB_MAX: i16 = 32766;
 pub const MATE_MAX_CUTOFF: i16 = AB_MAX - 16;
 pub const MATE_MIN_CUTOFF: i16 = AB_MIN + 16;
 
-use super::{move_orderer, ChimpEngine};
+// Tablebase wins score above any evaluation but below the mate scores, as the mate isn't known yet
+pub const TB_WIN: i16 = 20000;
+
+use super::{move_orderer, ChimpEngine, SearchInfo};
 
 impl ChimpEngine {
     fn make(&mut self, game_state: GameState, m: Move) -> Option<GameState> {
         assert!(!m.is_empty());
         let (new_zorb, move_segments) = game_state.position.board.zorb_key_after_move(m);
+        self.transposition_table.prefetch(new_zorb);
 
         let lookup_result = self.position_cache.lookup(new_zorb);
 
@@ -49,6 +57,18 @@ impl ChimpEngine {
         cutoff: &CutoffFunc,
         priority_line: Vec<Move>,
     ) -> Vec<Move>
+    where
+        CutoffFunc: Fn() -> bool,
+    {
+        self.iterative_deepening_to_depth(cutoff, priority_line, 12)
+    }
+
+    pub fn iterative_deepening_to_depth<CutoffFunc>(
+        &mut self,
+        cutoff: &CutoffFunc,
+        priority_line: Vec<Move>,
+        max_depth: u8,
+    ) -> Vec<Move>
     where
         CutoffFunc: Fn() -> bool,
     {
@@ -58,11 +78,26 @@ impl ChimpEngine {
 
         let timer = Instant::now();
 
-        while !cutoff() && depth < 12 {
+        if self.options.deterministic {
+            self.clear_caches();
+        }
+        self.nodes = 0;
+        self.transposition_table.allocate();
+        self.transposition_table.new_search();
+        self.prepare_draw_score();
+        self.key_history = self.game.previous_keys();
+        self.last_search_info = SearchInfo {
+            draw_score: self.root_draw_score,
+            ..Default::default()
+        };
+        self.prepare_tablebase();
+
+        while !self.should_stop(cutoff) && depth < max_depth {
             depth += 1;
+            self.trace_begin_iteration(depth);
 
             let result = self.alpha_beta_search(
-                self.current_game_state,
+                self.current_game_state(),
                 cutoff,
                 depth,
                 0,
@@ -77,6 +112,12 @@ impl ChimpEngine {
             }
 
             output = result;
+            self.last_search_info.depth = depth;
+            self.last_search_info.score = output.0;
+            self.last_search_info.pv = output.1.clone();
+            self.last_search_info.hashfull = self.transposition_table.hashfull();
+            self.last_search_info.nodes = self.nodes;
+            self.last_search_info.tb_hits = self.tb_hits;
 
             let dur = timer.elapsed();
             debug!("{depth}: {} \t{:?} \t {:?}", output.0, dur, output.1);
@@ -102,13 +143,36 @@ impl ChimpEngine {
     where
         CutoffFunc: Fn() -> bool,
     {
+        self.trace_enter(
+            if ply == 0 { None } else { Some(game_state.entry_move) },
+            alpha,
+            beta,
+            depth,
+            false,
+        );
+        self.nodes += 1;
+
+        // Repetitions, the 50 move rule and dead positions are scored using the contempt adjusted draw score
+        if ply > 0 && game_state.draw_by_rule(&self.key_history).is_some() {
+            let draw_score = self.node_draw_score(game_state.position.board.black_turn);
+            return (self.trace_exit(draw_score), vec![]);
+        }
+
+        // Positions in the endgame tables are scored from them without searching any further
+        if ply > 0 {
+            if let Some(score) = self.probe_tablebase(&game_state, depth, ply) {
+                return (self.trace_exit(score), vec![]);
+            }
+        }
+
         // If we have an entry in the TT table shortcut the search using its value
-        let tt_entry =
-            self.transposition_table
-                .lookup(game_state.position.board.zorb_key, depth, alpha, beta);
-        if tt_entry != None {
-            let (eval, m) = tt_entry.unwrap();
-            return (eval, vec![m]);
+        let tt_entry = self
+            .transposition_table
+            .lookup(&game_state.position.board, depth, alpha, beta);
+        // At the root a stored move may be one the tablebase filter has ruled out, so the root is always searched
+        if let Some((eval, m)) = tt_entry.filter(|_| ply > 0 || self.root_moves.is_empty()) {
+            self.trace_tt(TraceTTResult::Hit(eval));
+            return (self.trace_exit(eval), m.into_iter().collect());
         }
 
         // If we're at depth 0 we're on a leaf node so store its value in the TT table and return
@@ -120,14 +184,14 @@ impl ChimpEngine {
                 ply,
                 q_result.0,
                 crate::shared::transposition_table::NodeType::PVNode,
-                Some(game_state.entry_move),
+                None,
             );
 
-            return q_result;
+            return (self.trace_exit(q_result.0), q_result.1);
         }
 
-        if cutoff() {
-            return (AB_MIN, vec![]);
+        if self.should_stop(cutoff) {
+            return (self.trace_exit(AB_MIN), vec![]);
         }
 
         // We need to evaluate this node
@@ -138,17 +202,19 @@ impl ChimpEngine {
         let pv = priority_line.iter().nth(ply as usize);
         let hm = self
             .transposition_table
-            .get_move(game_state.position.board.zorb_key);
+            .get_move(&game_state.position.board);
         let board = game_state.position.board;
+        let mut legal_moves = MoveList::new();
+        self.moves_cache.get_moves(board, &mut legal_moves);
         let move_orderer = MoveOrderer::new(
             pv,
             hm,
             game_state.position,
             self.killer_store.get_ply(ply as usize),
+            &legal_moves,
         );
 
         let mut move_index = -1;
-        let legal_moves = get_moves(board);
         // for &m in &legal_moves {
         //     println!("{m:?}")
         // }
@@ -156,15 +222,22 @@ impl ChimpEngine {
         let phase = calculate_game_phase(board);
 
         for m in move_orderer {
-            move_index += 1;
-            if !legal_moves.contains(&m) {
-                error!("position is {}", game_state.to_fen());
-                error!("pv at ply {ply} is {pv:?}");
-                error!("hm at ply {ply} is {hm:?}");
-                error!("killers: {:?}", self.killer_store.get_ply(ply as usize));
-                error!("move {m:?} not in legal moves list {legal_moves:?}");
-                panic!("move {m:?} not in legal moves list {legal_moves:?}");
+            if ply == 0 && !self.root_moves.is_empty() && !self.root_moves.contains(&m) {
+                continue;
             }
+            move_index += 1;
+            let scored_move = match legal_moves.find(m) {
+                Some(s) => s,
+                None => {
+                    error!("position is {}", game_state.to_fen());
+                    error!("pv at ply {ply} is {pv:?}");
+                    error!("hm at ply {ply} is {hm:?}");
+                    error!("killers: {:?}", self.killer_store.get_ply(ply as usize));
+                    error!("move {m:?} not in legal moves list {legal_moves:?}");
+                    self.trace_dump_on_error();
+                    panic!("move {m:?} not in legal moves list {legal_moves:?}");
+                }
+            };
             let new_game_state = match self.make(game_state, m) {
                 Some(s) => s,
                 None => {
@@ -178,10 +251,12 @@ impl ChimpEngine {
 
             has_legal_move = true;
 
-            let extension = get_extensions(new_game_state, m, total_extensions);
+            let extension = get_extensions(new_game_state, scored_move, total_extensions);
+            self.key_history.push(game_state.position.board.zorb_key);
 
             // Reduce late moves if possible
-            let shallow_eval = if extension == 0 && depth > 2 && move_index > 3 && m.is_quiet() && phase < 200 {
+            let shallow_eval = if extension == 0 && depth > 2 && move_index > 3 && scored_move.is_quiet() && phase < 200 {
+                self.trace_child_params(0, 1);
                 let (opponent_val, moves) = self.alpha_beta_search(
                     new_game_state,
                     cutoff,
@@ -206,6 +281,7 @@ impl ChimpEngine {
             let (val, moves) = match shallow_eval {
                 Some(eval_pair) => eval_pair,
                 None => {
+                    self.trace_child_params(extension, 0);
                     let (opponent_val, moves) = self.alpha_beta_search(
                         new_game_state,
                         cutoff,
@@ -219,8 +295,9 @@ impl ChimpEngine {
                     (opponent_val * -1, moves)
                 }
             };
+            self.key_history.pop();
 
-            if line.len() != 0 && cutoff() {
+            if line.len() != 0 && self.should_stop(cutoff) {
                 break;
             }
 
@@ -237,7 +314,7 @@ impl ChimpEngine {
                 if !m.is_capture() {
                     self.killer_store.set(ply as usize, m);
                 }
-                return (beta, vec![]);
+                return (self.trace_exit(beta), vec![]);
             }
 
             // This move is inside the alpha-beta window and is thus considered a PV node
@@ -253,7 +330,7 @@ impl ChimpEngine {
             alpha = if game_state.position.black_in_check | game_state.position.white_in_check {
                 AB_MIN
             } else {
-                0
+                self.node_draw_score(game_state.position.board.black_turn)
             };
         } else {
             self.transposition_table.record(
@@ -269,7 +346,7 @@ impl ChimpEngine {
                 },
             );
         }
-        return (alpha, line);
+        return (self.trace_exit(alpha), line);
     }
 
     pub fn quiescence_search<CutoffFunc>(
@@ -282,24 +359,30 @@ impl ChimpEngine {
     where
         CutoffFunc: Fn() -> bool,
     {
-        if cutoff() {
-            return (0, vec![]); // TODO: Confirm this
+        self.trace_enter(Some(game_state.entry_move), alpha, beta, 0, true);
+        self.nodes += 1;
+
+        if self.should_stop(cutoff) {
+            return (self.trace_exit(0), vec![]); // TODO: Confirm this
         }
 
-        if game_state.position.eval >= beta {
-            return (beta, vec![]);
+        let eval = self.evaluate(&game_state.position);
+        if eval >= beta {
+            return (self.trace_exit(beta), vec![]);
         }
 
-        if game_state.position.eval > alpha {
-            alpha = game_state.position.eval;
+        if eval > alpha {
+            alpha = eval;
         }
 
         let mut line = vec![];
-        let moves = get_moves(game_state.position.board);
-        for m in moves {
-            if m.see() <= 0 {
+        let mut moves = MoveList::new();
+        self.moves_cache.get_moves(game_state.position.board, &mut moves);
+        for scored_move in moves {
+            if scored_move.see <= 0 {
                 continue;
             }
+            let m = scored_move.m;
 
             let new_game_state = match self.make(game_state, m) {
                 Some(gs) => gs,
@@ -311,7 +394,7 @@ impl ChimpEngine {
             let val = opponent_val * -1;
 
             if val >= beta {
-                return (beta, vec![]);
+                return (self.trace_exit(beta), vec![]);
             }
 
             if val > alpha {
@@ -321,15 +404,109 @@ impl ChimpEngine {
             }
         }
 
-        (alpha, line)
+        (self.trace_exit(alpha), line)
     }
 }
 
-fn get_moves(board: BoardRep) -> Vec<Move> {
-    MOVE_CACHE.lock().unwrap().get_moves(board)
+impl ChimpEngine {
+    // Contempt is applied relative to the side to move at the root: the engine values a draw at -contempt for
+    // itself, and so at +contempt for its opponent
+    fn prepare_draw_score(&mut self) {
+        let board = self.current_game_state().position.board;
+        let contempt = self.options.contempt as i32;
+        let contempt = if self.options.contempt_phase_scaling {
+            contempt * (512 - calculate_game_phase(board) as i32) / 512
+        } else {
+            contempt
+        };
+        self.root_black_turn = board.black_turn;
+        self.root_draw_score = -contempt as i16;
+    }
+
+    // Time based cutoffs come from the caller, node limits are checked here so they stop at exactly the same node
+    // on every run
+    fn should_stop<CutoffFunc>(&self, cutoff: &CutoffFunc) -> bool
+    where
+        CutoffFunc: Fn() -> bool,
+    {
+        match self.node_limit {
+            Some(limit) if self.nodes >= limit => true,
+            _ => cutoff(),
+        }
+    }
+
+    fn node_draw_score(&self, black_turn: bool) -> i16 {
+        if black_turn == self.root_black_turn {
+            self.root_draw_score
+        } else {
+            -self.root_draw_score
+        }
+    }
 }
 
-fn get_extensions(new_state: GameState, test_move: Move, total_extensions: u8) -> u8 {
+#[cfg(feature = "syzygy")]
+impl ChimpEngine {
+    // Once the root is in the tables every position below it is too, so rather than probing in the search the
+    // root is limited to the moves that keep its result
+    fn prepare_tablebase(&mut self) {
+        self.tb_hits = 0;
+        self.tb_pieces = 0;
+        self.root_moves.clear();
+        let syzygy = match &self.syzygy {
+            Some(syzygy) => syzygy,
+            None => return,
+        };
+        let game_state = self.current_game_state();
+        let root = if game_state.position.board.occupancy.count_ones() <= syzygy.max_pieces() {
+            syzygy.root_moves(&game_state)
+        } else {
+            None
+        };
+        match root {
+            Some((moves, wdl)) => {
+                debug!("tablebase {wdl:?}, searching {moves:?}");
+                self.tb_hits = 1;
+                self.root_moves = moves;
+            }
+            // Without the DTZ tables the root can't be filtered but the search can still probe WDL
+            None => self.tb_pieces = syzygy.max_pieces(),
+        }
+    }
+
+    // Only positions straight after a capture or pawn move are probed, as their result can't be changed by the 50
+    // move counter
+    fn probe_tablebase(&mut self, game_state: &GameState, depth: u8, ply: u8) -> Option<i16> {
+        let pieces = game_state.position.board.occupancy.count_ones();
+        if game_state.half_moves != 0
+            || pieces > self.tb_pieces
+            || (pieces == self.tb_pieces && depth < self.options.syzygy_probe_depth)
+        {
+            return None;
+        }
+        let wdl = self.syzygy.as_ref()?.probe_wdl(&game_state.position.board)?;
+        self.tb_hits += 1;
+        let draw_score = self.node_draw_score(game_state.position.board.black_turn);
+        Some(match wdl {
+            Wdl::Win => TB_WIN - ply as i16,
+            Wdl::CursedWin => draw_score + 1,
+            Wdl::Draw => draw_score,
+            Wdl::BlessedLoss => draw_score - 1,
+            Wdl::Loss => ply as i16 - TB_WIN,
+        })
+    }
+}
+
+// Built without the syzygy feature there are no tables, so the root is never filtered and nothing is probed
+#[cfg(not(feature = "syzygy"))]
+impl ChimpEngine {
+    fn prepare_tablebase(&mut self) {}
+
+    fn probe_tablebase(&mut self, _game_state: &GameState, _depth: u8, _ply: u8) -> Option<i16> {
+        None
+    }
+}
+
+fn get_extensions(new_state: GameState, test_move: ScoredMove, total_extensions: u8) -> u8 {
     if total_extensions >= MAX_EXTENSIONS {
         return 0;
     }
@@ -337,13 +514,81 @@ fn get_extensions(new_state: GameState, test_move: Move, total_extensions: u8) -
         return 1;
     }
 
-    if test_move.is_promotion() {
+    if test_move.m.is_promotion() {
         return 1;
     }
 
-    let m_rank = get_rank(test_move.to());
-    if test_move.piece_type() == PieceType::Pawn && (m_rank == 1 || m_rank == 6) {
+    let m_rank = get_rank(test_move.m.to());
+    if test_move.piece_type == PieceType::Pawn && (m_rank == 1 || m_rank == 6) {
         return 1;
     }
     return 0;
 }
+
+#[cfg(test)]
+mod test {
+    use crate::engine::ChimpEngine;
+
+    #[test]
+    pub fn contempt_draw_score_is_relative_to_root_side() {
+        let mut engine = ChimpEngine::new();
+        engine.options.contempt = 30;
+        engine.prepare_draw_score();
+        assert_eq!(engine.node_draw_score(false), -30);
+        assert_eq!(engine.node_draw_score(true), 30);
+    }
+
+    #[test]
+    pub fn contempt_phase_scaling_reduces_draw_score_in_endgame() {
+        let mut engine = ChimpEngine::from_position("8/8/4k3/8/8/3K4/8/7R b - - 0 1".into());
+        engine.options.contempt = 40;
+        engine.options.contempt_phase_scaling = true;
+        engine.prepare_draw_score();
+        assert!(engine.node_draw_score(true) > -40 && engine.node_draw_score(true) < 0);
+    }
+
+    #[test]
+    pub fn dead_position_scored_with_contempt() {
+        let mut engine = ChimpEngine::from_position("8/8/4k3/8/8/3KN3/8/8 w - - 0 1".into());
+        engine.options.contempt = 30;
+        engine.iterative_deepening_to_depth(&|| false, vec![], 2);
+        assert_eq!(engine.last_search_info.draw_score, -30);
+        assert_eq!(engine.last_search_info.score, -30);
+    }
+
+    #[test]
+    pub fn repetitions_count_the_moves_played_before_the_search() {
+        let mut engine = ChimpEngine::new();
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"] {
+            engine.game.play_uci(uci).unwrap();
+        }
+        // An engine that wants a draw takes the threefold repetition
+        engine.options.contempt = -500;
+        let line = engine.iterative_deepening_to_depth(&|| false, vec![], 3);
+        assert_eq!(line[0].uci(), "f6g8");
+        assert_eq!(engine.last_search_info.score, 500);
+        assert_eq!(engine.key_history, engine.game.previous_keys());
+    }
+
+    #[cfg(feature = "syzygy")]
+    #[test]
+    #[ignore = "needs the KQvK and KRvK tables in test-tables"]
+    pub fn root_in_the_tables_never_plays_a_losing_tt_move() {
+        use crate::shared::transposition_table::NodeType;
+
+        let mut engine = ChimpEngine::from_position("8/8/8/8/3k4/8/4Q3/K7 w - - 0 1".into());
+        assert!(engine.set_option("SyzygyPath", Some(concat!(env!("CARGO_MANIFEST_DIR"), "/test-tables"))));
+        // A deep entry for the root whose move hangs the queen
+        let root = engine.current_game_state();
+        let blunder = root.parse_uci_move("e2e3").unwrap();
+        engine.transposition_table.allocate();
+        engine
+            .transposition_table
+            .record(root.position.board.zorb_key, 50, 0, 500, NodeType::PVNode, Some(blunder));
+
+        let line = engine.iterative_deepening_to_depth(&|| false, vec![], 3);
+        assert!(engine.last_search_info.tb_hits > 0);
+        assert!(engine.root_moves.contains(&line[0]));
+        assert_ne!(line[0], blunder);
+    }
+}
@@ -0,0 +1,384 @@
+// Opt-in recording of the search tree. With the `search_trace` feature disabled every hook below is an empty
+// inline function so normal builds pay nothing for it.
+use crate::r#move::Move;
+
+use super::ChimpEngine;
+
+#[cfg(feature = "search_trace")]
+pub use self::tracing::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceTTResult {
+    Miss,
+    Hit(i16),
+}
+
+#[cfg(feature = "search_trace")]
+mod tracing {
+    use std::{
+        fs::File,
+        io::{BufWriter, Result, Write},
+        path::Path,
+    };
+
+    use crate::r#move::Move;
+
+    use super::TraceTTResult;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct TraceLimits {
+        // Nodes deeper than this ply (and their children) are not recorded
+        pub max_ply: u8,
+        // Iterative deepening iterations beyond this depth are not recorded
+        pub max_depth: u8,
+        // Hard cap so a long search can't exhaust memory
+        pub max_nodes: usize,
+    }
+
+    impl Default for TraceLimits {
+        fn default() -> Self {
+            Self {
+                max_ply: u8::MAX,
+                max_depth: u8::MAX,
+                max_nodes: 1_000_000,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct TraceNode {
+        pub id: usize,
+        pub parent: Option<usize>,
+        pub iteration: u8,
+        pub ply: u8,
+        pub m: Option<Move>,
+        pub alpha: i16,
+        pub beta: i16,
+        pub depth: u8,
+        pub extension: u8,
+        pub reduction: u8,
+        pub quiescence: bool,
+        pub tt: TraceTTResult,
+        pub value: Option<i16>,
+    }
+
+    #[derive(Default)]
+    pub struct SearchTrace {
+        pub enabled: bool,
+        pub limits: TraceLimits,
+        // If set the trace is written here (as JSON lines) before the search panics on an internal error
+        pub error_dump_path: Option<String>,
+        nodes: Vec<TraceNode>,
+        // Open nodes, None for nodes that were skipped due to the limits
+        stack: Vec<Option<usize>>,
+        iteration: u8,
+        pending_extension: u8,
+        pending_reduction: u8,
+    }
+
+    impl SearchTrace {
+        pub fn new(limits: TraceLimits) -> Self {
+            Self {
+                enabled: true,
+                limits,
+                ..Default::default()
+            }
+        }
+
+        pub fn nodes(&self) -> &Vec<TraceNode> {
+            &self.nodes
+        }
+
+        pub fn clear(&mut self) {
+            self.nodes.clear();
+            self.stack.clear();
+        }
+
+        pub(crate) fn begin_iteration(&mut self, depth: u8) {
+            self.iteration = depth;
+            self.stack.clear();
+        }
+
+        pub(crate) fn set_child_params(&mut self, extension: u8, reduction: u8) {
+            self.pending_extension = extension;
+            self.pending_reduction = reduction;
+        }
+
+        pub(crate) fn enter(
+            &mut self,
+            m: Option<Move>,
+            alpha: i16,
+            beta: i16,
+            depth: u8,
+            quiescence: bool,
+        ) -> Option<usize> {
+            let ply = self.stack.len() as u8;
+            let parent = match self.stack.last() {
+                Some(p) => *p,
+                None => None,
+            };
+            let parent_skipped = !self.stack.is_empty() && parent.is_none();
+            let extension = self.pending_extension;
+            let reduction = self.pending_reduction;
+            self.pending_extension = 0;
+            self.pending_reduction = 0;
+
+            if !self.enabled
+                || parent_skipped
+                || ply > self.limits.max_ply
+                || self.iteration > self.limits.max_depth
+                || self.nodes.len() >= self.limits.max_nodes
+            {
+                self.stack.push(None);
+                return None;
+            }
+
+            let id = self.nodes.len();
+            self.nodes.push(TraceNode {
+                id,
+                parent,
+                iteration: self.iteration,
+                ply,
+                m,
+                alpha,
+                beta,
+                depth,
+                extension,
+                reduction,
+                quiescence,
+                tt: TraceTTResult::Miss,
+                value: None,
+            });
+            self.stack.push(Some(id));
+            Some(id)
+        }
+
+        pub(crate) fn tt_result(&mut self, result: TraceTTResult) {
+            if let Some(Some(id)) = self.stack.last() {
+                self.nodes[*id].tt = result;
+            }
+        }
+
+        pub(crate) fn exit(&mut self, value: i16) {
+            if let Some(Some(id)) = self.stack.pop() {
+                self.nodes[id].value = Some(value);
+            }
+        }
+
+        pub fn write_json_lines<P: AsRef<Path>>(&self, path: P, max_depth: u8, max_ply: u8) -> Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for node in self.filtered(max_depth, max_ply) {
+                writeln!(writer, "{}", node_to_json(node))?;
+            }
+            writer.flush()
+        }
+
+        pub fn write_dot<P: AsRef<Path>>(&self, path: P, max_depth: u8, max_ply: u8) -> Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            writeln!(writer, "digraph search {{")?;
+            writeln!(writer, "  node [shape=box, fontname=\"monospace\"];")?;
+            for node in self.filtered(max_depth, max_ply) {
+                writeln!(
+                    writer,
+                    "  n{} [label=\"{}\\nd:{} [{}, {}]\\ntt:{} v:{}\"{}];",
+                    node.id,
+                    move_label(node.m),
+                    node.depth,
+                    node.alpha,
+                    node.beta,
+                    tt_label(node.tt),
+                    value_label(node.value),
+                    if node.quiescence { ", style=dashed" } else { "" }
+                )?;
+                if let Some(parent) = node.parent {
+                    if node.extension != 0 || node.reduction != 0 {
+                        writeln!(
+                            writer,
+                            "  n{parent} -> n{} [label=\"+{} -{}\"];",
+                            node.id, node.extension, node.reduction
+                        )?;
+                    } else {
+                        writeln!(writer, "  n{parent} -> n{};", node.id)?;
+                    }
+                }
+            }
+            writeln!(writer, "}}")?;
+            writer.flush()
+        }
+
+        fn filtered(&self, max_depth: u8, max_ply: u8) -> impl Iterator<Item = &TraceNode> {
+            self.nodes
+                .iter()
+                .filter(move |n| n.iteration <= max_depth && n.ply <= max_ply)
+        }
+    }
+
+    fn move_label(m: Option<Move>) -> String {
+        match m {
+            Some(m) => m.uci(),
+            None => "root".into(),
+        }
+    }
+
+    fn tt_label(tt: TraceTTResult) -> String {
+        match tt {
+            TraceTTResult::Miss => "miss".into(),
+            TraceTTResult::Hit(v) => format!("hit({v})"),
+        }
+    }
+
+    fn value_label(value: Option<i16>) -> String {
+        match value {
+            Some(v) => v.to_string(),
+            None => "null".into(),
+        }
+    }
+
+    fn node_to_json(node: &TraceNode) -> String {
+        format!(
+            "{{\"id\":{},\"parent\":{},\"iteration\":{},\"ply\":{},\"move\":{},\"alpha\":{},\"beta\":{},\"depth\":{},\"extension\":{},\"reduction\":{},\"quiescence\":{},\"tt\":\"{}\",\"value\":{}}}",
+            node.id,
+            match node.parent {
+                Some(p) => p.to_string(),
+                None => "null".into(),
+            },
+            node.iteration,
+            node.ply,
+            match node.m {
+                Some(m) => format!("\"{}\"", m.uci()),
+                None => "null".into(),
+            },
+            node.alpha,
+            node.beta,
+            node.depth,
+            node.extension,
+            node.reduction,
+            node.quiescence,
+            tt_label(node.tt),
+            value_label(node.value)
+        )
+    }
+}
+
+#[cfg(feature = "search_trace")]
+impl ChimpEngine {
+    #[inline]
+    pub(super) fn trace_begin_iteration(&mut self, depth: u8) {
+        self.search_trace.begin_iteration(depth);
+    }
+
+    #[inline]
+    pub(super) fn trace_child_params(&mut self, extension: u8, reduction: u8) {
+        self.search_trace.set_child_params(extension, reduction);
+    }
+
+    #[inline]
+    pub(super) fn trace_enter(&mut self, m: Option<Move>, alpha: i16, beta: i16, depth: u8, quiescence: bool) {
+        self.search_trace.enter(m, alpha, beta, depth, quiescence);
+    }
+
+    #[inline]
+    pub(super) fn trace_tt(&mut self, result: TraceTTResult) {
+        self.search_trace.tt_result(result);
+    }
+
+    #[inline]
+    pub(super) fn trace_exit(&mut self, value: i16) -> i16 {
+        self.search_trace.exit(value);
+        value
+    }
+
+    pub(super) fn trace_dump_on_error(&self) {
+        if let Some(path) = &self.search_trace.error_dump_path {
+            if let Err(e) = self.search_trace.write_json_lines(path, u8::MAX, u8::MAX) {
+                log::error!("failed to write search trace to {path}: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "search_trace"))]
+impl ChimpEngine {
+    #[inline(always)]
+    pub(super) fn trace_begin_iteration(&mut self, _depth: u8) {}
+
+    #[inline(always)]
+    pub(super) fn trace_child_params(&mut self, _extension: u8, _reduction: u8) {}
+
+    #[inline(always)]
+    pub(super) fn trace_enter(&mut self, _m: Option<Move>, _alpha: i16, _beta: i16, _depth: u8, _quiescence: bool) {}
+
+    #[inline(always)]
+    pub(super) fn trace_tt(&mut self, _result: TraceTTResult) {}
+
+    #[inline(always)]
+    pub(super) fn trace_exit(&mut self, value: i16) -> i16 {
+        value
+    }
+
+    #[inline(always)]
+    pub(super) fn trace_dump_on_error(&self) {}
+}
+
+#[cfg(all(test, feature = "search_trace"))]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    pub fn trace_records_root_and_children() {
+        let mut engine = ChimpEngine::new();
+        engine.search_trace = SearchTrace::new(TraceLimits::default());
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        engine.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 2);
+
+        let nodes = engine.search_trace.nodes();
+        assert!(nodes.len() > 20);
+        assert_eq!(nodes[0].parent, None);
+        assert_eq!(nodes[0].ply, 0);
+        assert!(nodes.iter().all(|n| n.value.is_some()));
+        assert!(nodes
+            .iter()
+            .filter(|n| n.ply > 0)
+            .all(|n| n.parent.is_some() && n.m.is_some()));
+        assert_eq!(nodes.iter().filter(|n| n.ply == 0).count(), 2);
+    }
+
+    #[test]
+    pub fn trace_respects_ply_limit() {
+        let mut engine = ChimpEngine::new();
+        engine.search_trace = SearchTrace::new(TraceLimits {
+            max_ply: 1,
+            ..Default::default()
+        });
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        engine.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 3);
+
+        assert!(engine.search_trace.nodes().iter().all(|n| n.ply <= 1));
+    }
+
+    #[test]
+    pub fn trace_exports_json_lines_and_dot() {
+        let mut engine = ChimpEngine::new();
+        engine.search_trace = SearchTrace::new(TraceLimits::default());
+        let timeout = Instant::now().checked_add(Duration::from_secs(60)).unwrap();
+        engine.iterative_deepening_to_depth(&|| Instant::now() > timeout, vec![], 2);
+
+        let dir = std::env::temp_dir();
+        let json_path = dir.join("chimp_trace_test.jsonl");
+        let dot_path = dir.join("chimp_trace_test.dot");
+        engine.search_trace.write_json_lines(&json_path, 1, 1).unwrap();
+        engine.search_trace.write_dot(&dot_path, 2, 1).unwrap();
+
+        let json = std::fs::read_to_string(&json_path).unwrap();
+        assert!(json.lines().count() > 1);
+        assert!(json.lines().all(|l| l.starts_with("{\"id\":") && l.contains("\"iteration\":1")));
+        let dot = std::fs::read_to_string(&dot_path).unwrap();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains(" -> "));
+
+        std::fs::remove_file(json_path).unwrap();
+        std::fs::remove_file(dot_path).unwrap();
+    }
+}
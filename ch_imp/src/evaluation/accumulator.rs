@@ -0,0 +1,152 @@
+use crate::{board::board_rep::BoardRep, shared::piece_type::PieceType};
+
+use super::{
+    eval_precomputed_data::{PieceValueBoard, PHASE_MATERIAL_VALUES},
+    endgame::{self, *},
+    opening::{self, *},
+};
+
+// Running totals for the parts of the evaluation that only depend on which piece sits on which square.
+// Scores are from whites point of view, piece counts are indexed by [is_black][piece_type]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalAccumulator {
+    pub opening_material: i16,
+    pub endgame_material: i16,
+    pub opening_piece_square: i16,
+    pub endgame_piece_square: i16,
+    pub phase_material: i16,
+    pub piece_counts: [[u8; 7]; 2],
+}
+
+impl EvalAccumulator {
+    pub fn from_board(board: &BoardRep) -> Self {
+        let mut r = Self::default();
+        let mut occupancy = board.occupancy;
+        while occupancy != 0 {
+            let index = occupancy.trailing_zeros() as u8;
+            let is_black = board.black_occupancy & (1 << index) != 0;
+            r.add_piece(board.get_piece_type_at_index(index), index, is_black);
+            occupancy ^= 1 << index;
+        }
+        r
+    }
+
+    pub fn add_piece(&mut self, piece_type: PieceType, index: u8, is_black: bool) {
+        self.update(piece_type, index, is_black, 1);
+    }
+
+    pub fn remove_piece(&mut self, piece_type: PieceType, index: u8, is_black: bool) {
+        self.update(piece_type, index, is_black, -1);
+    }
+
+    pub fn piece_count(&self, piece_type: PieceType, is_black: bool) -> u8 {
+        self.piece_counts[is_black as usize][piece_type as usize]
+    }
+
+    fn update(&mut self, piece_type: PieceType, index: u8, is_black: bool, direction: i16) {
+        let value_index = piece_type as usize - 1;
+        let sign = if is_black { -direction } else { direction };
+
+        self.opening_material += sign * opening::MATERIAL_VALUES[value_index];
+        self.endgame_material += sign * endgame::MATERIAL_VALUES[value_index];
+        self.opening_piece_square +=
+            sign * opening_square_scores(piece_type, is_black)[index as usize];
+        self.endgame_piece_square +=
+            sign * endgame_square_scores(piece_type, is_black)[index as usize];
+        self.phase_material += direction * PHASE_MATERIAL_VALUES[value_index];
+
+        let count = &mut self.piece_counts[is_black as usize][piece_type as usize];
+        *count = (*count as i16 + direction) as u8;
+    }
+}
+
+fn opening_square_scores(piece_type: PieceType, is_black: bool) -> &'static PieceValueBoard {
+    match (piece_type, is_black) {
+        (PieceType::Pawn, false) => &OPENING_WHITE_PAWN_SQUARE_SCORE,
+        (PieceType::Pawn, true) => &OPENING_BLACK_PAWN_SQUARE_SCORE,
+        (PieceType::Knight, false) => &OPENING_WHITE_KNIGHT_SQUARE_SCORE,
+        (PieceType::Knight, true) => &OPENING_BLACK_KNIGHT_SQUARE_SCORE,
+        (PieceType::Bishop, false) => &OPENING_WHITE_BISHOP_SQUARE_SCORE,
+        (PieceType::Bishop, true) => &OPENING_BLACK_BISHOP_SQUARE_SCORE,
+        (PieceType::Rook, false) => &OPENING_WHITE_ROOK_SQUARE_SCORE,
+        (PieceType::Rook, true) => &OPENING_BLACK_ROOK_SQUARE_SCORE,
+        (PieceType::Queen, false) => &OPENING_WHITE_QUEEN_SQUARE_SCORE,
+        (PieceType::Queen, true) => &OPENING_BLACK_QUEEN_SQUARE_SCORE,
+        (PieceType::King, false) => &OPENING_WHITE_KING_SQUARE_SCORE,
+        (PieceType::King, true) => &OPENING_BLACK_KING_SQUARE_SCORE,
+        (PieceType::None, _) => panic!("Unexpected lack of piece type at square score lookup"),
+    }
+}
+
+fn endgame_square_scores(piece_type: PieceType, is_black: bool) -> &'static PieceValueBoard {
+    match (piece_type, is_black) {
+        (PieceType::Pawn, false) => &ENDGAME_WHITE_PAWN_SQUARE_SCORE,
+        (PieceType::Pawn, true) => &ENDGAME_BLACK_PAWN_SQUARE_SCORE,
+        (PieceType::Knight, false) => &ENDGAME_WHITE_KNIGHT_SQUARE_SCORE,
+        (PieceType::Knight, true) => &ENDGAME_BLACK_KNIGHT_SQUARE_SCORE,
+        (PieceType::Bishop, false) => &ENDGAME_WHITE_BISHOP_SQUARE_SCORE,
+        (PieceType::Bishop, true) => &ENDGAME_BLACK_BISHOP_SQUARE_SCORE,
+        (PieceType::Rook, false) => &ENDGAME_WHITE_ROOK_SQUARE_SCORE,
+        (PieceType::Rook, true) => &ENDGAME_BLACK_ROOK_SQUARE_SCORE,
+        (PieceType::Queen, false) => &ENDGAME_WHITE_QUEEN_SQUARE_SCORE,
+        (PieceType::Queen, true) => &ENDGAME_BLACK_QUEEN_SQUARE_SCORE,
+        (PieceType::King, false) => &ENDGAME_WHITE_KING_SQUARE_SCORE,
+        (PieceType::King, true) => &ENDGAME_BLACK_KING_SQUARE_SCORE,
+        (PieceType::None, _) => panic!("Unexpected lack of piece type at square score lookup"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{match_state::game_state::GameState, shared::piece_type::PieceType};
+
+    use super::*;
+
+    // Plays a handful of lines covering captures, castling, en passant and promotion and checks the
+    // incrementally updated totals against a fresh count after every move
+    #[test]
+    pub fn incremental_updates_match_a_full_recount() {
+        let lines = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                vec![
+                    "e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c8d7", "g1f3", "d7b5", "d2d3", "e7e5",
+                    "d5e6", "f8e7", "e1g1",
+                ],
+            ),
+            (
+                "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                vec!["b7a8q", "e7d8", "a8d8", "e8d8", "e1c1", "h3g2", "h2h4", "g2h1n"],
+            ),
+        ];
+
+        for (fen, moves) in lines {
+            let mut game_state = GameState::new(fen.into());
+            let board = game_state.position.board;
+            assert_eq!(board.eval_accumulator, EvalAccumulator::from_board(&board));
+            for uci in moves {
+                game_state = game_state.make(game_state.parse_uci_move(uci).unwrap());
+                let board = game_state.position.board;
+                assert_eq!(
+                    board.eval_accumulator,
+                    EvalAccumulator::from_board(&board),
+                    "Accumulator diverged after {uci} ({})",
+                    game_state.to_fen()
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn start_position_is_balanced() {
+        let accumulator = EvalAccumulator::from_board(&BoardRep::default());
+        assert_eq!(accumulator.opening_material, 0);
+        assert_eq!(accumulator.endgame_material, 0);
+        assert_eq!(accumulator.opening_piece_square, 0);
+        assert_eq!(accumulator.endgame_piece_square, 0);
+        assert_eq!(accumulator.phase_material, 24);
+        assert_eq!(accumulator.piece_count(PieceType::Pawn, false), 8);
+        assert_eq!(accumulator.piece_count(PieceType::Knight, true), 2);
+        assert_eq!(accumulator.piece_count(PieceType::King, true), 1);
+    }
+}
@@ -0,0 +1,956 @@
+use once_cell::sync::Lazy;
+
+use crate::{
+    board::board_rep::BoardRep,
+    shared::{
+        board_utils::{get_file, get_index_from_file_and_rank, get_rank},
+        piece_type::PieceType,
+    },
+    MOVE_DATA,
+};
+
+const MAX_MEN: u32 = 4;
+const FLIP_RANKS: u8 = 56;
+const MIRROR_FILES: u8 = 7;
+const H_FILE: u64 = 0x0101_0101_0101_0101;
+const A_FILE: u64 = H_FILE << 7;
+
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+// The piece white and then black has besides their king, in the order the tables are built. Captures and
+// promotions only lead into endings earlier in the list, or ones with insufficient material
+const ENDINGS: [(PieceType, PieceType); 18] = [
+    (PieceType::Queen, PieceType::None),
+    (PieceType::Rook, PieceType::None),
+    (PieceType::Pawn, PieceType::None),
+    (PieceType::Queen, PieceType::Queen),
+    (PieceType::Queen, PieceType::Rook),
+    (PieceType::Queen, PieceType::Bishop),
+    (PieceType::Queen, PieceType::Knight),
+    (PieceType::Rook, PieceType::Rook),
+    (PieceType::Rook, PieceType::Bishop),
+    (PieceType::Rook, PieceType::Knight),
+    (PieceType::Bishop, PieceType::Bishop),
+    (PieceType::Bishop, PieceType::Knight),
+    (PieceType::Knight, PieceType::Knight),
+    (PieceType::Queen, PieceType::Pawn),
+    (PieceType::Rook, PieceType::Pawn),
+    (PieceType::Bishop, PieceType::Pawn),
+    (PieceType::Knight, PieceType::Pawn),
+    (PieceType::Pawn, PieceType::Pawn),
+];
+
+// The endings the evaluation asks about. The rest are only needed while building these and are dropped after
+const KEPT: [(PieceType, PieceType); 6] = [
+    (PieceType::Queen, PieceType::None),
+    (PieceType::Rook, PieceType::None),
+    (PieceType::Pawn, PieceType::None),
+    (PieceType::Queen, PieceType::Pawn),
+    (PieceType::Rook, PieceType::Pawn),
+    (PieceType::Pawn, PieceType::Pawn),
+];
+
+static BITBASES: Lazy<Vec<Bitbase>> = Lazy::new(|| {
+    let mut bitbases: Vec<Bitbase> = Vec::new();
+    for (white, black) in ENDINGS {
+        let bitbase = Bitbase::generate(white, black, &bitbases);
+        bitbases.push(bitbase);
+    }
+    bitbases.retain(|bitbase| KEPT.contains(&(bitbase.white, bitbase.black)));
+    bitbases
+});
+
+// From the point of view of the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitbaseResult {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl BitbaseResult {
+    pub fn flip(self) -> Self {
+        match self {
+            BitbaseResult::Win => BitbaseResult::Loss,
+            BitbaseResult::Draw => BitbaseResult::Draw,
+            BitbaseResult::Loss => BitbaseResult::Win,
+        }
+    }
+}
+
+// Builds every table now rather than the first time it's probed, which could be in the middle of a timed search
+pub fn init() {
+    Lazy::force(&BITBASES);
+}
+
+// The result of a position with a king and a pawn, rook or queen against a lone king, or a rook, queen or pawn
+// against a pawn. None for anything else, or when there are castling rights
+pub fn probe(board: &BoardRep) -> Option<BitbaseResult> {
+    if board.occupancy.count_ones() > MAX_MEN
+        || board.white_king_side_castling
+        || board.white_queen_side_castling
+        || board.black_king_side_castling
+        || board.black_queen_side_castling
+    {
+        return None;
+    }
+    let men = Men::from_board(board)?;
+    let result = men.lookup(&BITBASES, board.black_turn)?;
+    if board.ep_index == u8::MAX {
+        return Some(result);
+    }
+
+    // The tables are for positions without en passant, taking it is one more move on top of theirs
+    let capturer = if board.black_turn {
+        MOVE_DATA.white_pawn_captures[board.ep_index as usize]
+    } else {
+        MOVE_DATA.black_pawn_captures[board.ep_index as usize]
+    };
+    let own = if board.black_turn { board.black_occupancy } else { board.white_occupancy };
+    if capturer & board.pawn_bitboard & own == 0 {
+        return Some(result);
+    }
+    let after = men.en_passant(board.black_turn, board.ep_index);
+    let taking = after.lookup(&BITBASES, !board.black_turn)?.flip();
+    Some(best(result, taking))
+}
+
+fn best(a: BitbaseResult, b: BitbaseResult) -> BitbaseResult {
+    match (a, b) {
+        (BitbaseResult::Win, _) | (_, BitbaseResult::Win) => BitbaseResult::Win,
+        (BitbaseResult::Draw, _) | (_, BitbaseResult::Draw) => BitbaseResult::Draw,
+        _ => BitbaseResult::Loss,
+    }
+}
+
+// The kings and the one piece each side can have besides them, PieceType::None when it has none
+#[derive(Clone, Copy, Debug)]
+struct Men {
+    white_king: u8,
+    black_king: u8,
+    white: (PieceType, u8),
+    black: (PieceType, u8),
+}
+
+impl Men {
+    // None when either side has more than one piece
+    fn from_board(board: &BoardRep) -> Option<Self> {
+        let kings = (1 << board.white_king_position) | (1 << board.black_king_position);
+        let piece = |occupancy: u64| match occupancy & !kings {
+            0 => Some((PieceType::None, 0)),
+            piece if piece.count_ones() == 1 => {
+                let square = piece.trailing_zeros() as u8;
+                Some((board.get_piece_type_at_index(square), square))
+            }
+            _ => None,
+        };
+        Some(Self {
+            white_king: board.white_king_position,
+            black_king: board.black_king_position,
+            white: piece(board.white_occupancy)?,
+            black: piece(board.black_occupancy)?,
+        })
+    }
+
+    // The colours swapped and the ranks flipped, which keeps the result for the side to move
+    fn flipped(&self) -> Self {
+        Self {
+            white_king: self.black_king ^ FLIP_RANKS,
+            black_king: self.white_king ^ FLIP_RANKS,
+            white: (self.black.0, self.black.1 ^ FLIP_RANKS),
+            black: (self.white.0, self.white.1 ^ FLIP_RANKS),
+        }
+    }
+
+    // After one pawn takes the other en passant, landing on `to`
+    fn en_passant(&self, black_takes: bool, to: u8) -> Self {
+        let mut after = *self;
+        if black_takes {
+            after.white = (PieceType::None, 0);
+            after.black.1 = to;
+        } else {
+            after.black = (PieceType::None, 0);
+            after.white.1 = to;
+        }
+        after
+    }
+
+    fn lookup(&self, bitbases: &[Bitbase], black_turn: bool) -> Option<BitbaseResult> {
+        let (white, black) = (self.white.0, self.black.0);
+        let minor = |piece_type| matches!(piece_type, PieceType::None | PieceType::Knight | PieceType::Bishop);
+        if black == PieceType::None && minor(white) || white == PieceType::None && minor(black) {
+            return Some(BitbaseResult::Draw);
+        }
+        if let Some(bitbase) = bitbases.iter().find(|bitbase| (bitbase.white, bitbase.black) == (white, black)) {
+            return Some(bitbase.get(self, black_turn));
+        }
+        let bitbase = bitbases.iter().find(|bitbase| (bitbase.white, bitbase.black) == (black, white))?;
+        Some(bitbase.get(&self.flipped(), !black_turn))
+    }
+}
+
+// The result for the side to move when leaving a table, which has to be one built before it
+fn exit_result(bitbases: &[Bitbase], men: &Men, black_turn: bool) -> BitbaseResult {
+    men.lookup(bitbases, black_turn).unwrap_or_else(|| panic!("no bitbase for {men:?}"))
+}
+
+// Masks of black king squares that stay the same through a table's build
+#[derive(Clone, Copy, Default)]
+struct Fixed {
+    legal: u64,
+    // Legal and not stalemate, so the position is lost if every move is
+    decisive: u64,
+    // Captures and promotions that win, or at least don't lose, for the side to move
+    exit_wins: u64,
+    exit_holds: u64,
+    // Taking en passant after the side to move pushes its pawn two squares, for the side that takes
+    en_passant_wins: u64,
+    en_passant_holds: u64,
+}
+
+// Wins and losses for every position with one set of men, found by retrograde analysis. Each word covers every
+// square of the black king for one placement of the other men, so most of the work is done 64 positions at a time.
+// Positions are only stored with the white king on the left half of the board, and on the bottom half too without
+// pawns, the rest are looked up mirrored
+pub struct Bitbase {
+    white: PieceType,
+    black: PieceType,
+    // Indexed by whether black is to move and then by placement
+    // Wins and losses together so a move only has to read one place
+    results: [Vec<(u64, u64)>; 2],
+}
+
+impl Bitbase {
+    // `bitbases` has to cover every ending a capture or promotion can reach, other than ones with insufficient
+    // material
+    fn generate(white: PieceType, black: PieceType, bitbases: &[Bitbase]) -> Self {
+        let mut bitbase = Self {
+            white,
+            black,
+            results: [Vec::new(), Vec::new()],
+        };
+        let size = bitbase.size();
+        bitbase.results = [vec![(0, 0); size], vec![(0, 0); size]];
+        let fixed: [Vec<Fixed>; 2] = [false, true].map(|black_turn| {
+            (0..size).map(|placement| bitbase.fixed(placement, black_turn, bitbases)).collect()
+        });
+
+        // A move into a lost position wins, and a position is lost once every move from it reaches a win for the
+        // opponent. Going over the positions until nothing changes leaves the ones that can be held forever. Only
+        // placements that can reach one that changed are looked at again
+        let mut dirty = [vec![true; size], vec![true; size]];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for black_turn in [false, true] {
+                let turn = black_turn as usize;
+                for placement in 0..size {
+                    let fixed = &fixed[turn][placement];
+                    let (wins, losses) = bitbase.results[turn][placement];
+                    if !dirty[turn][placement] || wins | losses == fixed.legal {
+                        continue;
+                    }
+                    dirty[turn][placement] = false;
+                    let results = bitbase.solve(placement, black_turn, fixed);
+                    if results != (wins, losses) {
+                        bitbase.results[turn][placement] = results;
+                        bitbase.predecessors(placement, black_turn, &mut |previous| dirty[1 - turn][previous] = true);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        bitbase
+    }
+
+    fn get(&self, men: &Men, black_turn: bool) -> BitbaseResult {
+        let symmetry = self.symmetry(men.white_king);
+        let placement = self.placement(
+            men.white_king ^ symmetry,
+            men.white.1 ^ symmetry,
+            men.black.1 ^ symmetry,
+        );
+        let bit = 1 << (men.black_king ^ symmetry);
+        let (wins, losses) = self.results[black_turn as usize][placement];
+        if wins & bit != 0 {
+            BitbaseResult::Win
+        } else if losses & bit != 0 {
+            BitbaseResult::Loss
+        } else {
+            BitbaseResult::Draw
+        }
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.white == PieceType::Pawn || self.black == PieceType::Pawn
+    }
+
+    fn white_king_ranks(&self) -> usize {
+        if self.has_pawns() {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn black_squares(&self) -> usize {
+        if self.black == PieceType::None {
+            1
+        } else {
+            64
+        }
+    }
+
+    fn size(&self) -> usize {
+        4 * self.white_king_ranks() * 64 * self.black_squares()
+    }
+
+    // What to flip the squares by so the white king is on a stored square
+    fn symmetry(&self, white_king: u8) -> u8 {
+        let mut symmetry = 0;
+        if get_file(white_king) > 3 {
+            symmetry |= MIRROR_FILES;
+        }
+        if !self.has_pawns() && get_rank(white_king) > 3 {
+            symmetry |= FLIP_RANKS;
+        }
+        symmetry
+    }
+
+    fn placement(&self, white_king: u8, white: u8, black: u8) -> usize {
+        let black = if self.black == PieceType::None { 0 } else { black as usize };
+        let king = get_file(white_king) as usize * self.white_king_ranks() + get_rank(white_king) as usize;
+        (king * 64 + white as usize) * self.black_squares() + black
+    }
+
+    // The white king, white's piece and black's piece
+    fn squares(&self, placement: usize) -> (u8, u8, u8) {
+        let black = placement % self.black_squares();
+        let white = placement / self.black_squares() % 64;
+        let king = placement / self.black_squares() / 64;
+        let white_king = get_index_from_file_and_rank(
+            (king / self.white_king_ranks()) as u8,
+            (king % self.white_king_ranks()) as u8,
+        );
+        (white_king, white as u8, black as u8)
+    }
+
+    fn men(&self, white_king: u8, white: u8, black: u8) -> Men {
+        Men {
+            white_king,
+            black_king: 0,
+            white: (self.white, white),
+            black: (self.black, black),
+        }
+    }
+
+    fn fixed(&self, placement: usize, black_turn: bool, bitbases: &[Bitbase]) -> Fixed {
+        let (white_king, white, black) = self.squares(placement);
+        let men = self.men(white_king, white, black);
+        let mut fixed = Fixed {
+            legal: self.legal(&men, black_turn),
+            ..Default::default()
+        };
+        if fixed.legal == 0 {
+            return fixed;
+        }
+
+        let mut exit_moves = 0;
+        let mut exit = |after: Men, squares: u64, king_moved: bool| {
+            let squares = squares & fixed.legal;
+            exit_moves |= squares;
+            let result_for = |black_king| {
+                let mut after = after;
+                if !king_moved {
+                    after.black_king = black_king;
+                }
+                exit_result(bitbases, &after, !black_turn).flip()
+            };
+            if king_moved && squares != 0 {
+                let result = result_for(0);
+                fixed.exit_wins |= if result == BitbaseResult::Win { squares } else { 0 };
+                fixed.exit_holds |= if result != BitbaseResult::Loss { squares } else { 0 };
+                return;
+            }
+            for black_king in Bits(squares) {
+                let result = result_for(black_king);
+                fixed.exit_wins |= if result == BitbaseResult::Win { 1 << black_king } else { 0 };
+                fixed.exit_holds |= if result != BitbaseResult::Loss { 1 << black_king } else { 0 };
+            }
+        };
+        let mut has_moves = 0;
+        let mut en_passant = (0, 0);
+        let mut quiet = |_: usize, _: u8, squares: u64, double_push: Option<u8>| {
+            has_moves |= squares;
+            if let Some(to) = double_push {
+                // The result once the other pawn takes the one that was pushed
+                for black_king in Bits(squares & fixed.legal) {
+                    let mut after = men.en_passant(!black_turn, to);
+                    after.black_king = black_king;
+                    let result = exit_result(bitbases, &after, black_turn).flip();
+                    en_passant.0 |= if result == BitbaseResult::Win { 1 << black_king } else { 0 };
+                    en_passant.1 |= if result != BitbaseResult::Loss { 1 << black_king } else { 0 };
+                }
+            }
+        };
+        self.moves(&men, black_turn, &mut quiet, &mut exit);
+        has_moves |= exit_moves;
+
+        let occupancy = men.occupancy();
+        let mut in_check = 0;
+        if black_turn {
+            has_moves |= king_spread(self.king_targets(&men, occupancy));
+            in_check = attacks(self.white, false, white, occupancy);
+        } else if self.black != PieceType::None {
+            in_check = attacked_by(&men.black, true, white_king, occupancy);
+        }
+        fixed.decisive = fixed.legal & (has_moves | in_check);
+        (fixed.en_passant_wins, fixed.en_passant_holds) = en_passant;
+        fixed
+    }
+
+    // Black king squares where `men` is a position that can come up with the given side to move
+    fn legal(&self, men: &Men, black_turn: bool) -> u64 {
+        let (white_king, white, black) = (men.white_king, men.white.1, men.black.1);
+        let pawn_ok = |(piece_type, square): (PieceType, u8)| {
+            piece_type != PieceType::Pawn || (1..7).contains(&get_rank(square))
+        };
+        if white == white_king
+            || self.black != PieceType::None && (black == white_king || black == white)
+            || !pawn_ok(men.white)
+            || !pawn_ok(men.black)
+        {
+            return 0;
+        }
+        let occupancy = men.occupancy();
+        let mut legal = !occupancy & !MOVE_DATA.king_moves[white_king as usize];
+        // The side that just moved can't be in check
+        if black_turn {
+            legal &= !attacked_by(&men.black, true, white_king, occupancy);
+        } else {
+            legal &= !attacks(self.white, false, white, occupancy);
+        }
+        legal
+    }
+
+    // Squares the black king can step to without taking white's piece
+    fn king_targets(&self, men: &Men, occupancy: u64) -> u64 {
+        !occupancy
+            & !MOVE_DATA.king_moves[men.white_king as usize]
+            & !attacks(self.white, false, men.white.1, occupancy)
+    }
+
+    // Calls `quiet` for moves that stay in the table, with the placement they reach, the symmetry to read it with,
+    // the black king squares the move can be made from and, for a double pawn push that can be taken en passant,
+    // the square the taking pawn lands on. Calls `exit` for captures and promotions, with the
+    // men after and the black king squares, which `king_moved` says are where it comes from rather than where it
+    // stays. The black king's own steps that stay in the table are left to the caller
+    fn moves(
+        &self,
+        men: &Men,
+        black_turn: bool,
+        quiet: &mut impl FnMut(usize, u8, u64, Option<u8>),
+        exit: &mut impl FnMut(Men, u64, bool),
+    ) {
+        let (white_king, white, black) = (men.white_king, men.white.1, men.black.1);
+        let occupancy = men.occupancy();
+        let has_black = self.black != PieceType::None;
+
+        if black_turn {
+            // Taking the white piece, as long as the white king doesn't guard it
+            if MOVE_DATA.king_moves[white_king as usize] & (1 << white) == 0 {
+                let mut after = self.men(white_king, 0, black);
+                after.white = (PieceType::None, 0);
+                after.black_king = white;
+                exit(after, MOVE_DATA.king_moves[white as usize], true);
+            }
+            if !has_black {
+                return;
+            }
+
+            // The black king can't be left attacked by the white piece, which only changes when black's piece
+            // lands in its way
+            let without = occupancy & !(1 << black);
+            let attacked = attacks(self.white, false, white, without);
+            let safe = |to: u8| {
+                if is_slider(self.white) && attacked & (1 << to) != 0 {
+                    !attacks(self.white, false, white, without | (1 << to))
+                } else {
+                    !attacked
+                }
+            };
+            let mut taken = |piece_type: PieceType, squares: u64| {
+                let mut after = self.men(white_king, 0, white);
+                after.white = (PieceType::None, 0);
+                after.black = (piece_type, white);
+                exit(after, squares, false);
+            };
+            if self.black == PieceType::Pawn {
+                if MOVE_DATA.black_pawn_captures[black as usize] & (1 << white) != 0 {
+                    if get_rank(white) == 0 {
+                        PROMOTIONS.iter().for_each(|&piece_type| taken(piece_type, !0));
+                    } else {
+                        taken(PieceType::Pawn, !0);
+                    }
+                }
+                let to = black - 8;
+                if occupancy & (1 << to) != 0 {
+                    return;
+                }
+                let squares = !(1 << to) & safe(to);
+                if get_rank(to) == 0 {
+                    for piece_type in PROMOTIONS {
+                        let mut after = *men;
+                        after.black = (piece_type, to);
+                        exit(after, squares, false);
+                    }
+                    return;
+                }
+                quiet(self.placement(white_king, white, to), 0, squares, None);
+                let two = black - 16;
+                if get_rank(black) == 6 && occupancy & (1 << two) == 0 {
+                    let squares = !(1 << to) & !(1 << two) & safe(two);
+                    let en_passant = (self.white == PieceType::Pawn
+                        && get_rank(white) == 4
+                        && get_file(white).abs_diff(get_file(two)) == 1)
+                        .then_some(to);
+                    quiet(self.placement(white_king, white, two), 0, squares, en_passant);
+                }
+                return;
+            }
+            let targets = attacks(self.black, true, black, occupancy) & !(1 << white_king);
+            for to in Bits(targets) {
+                let squares = !between(black, to);
+                if to == white {
+                    taken(self.black, squares);
+                } else {
+                    quiet(self.placement(white_king, white, to), 0, squares & !(1 << to) & safe(to), None);
+                }
+            }
+            return;
+        }
+
+        // The white king can't be left attacked by black's piece
+        let king_safe = |king: u8, occupancy: u64| !attacked_by(&men.black, true, king, occupancy);
+        let mut taken = |king: u8, piece_type: PieceType, square: u8, squares: u64| {
+            let mut after = self.men(king, square, 0);
+            after.white.0 = piece_type;
+            after.black = (PieceType::None, 0);
+            exit(after, squares, false);
+        };
+
+        let king_targets = MOVE_DATA.king_moves[white_king as usize] & !(1 << white);
+        for to in Bits(king_targets) {
+            if has_black && to == black {
+                // Only when the black king doesn't guard the piece
+                taken(to, self.white, white, !MOVE_DATA.king_moves[black as usize]);
+                continue;
+            }
+            let occupancy = occupancy & !(1 << white_king) | (1 << to);
+            let squares = !MOVE_DATA.king_moves[to as usize] & !(1 << to) & king_safe(to, occupancy);
+            let symmetry = self.symmetry(to);
+            quiet(
+                self.placement(to ^ symmetry, white ^ symmetry, black ^ symmetry),
+                symmetry,
+                squares,
+                None,
+            );
+        }
+
+        // White's piece can leave its king attacked unless it lands in the way
+        let exposed = attacked_by(&men.black, true, white_king, occupancy & !(1 << white));
+        let safe = |to: u8| {
+            if between(black, white_king) & (1 << to) != 0 {
+                !0
+            } else {
+                !exposed
+            }
+        };
+        if self.white == PieceType::Pawn {
+            if has_black && MOVE_DATA.white_pawn_captures[white as usize] & (1 << black) != 0 {
+                if get_rank(black) == 7 {
+                    PROMOTIONS.iter().for_each(|&piece_type| taken(white_king, piece_type, black, !0));
+                } else {
+                    taken(white_king, PieceType::Pawn, black, !0);
+                }
+            }
+            let to = white + 8;
+            if occupancy & (1 << to) != 0 {
+                return;
+            }
+            let squares = !(1 << to) & safe(to);
+            if get_rank(to) == 7 {
+                for piece_type in PROMOTIONS {
+                    let mut after = *men;
+                    after.white = (piece_type, to);
+                    exit(after, squares, false);
+                }
+                return;
+            }
+            quiet(self.placement(white_king, to, black), 0, squares, None);
+            let two = white + 16;
+            if get_rank(white) == 1 && occupancy & (1 << two) == 0 {
+                let squares = !(1 << to) & !(1 << two) & safe(two);
+                let en_passant = (self.black == PieceType::Pawn
+                    && get_rank(black) == 3
+                    && get_file(black).abs_diff(get_file(two)) == 1)
+                    .then_some(to);
+                quiet(self.placement(white_king, two, black), 0, squares, en_passant);
+            }
+            return;
+        }
+        let targets = attacks(self.white, false, white, occupancy) & !(1 << white_king);
+        for to in Bits(targets) {
+            let squares = !between(white, to);
+            if has_black && to == black {
+                taken(white_king, self.white, to, squares);
+            } else {
+                let squares = squares & !(1 << to) & safe(to);
+                quiet(self.placement(white_king, to, black), 0, squares, None);
+            }
+        }
+    }
+
+    // Placements with the other side to move with a move into `placement` that stays in the table. Sliders are
+    // allowed through the black king, which only adds placements that get looked at for nothing
+    fn predecessors(&self, placement: usize, black_turn: bool, visit: &mut impl FnMut(usize)) {
+        let (white_king, white, black) = self.squares(placement);
+        let occupancy = self.men(white_king, white, black).occupancy();
+        let free = |square: u8| occupancy & (1 << square) == 0;
+
+        if !black_turn {
+            // The black king's steps stay within the placement
+            visit(placement);
+            if self.black == PieceType::None {
+                return;
+            }
+            if self.black == PieceType::Pawn {
+                let one = black + 8;
+                if get_rank(one) < 7 && free(one) {
+                    visit(self.placement(white_king, white, one));
+                    if get_rank(black) == 4 && free(one + 8) {
+                        visit(self.placement(white_king, white, one + 8));
+                    }
+                }
+                return;
+            }
+            for from in Bits(attacks(self.black, true, black, occupancy) & !occupancy) {
+                visit(self.placement(white_king, white, from));
+            }
+            return;
+        }
+
+        for from in Bits(MOVE_DATA.king_moves[white_king as usize] & !occupancy) {
+            let symmetry = self.symmetry(from);
+            visit(self.placement(from ^ symmetry, white ^ symmetry, black ^ symmetry));
+        }
+        if self.white == PieceType::Pawn {
+            let one = white - 8;
+            if get_rank(one) > 0 && free(one) {
+                visit(self.placement(white_king, one, black));
+                if get_rank(white) == 3 && free(one - 8) {
+                    visit(self.placement(white_king, one - 8, black));
+                }
+            }
+            return;
+        }
+        for from in Bits(attacks(self.white, false, white, occupancy) & !occupancy) {
+            visit(self.placement(white_king, from, black));
+        }
+    }
+
+    // The wins and losses for one placement from the current results of the positions its moves reach
+    fn solve(&self, placement: usize, black_turn: bool, fixed: &Fixed) -> (u64, u64) {
+        let next = !black_turn as usize;
+        let mut wins = fixed.exit_wins;
+        let mut holds = fixed.exit_holds;
+        let mut quiet = |reached: usize, symmetry: u8, squares: u64, double_push: Option<u8>| {
+            let (their_wins, their_losses) = self.results[next][reached];
+            let mut their_wins = transform(their_wins, symmetry);
+            let mut their_losses = transform(their_losses, symmetry);
+            if double_push.is_some() {
+                their_wins |= fixed.en_passant_wins;
+                their_losses &= !fixed.en_passant_holds;
+            }
+            wins |= squares & their_losses;
+            holds |= squares & !their_wins;
+        };
+        let (white_king, white, black) = self.squares(placement);
+        let men = self.men(white_king, white, black);
+        self.moves(&men, black_turn, &mut quiet, &mut |_, _, _| {});
+
+        if black_turn {
+            let targets = self.king_targets(&men, men.occupancy());
+            let (their_wins, their_losses) = self.results[next][placement];
+            wins |= king_spread(targets & their_losses);
+            holds |= king_spread(targets & !their_wins);
+        }
+        (fixed.legal & wins, fixed.decisive & !holds)
+    }
+}
+
+impl Men {
+    // Everything but the black king
+    fn occupancy(&self) -> u64 {
+        let mut occupancy = (1 << self.white_king) | (1 << self.white.1);
+        if self.black.0 != PieceType::None {
+            occupancy |= 1 << self.black.1;
+        }
+        occupancy
+    }
+}
+
+// Black king squares where `piece` attacks `target`. A slider is blocked when the black king is between them
+fn attacked_by(piece: &(PieceType, u8), black: bool, target: u8, occupancy: u64) -> u64 {
+    let (piece_type, square) = *piece;
+    if piece_type == PieceType::None || attacks(piece_type, black, square, occupancy) & (1 << target) == 0 {
+        return 0;
+    }
+    if is_slider(piece_type) {
+        !between(square, target)
+    } else {
+        !0
+    }
+}
+
+fn attacks(piece_type: PieceType, black: bool, square: u8, occupancy: u64) -> u64 {
+    let magic = &MOVE_DATA.magic_bitboard_table;
+    let index = square as usize;
+    match piece_type {
+        PieceType::Pawn if black => MOVE_DATA.black_pawn_captures[index],
+        PieceType::Pawn => MOVE_DATA.white_pawn_captures[index],
+        PieceType::Knight => MOVE_DATA.knight_moves[index],
+        PieceType::Bishop => magic.get_bishop_attacks(index, occupancy),
+        PieceType::Rook => magic.get_rook_attacks(index, occupancy),
+        PieceType::Queen => magic.get_bishop_attacks(index, occupancy) | magic.get_rook_attacks(index, occupancy),
+        PieceType::King => MOVE_DATA.king_moves[index],
+        PieceType::None => 0,
+    }
+}
+
+fn is_slider(piece_type: PieceType) -> bool {
+    matches!(piece_type, PieceType::Bishop | PieceType::Rook | PieceType::Queen)
+}
+
+fn between(from: u8, to: u8) -> u64 {
+    MOVE_DATA.get_slide_inbetween(from, to)
+}
+
+// Every square a king step away from one in `squares`
+fn king_spread(squares: u64) -> u64 {
+    let sideways = ((squares & !H_FILE) >> 1) | ((squares & !A_FILE) << 1);
+    let row = squares | sideways;
+    sideways | (row << 8) | (row >> 8)
+}
+
+// A word of black king squares read with the squares flipped by `symmetry`
+fn transform(mut word: u64, symmetry: u8) -> u64 {
+    if symmetry & MIRROR_FILES != 0 {
+        word = word.reverse_bits().swap_bytes();
+    }
+    if symmetry & FLIP_RANKS != 0 {
+        word = word.swap_bytes();
+    }
+    word
+}
+
+// The squares set in a bitboard
+struct Bits(u64);
+
+impl Iterator for Bits {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use once_cell::sync::Lazy;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{best, init, probe, BitbaseResult, BITBASES, KEPT};
+    use crate::{
+        board::{board_rep::BoardRep, position::Position},
+        evaluation::{endgame_registry::KNOWN_WIN, pawn_structure::PawnStructureCache},
+        move_generation::generate_moves_for_board,
+        r#move::move_list::MoveList,
+        shared::{
+            board_utils::{get_file, get_index_from_file_and_rank, get_rank},
+            piece_type::PieceType,
+        },
+    };
+
+    fn result(fen: &str) -> Option<BitbaseResult> {
+        probe(&BoardRep::from_fen(fen.into()))
+    }
+
+    // None when the pieces share a square, a pawn is on the first or last rank or the kings touch
+    fn fen(pieces: &[(char, u8)], black_turn: bool) -> Option<String> {
+        let mut squares = [None; 64];
+        for &(piece, square) in pieces {
+            let pawn_rank = piece.eq_ignore_ascii_case(&'p') && (get_rank(square) == 0 || get_rank(square) == 7);
+            if squares[square as usize].replace(piece).is_some() || pawn_rank {
+                return None;
+            }
+        }
+        let kings = |piece| pieces.iter().find(|&&(p, _)| p == piece).unwrap().1;
+        if get_file(kings('K')).abs_diff(get_file(kings('k'))) <= 1 && get_rank(kings('K')).abs_diff(get_rank(kings('k'))) <= 1 {
+            return None;
+        }
+
+        let mut ranks = Vec::new();
+        for rank in (0..8).rev() {
+            let mut row = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match squares[get_index_from_file_and_rank(file, rank) as usize] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            row += &empty.to_string();
+                            empty = 0;
+                        }
+                        row.push(piece);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row += &empty.to_string();
+            }
+            ranks.push(row);
+        }
+        Some(format!("{} {} - - 0 1", ranks.join("/"), if black_turn { 'b' } else { 'w' }))
+    }
+
+    #[test]
+    pub fn init_builds_every_table() {
+        init();
+        assert!(Lazy::get(&BITBASES).is_some_and(|bitbases| bitbases.len() == KEPT.len()));
+    }
+
+    // Each result is the best of the results after the moves the engine generates, for random positions from
+    // every table
+    #[test]
+    pub fn results_follow_from_the_moves() {
+        let letter = |piece_type: PieceType| match piece_type {
+            PieceType::Pawn => 'P',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            _ => unreachable!(),
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut moves = MoveList::new();
+        for (white, black) in KEPT {
+            let mut checked = 0;
+            while checked < 1000 {
+                let mut pieces = vec![('K', rng.gen_range(0..64)), ('k', rng.gen_range(0..64))];
+                pieces.push((letter(white), rng.gen_range(0..64)));
+                if black != PieceType::None {
+                    pieces.push((letter(black).to_ascii_lowercase(), rng.gen_range(0..64)));
+                }
+                let Some(fen) = fen(&pieces, rng.gen()) else {
+                    continue;
+                };
+                let position = Position::from_fen(fen.clone());
+                let waiting_in_check = if position.board.black_turn {
+                    position.white_in_check
+                } else {
+                    position.black_in_check
+                };
+                if waiting_in_check {
+                    continue;
+                }
+
+                generate_moves_for_board(position.board, &mut moves);
+                let mut expected = if moves.is_empty() && !position.current_in_check() {
+                    Some(BitbaseResult::Draw)
+                } else {
+                    Some(BitbaseResult::Loss)
+                };
+                for m in moves.iter().map(|s| s.m) {
+                    let (zorb_key, segments) = position.board.zorb_key_after_move(m);
+                    let after = position.apply_segments(segments, zorb_key);
+                    // Some captures and promotions lead to endings that aren't kept
+                    expected = expected.zip(probe(&after.board)).map(|(r, after)| best(r, after.flip()));
+                }
+                if let Some(expected) = expected {
+                    assert_eq!(probe(&position.board), Some(expected), "{fen}");
+                    checked += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn kqk_and_krk() {
+        assert_eq!(result("8/8/8/4k3/8/8/8/4K2Q w - - 0 1"), Some(BitbaseResult::Win));
+        assert_eq!(result("8/8/8/4k3/8/8/8/4K2Q b - - 0 1"), Some(BitbaseResult::Loss));
+        // Mated, stalemated, and the rook can be taken
+        assert_eq!(result("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), Some(BitbaseResult::Loss));
+        assert_eq!(result("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("8/8/8/8/8/8/1r6/K6k w - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("8/8/8/8/8/8/1r6/K6k b - - 0 1"), Some(BitbaseResult::Win));
+        assert_eq!(result("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), None);
+    }
+
+    #[test]
+    pub fn kpk() {
+        // A king on the sixth rank in front of its pawn wins whoever is to move
+        assert_eq!(result("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some(BitbaseResult::Win));
+        assert_eq!(result("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some(BitbaseResult::Loss));
+        assert_eq!(result("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("8/8/8/8/8/3k4/4p3/6K1 b - - 0 1"), Some(BitbaseResult::Win));
+        // Rook pawns are drawn once the defending king reaches the corner
+        assert_eq!(result("7k/8/8/6KP/8/8/8/8 w - - 0 1"), Some(BitbaseResult::Draw));
+        // Outside the square of the pawn
+        assert_eq!(result("8/8/8/P7/8/8/6k1/K7 w - - 0 1"), Some(BitbaseResult::Win));
+        assert_eq!(result("8/8/8/P7/8/8/8/K5k1 b - - 0 1"), Some(BitbaseResult::Loss));
+        assert_eq!(result("k7/8/8/8/8/8/PP6/K7 w - - 0 1"), None);
+    }
+
+    #[test]
+    pub fn pawn_against_rook_or_queen() {
+        // The king blocks the pawn
+        assert_eq!(result("7k/R7/8/8/3p4/8/8/3K4 w - - 0 1"), Some(BitbaseResult::Win));
+        // A pawn supported by its king with the other king too far away
+        assert_eq!(result("R7/8/7K/8/8/2pk4/8/8 b - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("8/8/2PK4/8/7k/8/8/r7 w - - 0 1"), Some(BitbaseResult::Draw));
+        // Bishop and rook pawns on the seventh hold through stalemate, a centre pawn doesn't
+        assert_eq!(result("7K/8/8/8/6Q1/8/1kp5/8 w - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("K7/8/8/8/8/1k6/p7/3Q4 b - - 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("7K/8/8/8/6Q1/8/2kp4/8 w - - 0 1"), Some(BitbaseResult::Win));
+    }
+
+    #[test]
+    pub fn kpkp() {
+        // White wins, but not by pushing two squares as black takes en passant and draws
+        assert_eq!(result("8/8/8/8/5p2/8/6P1/1k5K w - - 0 1"), Some(BitbaseResult::Win));
+        assert_eq!(result("8/8/8/8/5pP1/8/8/1k5K b - g3 0 1"), Some(BitbaseResult::Draw));
+        assert_eq!(result("8/8/8/8/5pP1/8/8/1k5K b - - 0 1"), Some(BitbaseResult::Loss));
+        // Black's pawn queens first once the king gets out of its way
+        assert_eq!(result("8/8/8/8/8/8/P6p/K6k w - - 0 1"), Some(BitbaseResult::Loss));
+    }
+
+    #[test]
+    pub fn evaluation_uses_the_results() {
+        let mut pawn_cache = PawnStructureCache::with_size_mb(1);
+        let mut evaluate = |fen: &str| Position::from_fen(fen.into()).evaluate(&mut pawn_cache);
+        assert_eq!(evaluate("7k/8/8/6KP/8/8/8/8 w - - 0 1"), 0);
+        assert!(evaluate("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") > KNOWN_WIN);
+        assert!(evaluate("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") < -KNOWN_WIN);
+        // The side with the rook is winning however it's coloured, and wins score higher with the losing king on the edge
+        let centre = evaluate("8/8/3k4/8/4K3/8/7r/8 b - - 0 1");
+        let edge = evaluate("8/8/3k4/8/8/8/7r/K7 b - - 0 1");
+        assert!(centre > KNOWN_WIN && edge > KNOWN_WIN);
+        assert!(centre < edge);
+    }
+}
@@ -23,12 +23,12 @@ use super::{
     subcategories::mobility::get_mobility,
     utils::{
         distance_to_center, get_piece_safety_penalty, manhattan_distance,
-        manhattan_distance_to_center, piece_aggregate_score, piece_square_score,
+        manhattan_distance_to_center,
     },
     PieceSafetyInfo,
 };
 
-const MATERIAL_VALUES: PieceValues = [
+pub(super) const MATERIAL_VALUES: PieceValues = [
     195,  // Pawn
     420,  // Knight
     500,  // Bishop
@@ -90,25 +90,24 @@ pub fn calculate(
 }
 
 fn material_score(board: BoardRep) -> i16 {
-    let mut score = 0;
-    score += piece_aggregate_score(board, board.white_occupancy, MATERIAL_VALUES);
-    score -= piece_aggregate_score(board, board.black_occupancy, MATERIAL_VALUES);
+    let accumulator = board.eval_accumulator;
+    let mut score = accumulator.endgame_material;
 
     // Double Bishop reward
-    score += if (board.white_occupancy & board.bishop_bitboard).count_ones() == 2 {
+    score += if accumulator.piece_count(PieceType::Bishop, false) == 2 {
         DOUBLE_BISHOP_REWARD
     } else {
         0
     };
-    score -= if (board.black_occupancy & board.bishop_bitboard).count_ones() == 2 {
+    score -= if accumulator.piece_count(PieceType::Bishop, true) == 2 {
         DOUBLE_BISHOP_REWARD
     } else {
         0
     };
 
     // Pawn advantage
-    let pawn_difference = (board.pawn_bitboard & board.white_occupancy).count_ones() as i16
-        - (board.pawn_bitboard & board.black_occupancy).count_ones() as i16;
+    let pawn_difference = accumulator.piece_count(PieceType::Pawn, false) as i16
+        - accumulator.piece_count(PieceType::Pawn, true) as i16;
     let difference_score =
         i16::signum(pawn_difference) * PAWN_DIFFERENCE_SCORE[i16::abs(pawn_difference) as usize];
     score += difference_score;
@@ -118,52 +117,7 @@ fn material_score(board: BoardRep) -> i16 {
 
 fn piece_positioning_score(board: BoardRep, open_files: u64) -> i16 {
     let mut score = 0;
-    score += piece_square_score(
-        board.white_occupancy & board.pawn_bitboard,
-        ENDGAME_WHITE_PAWN_SQUARE_SCORE,
-    );
-    score -= piece_square_score(
-        board.black_occupancy & board.pawn_bitboard,
-        ENDGAME_BLACK_PAWN_SQUARE_SCORE,
-    );
-
-    score += piece_square_score(
-        board.white_occupancy & board.knight_bitboard,
-        ENDGAME_WHITE_KNIGHT_SQUARE_SCORE,
-    );
-    score -= piece_square_score(
-        board.black_occupancy & board.knight_bitboard,
-        ENDGAME_BLACK_KNIGHT_SQUARE_SCORE,
-    );
-
-    score += piece_square_score(
-        board.white_occupancy & board.bishop_bitboard,
-        ENDGAME_WHITE_BISHOP_SQUARE_SCORE,
-    );
-    score -= piece_square_score(
-        board.black_occupancy & board.bishop_bitboard,
-        ENDGAME_BLACK_BISHOP_SQUARE_SCORE,
-    );
-
-    score += piece_square_score(
-        board.white_occupancy & board.rook_bitboard,
-        ENDGAME_WHITE_ROOK_SQUARE_SCORE,
-    );
-    score -= piece_square_score(
-        board.black_occupancy & board.rook_bitboard,
-        ENDGAME_BLACK_ROOK_SQUARE_SCORE,
-    );
-
-    score += piece_square_score(
-        board.white_occupancy & board.queen_bitboard,
-        ENDGAME_WHITE_QUEEN_SQUARE_SCORE,
-    );
-    score -= piece_square_score(
-        board.black_occupancy & board.queen_bitboard,
-        ENDGAME_BLACK_QUEEN_SQUARE_SCORE,
-    );
-    score += ENDGAME_WHITE_KING_SQUARE_SCORE[board.white_king_position as usize];
-    score -= ENDGAME_BLACK_KING_SQUARE_SCORE[board.black_king_position as usize];
+    score += board.eval_accumulator.endgame_piece_square;
 
     // Knight Outpost
     score += count_knight_outposts(
@@ -0,0 +1,481 @@
+use crate::{
+    board::board_rep::BoardRep,
+    shared::{
+        board_utils::{chebyshev_distance, get_file, get_index_from_file_and_rank, get_rank},
+        piece_type::{Colour, PieceType},
+    },
+};
+
+use super::{
+    bitbase::{self, BitbaseResult},
+    endgame::MATERIAL_VALUES,
+    utils::manhattan_distance_to_center,
+};
+
+// Score of a position known to be won, above anything the evaluation can give but below the tablebase and mate
+// scores
+pub const KNOWN_WIN: i16 = 10000;
+
+// Scale factors are out of this and shrink the endgame part of the evaluation towards a draw
+pub const SCALE_NORMAL: i32 = 64;
+const SCALE_DRAW: i32 = 0;
+
+const EDGE_REWARD: i16 = 20;
+const KING_CLOSENESS_REWARD: i16 = 10;
+const BISHOP_CORNER_REWARD: i16 = 30;
+const PAWN_RANK_REWARD: i16 = 40;
+
+const KRKP_DRAWISH_SCORE: i16 = 80;
+const KRKP_UNCLEAR_SCORE: i16 = 200;
+const KRKP_DISTANCE_PENALTY: i16 = 8;
+
+const PAWNLESS_ROOK_VS_MINOR_SCALE: i32 = 4;
+const PAWNLESS_SCALE: i32 = 14;
+const OPPOSITE_BISHOPS_SCALE: i32 = 18;
+const OPPOSITE_BISHOPS_PAWN_SCALE: i32 = 4;
+const OPPOSITE_BISHOPS_WITH_PIECES_SCALE: i32 = 22;
+const OPPOSITE_BISHOPS_PIECE_SCALE: i32 = 3;
+
+const H_FILE: u64 = 0x0101_0101_0101_0101;
+
+const KEY_PIECES: [PieceType; 5] =
+    [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen];
+const KEY_COUNT_BITS: u32 = 4;
+const KEY_COLOUR_SHIFT: u32 = 20;
+const KEY_COLOUR_MASK: u64 = (1 << KEY_COLOUR_SHIFT) - 1;
+
+// The number of each piece both sides have, 4 bits per piece from pawn to queen with white's in the low half
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialKey(u64);
+
+impl MaterialKey {
+    pub fn from_board(board: &BoardRep) -> Self {
+        let bitboards = [
+            board.pawn_bitboard,
+            board.knight_bitboard,
+            board.bishop_bitboard,
+            board.rook_bitboard,
+            board.queen_bitboard,
+        ];
+        let mut key = 0;
+        for (slot, bitboard) in bitboards.into_iter().enumerate() {
+            let shift = slot as u32 * KEY_COUNT_BITS;
+            key |= ((bitboard & board.white_occupancy).count_ones().min(15) as u64) << shift;
+            key |= ((bitboard & board.black_occupancy).count_ones().min(15) as u64) << (KEY_COLOUR_SHIFT + shift);
+        }
+        Self(key)
+    }
+
+    // Signatures like "KRKP" list white's pieces after the first king and black's after the second
+    pub const fn from_signature(signature: &str) -> Self {
+        let signature = signature.as_bytes();
+        let mut key = 0;
+        let mut colour_shift = 0;
+        let mut i = 0;
+        while i < signature.len() {
+            let piece_type = match signature[i] {
+                b'K' if i == 0 => None,
+                b'K' => {
+                    colour_shift = KEY_COLOUR_SHIFT;
+                    None
+                }
+                b'P' => Some(PieceType::Pawn),
+                b'N' => Some(PieceType::Knight),
+                b'B' => Some(PieceType::Bishop),
+                b'R' => Some(PieceType::Rook),
+                b'Q' => Some(PieceType::Queen),
+                _ => panic!("bad material signature"),
+            };
+            if let Some(piece_type) = piece_type {
+                key += 1 << (colour_shift + key_slot(piece_type) * KEY_COUNT_BITS);
+            }
+            i += 1;
+        }
+        Self(key)
+    }
+
+    // The same material with the colours swapped
+    pub fn flip(self) -> Self {
+        Self((self.0 >> KEY_COLOUR_SHIFT) | ((self.0 & KEY_COLOUR_MASK) << KEY_COLOUR_SHIFT))
+    }
+
+    pub fn count(self, piece_type: PieceType, colour: Colour) -> u32 {
+        let shift = key_slot(piece_type) * KEY_COUNT_BITS + if colour.is_black() { KEY_COLOUR_SHIFT } else { 0 };
+        ((self.0 >> shift) & 0xF) as u32
+    }
+
+    fn non_pawn_material(self, colour: Colour) -> i16 {
+        KEY_PIECES[1..]
+            .iter()
+            .map(|&piece_type| self.count(piece_type, colour) as i16 * material_value(piece_type))
+            .sum()
+    }
+}
+
+const fn key_slot(piece_type: PieceType) -> u32 {
+    piece_type as u32 - 1
+}
+
+// Scores for the strong side, which owns the pieces listed first in the signature. None falls back to the
+// normal evaluation
+type Evaluator = fn(&BoardRep, Colour) -> Option<i16>;
+
+// No ending with its own evaluation has more men than this
+const MAX_ENDGAME_MEN: u32 = 4;
+
+const KPK: MaterialKey = MaterialKey::from_signature("KPK");
+const KRK: MaterialKey = MaterialKey::from_signature("KRK");
+const KQK: MaterialKey = MaterialKey::from_signature("KQK");
+const KBNK: MaterialKey = MaterialKey::from_signature("KBNK");
+const KRKP: MaterialKey = MaterialKey::from_signature("KRKP");
+const KQKP: MaterialKey = MaterialKey::from_signature("KQKP");
+const KPKP: MaterialKey = MaterialKey::from_signature("KPKP");
+const KBK: MaterialKey = MaterialKey::from_signature("KBK");
+const KNK: MaterialKey = MaterialKey::from_signature("KNK");
+
+// Endings are listed with white as the strong side, black's are found by flipping the key
+fn evaluator(key: MaterialKey) -> Option<Evaluator> {
+    Some(match key {
+        KPK => kpk,
+        KRK | KQK => kxk,
+        KBNK => kbnk,
+        KRKP => krkp,
+        KQKP => kqkp,
+        KPKP => kpkp,
+        KBK | KNK => draw,
+        _ => return None,
+    })
+}
+
+// A score from white's point of view when the material has its own evaluation
+pub fn evaluate(board: &BoardRep, key: MaterialKey) -> Option<i16> {
+    if board.occupancy.count_ones() > MAX_ENDGAME_MEN {
+        return None;
+    }
+    if let Some(evaluate) = evaluator(key) {
+        return evaluate(board, Colour::White);
+    }
+    let evaluate = evaluator(key.flip())?;
+    evaluate(board, Colour::Black).map(|score| -score)
+}
+
+// How much of the endgame score the strong side can expect to keep, for endings that are hard to win whatever
+// the material says
+pub fn scale_factor(board: &BoardRep, key: MaterialKey, strong: Colour) -> i32 {
+    let weak = strong.opposite();
+    if key.count(PieceType::Pawn, strong) == 0 {
+        let strong_material = key.non_pawn_material(strong);
+        let weak_material = key.non_pawn_material(weak);
+        if strong_material - weak_material <= material_value(PieceType::Bishop) {
+            return if strong_material < material_value(PieceType::Rook) {
+                SCALE_DRAW
+            } else if weak_material <= material_value(PieceType::Bishop) {
+                PAWNLESS_ROOK_VS_MINOR_SCALE
+            } else {
+                PAWNLESS_SCALE
+            };
+        }
+        return SCALE_NORMAL;
+    }
+
+    if key.non_pawn_material(strong) == material_value(PieceType::Bishop) && wrong_bishop(board, strong) {
+        return SCALE_DRAW;
+    }
+
+    if key.count(PieceType::Bishop, Colour::White) == 1
+        && key.count(PieceType::Bishop, Colour::Black) == 1
+        && opposite_bishops(board)
+    {
+        let only_bishops = key.non_pawn_material(Colour::White) == material_value(PieceType::Bishop)
+            && key.non_pawn_material(Colour::Black) == material_value(PieceType::Bishop);
+        let scale = if only_bishops {
+            let pawn_advantage =
+                key.count(PieceType::Pawn, strong) as i32 - key.count(PieceType::Pawn, weak) as i32;
+            OPPOSITE_BISHOPS_SCALE + OPPOSITE_BISHOPS_PAWN_SCALE * pawn_advantage.max(0)
+        } else {
+            let pieces = occupancy(board, strong).count_ones() as i32;
+            OPPOSITE_BISHOPS_WITH_PIECES_SCALE + OPPOSITE_BISHOPS_PIECE_SCALE * pieces
+        };
+        return scale.min(SCALE_NORMAL);
+    }
+
+    SCALE_NORMAL
+}
+
+fn draw(_: &BoardRep, _: Colour) -> Option<i16> {
+    Some(0)
+}
+
+fn kpk(board: &BoardRep, strong: Colour) -> Option<i16> {
+    let pawn = relative(square(board.pawn_bitboard), strong);
+    bitbase_score(board, strong, get_rank(pawn) as i16 * PAWN_RANK_REWARD)
+}
+
+// King and rook or king and queen against a lone king
+fn kxk(board: &BoardRep, strong: Colour) -> Option<i16> {
+    bitbase_score(board, strong, mating_net(king(board, strong), king(board, strong.opposite())))
+}
+
+// Mate can only be forced in a corner the bishop covers
+fn kbnk(board: &BoardRep, strong: Colour) -> Option<i16> {
+    let strong_king = king(board, strong);
+    let weak_king = king(board, strong.opposite());
+    let corners = if is_dark(square(board.bishop_bitboard)) {
+        [get_index_from_file_and_rank(0, 0), get_index_from_file_and_rank(7, 7)]
+    } else {
+        [get_index_from_file_and_rank(0, 7), get_index_from_file_and_rank(7, 0)]
+    };
+    let corner_distance = corners.iter().map(|&corner| distance(weak_king, corner)).min().unwrap();
+    Some(KNOWN_WIN + mating_net(strong_king, weak_king) + BISHOP_CORNER_REWARD * (7 - corner_distance))
+}
+
+// Rook against pawn. Squares are seen from the rook's side, so the pawn runs down the board. The bitbase decides
+// the result, the rest is for positions it doesn't cover
+fn krkp(board: &BoardRep, strong: Colour) -> Option<i16> {
+    let strong_king = relative(king(board, strong), strong);
+    let weak_king = relative(king(board, strong.opposite()), strong);
+    let rook = relative(square(board.rook_bitboard), strong);
+    let pawn = relative(square(board.pawn_bitboard), strong);
+    let queening_square = get_index_from_file_and_rank(get_file(pawn), 0);
+    let weak_to_move = board.black_turn != strong.is_black();
+    let rook_value = material_value(PieceType::Rook);
+
+    let progress = rook_value + KING_CLOSENESS_REWARD * (7 - distance(strong_king, pawn));
+    if let Some(score) = bitbase_score(board, strong, progress) {
+        return Some(score);
+    }
+
+    // The strong king blocks the pawn, or the weak king is too far from both pawn and rook
+    if get_file(strong_king) == get_file(pawn) && get_rank(strong_king) < get_rank(pawn)
+        || distance(weak_king, pawn) >= 3 + weak_to_move as i16 && distance(weak_king, rook) >= 3
+    {
+        return Some(rook_value - distance(strong_king, pawn));
+    }
+
+    // A far advanced pawn supported by its king, with the strong king out of reach
+    if get_rank(weak_king) <= 2
+        && distance(weak_king, pawn) == 1
+        && get_rank(strong_king) >= 3
+        && distance(strong_king, pawn) > 2 + !weak_to_move as i16
+    {
+        return Some(KRKP_DRAWISH_SCORE - KRKP_DISTANCE_PENALTY * distance(strong_king, pawn));
+    }
+
+    let in_front = pawn - 8;
+    Some(
+        KRKP_UNCLEAR_SCORE
+            - KRKP_DISTANCE_PENALTY
+                * (distance(strong_king, in_front) - distance(weak_king, in_front) - distance(pawn, queening_square)),
+    )
+}
+
+// Queen against pawn. A rook or bishop pawn one step from queening with its king next to it holds the draw
+// through stalemate, which the bitbase knows exactly
+fn kqkp(board: &BoardRep, strong: Colour) -> Option<i16> {
+    let strong_king = king(board, strong);
+    let weak_king = king(board, strong.opposite());
+    let pawn = relative(square(board.pawn_bitboard), strong.opposite());
+
+    let progress = material_value(PieceType::Queen) - material_value(PieceType::Pawn)
+        + mating_net(strong_king, weak_king);
+    if let Some(score) = bitbase_score(board, strong, progress) {
+        return Some(score);
+    }
+
+    let mut score = KING_CLOSENESS_REWARD * (7 - distance(strong_king, weak_king));
+    if get_rank(pawn) != 6 || distance(relative(weak_king, strong.opposite()), pawn) != 1
+        || !matches!(get_file(pawn), 0 | 2 | 5 | 7)
+    {
+        score += material_value(PieceType::Queen) - material_value(PieceType::Pawn)
+            + EDGE_REWARD * manhattan_distance_to_center(weak_king);
+    }
+    Some(score)
+}
+
+// A pawn each, so either side can be the one winning
+fn kpkp(board: &BoardRep, _: Colour) -> Option<i16> {
+    let progress = |colour| {
+        let pawn = relative(square(board.pawn_bitboard & occupancy(board, colour)), colour);
+        get_rank(pawn) as i16 * PAWN_RANK_REWARD
+    };
+    let score = bitbase_score(board, Colour::White, progress(Colour::White))?;
+    if score < 0 {
+        bitbase_score(board, Colour::Black, progress(Colour::Black)).map(|score| -score)
+    } else {
+        Some(score)
+    }
+}
+
+// The strong side only has a bishop and rook pawns, all on one file, and the weak king sits in the corner the
+// bishop can't cover
+fn wrong_bishop(board: &BoardRep, strong: Colour) -> bool {
+    let pawns = board.pawn_bitboard & occupancy(board, strong);
+    let file = get_file(square(pawns));
+    if file != 0 && file != 7 || pawns & !(H_FILE << (7 - file)) != 0 {
+        return false;
+    }
+    let queening_square = relative(get_index_from_file_and_rank(file, 7), strong);
+    let bishop = square(board.bishop_bitboard & occupancy(board, strong));
+    is_dark(bishop) != is_dark(queening_square) && distance(king(board, strong.opposite()), queening_square) <= 1
+}
+
+fn opposite_bishops(board: &BoardRep) -> bool {
+    let white_bishop = square(board.bishop_bitboard & board.white_occupancy);
+    let black_bishop = square(board.bishop_bitboard & board.black_occupancy);
+    is_dark(white_bishop) != is_dark(black_bishop)
+}
+
+// None when the position isn't in the bitbase, such as when it still has castling rights
+fn bitbase_score(board: &BoardRep, strong: Colour, progress: i16) -> Option<i16> {
+    let result = bitbase::probe(board)?;
+    let result = if board.black_turn == strong.is_black() {
+        result
+    } else {
+        result.flip()
+    };
+    Some(match result {
+        BitbaseResult::Win => KNOWN_WIN + progress,
+        BitbaseResult::Draw => 0,
+        BitbaseResult::Loss => -KNOWN_WIN,
+    })
+}
+
+// Drive the weak king to the edge and bring the strong king next to it
+fn mating_net(strong_king: u8, weak_king: u8) -> i16 {
+    EDGE_REWARD * manhattan_distance_to_center(weak_king) + KING_CLOSENESS_REWARD * (7 - distance(strong_king, weak_king))
+}
+
+fn material_value(piece_type: PieceType) -> i16 {
+    MATERIAL_VALUES[piece_type as usize - 1]
+}
+
+fn occupancy(board: &BoardRep, colour: Colour) -> u64 {
+    if colour.is_black() {
+        board.black_occupancy
+    } else {
+        board.white_occupancy
+    }
+}
+
+fn king(board: &BoardRep, colour: Colour) -> u8 {
+    if colour.is_black() {
+        board.black_king_position
+    } else {
+        board.white_king_position
+    }
+}
+
+fn square(bitboard: u64) -> u8 {
+    bitboard.trailing_zeros() as u8
+}
+
+// Ranks are flipped for black so the given side always plays up the board
+fn relative(square: u8, colour: Colour) -> u8 {
+    if colour.is_black() {
+        square ^ 56
+    } else {
+        square
+    }
+}
+
+fn distance(a: u8, b: u8) -> i16 {
+    chebyshev_distance(a as i8, b as i8) as i16
+}
+
+fn is_dark(square: u8) -> bool {
+    (get_file(square) + get_rank(square)) & 1 == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate, scale_factor, MaterialKey, KNOWN_WIN, SCALE_DRAW, SCALE_NORMAL};
+    use crate::{
+        board::board_rep::BoardRep,
+        shared::piece_type::{Colour, PieceType},
+    };
+
+    fn score(fen: &str) -> Option<i16> {
+        let board = BoardRep::from_fen(fen.into());
+        evaluate(&board, MaterialKey::from_board(&board))
+    }
+
+    fn scale(fen: &str, strong: Colour) -> i32 {
+        let board = BoardRep::from_fen(fen.into());
+        scale_factor(&board, MaterialKey::from_board(&board), strong)
+    }
+
+    #[test]
+    pub fn material_keys() {
+        let board = BoardRep::from_fen("4k3/8/8/8/3p4/8/8/R3K3 w - - 0 1".into());
+        let key = MaterialKey::from_board(&board);
+        assert_eq!(key, MaterialKey::from_signature("KRKP"));
+        assert_eq!(key.flip(), MaterialKey::from_signature("KPKR"));
+        assert_eq!(key.count(PieceType::Rook, Colour::White), 1);
+        assert_eq!(key.count(PieceType::Pawn, Colour::Black), 1);
+        assert_eq!(key.count(PieceType::Pawn, Colour::White), 0);
+        assert_eq!(MaterialKey::from_board(&BoardRep::default()), MaterialKey::from_signature("KQRRBBNNPPPPPPPPKQRRBBNNPPPPPPPP"));
+    }
+
+    #[test]
+    pub fn kbnk_drives_to_the_bishops_corner() {
+        let right = score("8/8/8/8/4N3/2K5/8/k1B5 w - - 0 1").unwrap();
+        let wrong = score("k7/8/2K5/8/4N3/8/8/2B5 w - - 0 1").unwrap();
+        assert!(wrong > KNOWN_WIN && right > wrong);
+        assert_eq!(score("8/8/8/8/4n3/2k5/8/K1b5 w - - 0 1").unwrap(), -right);
+    }
+
+    #[test]
+    pub fn positions_missing_from_the_bitbase_use_the_normal_evaluation() {
+        assert!(score("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap() > KNOWN_WIN);
+        assert_eq!(score("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), None);
+    }
+
+    #[test]
+    pub fn krkp() {
+        // The king blocks the pawn
+        assert!(score("7k/R7/8/8/3p4/8/8/3K4 w - - 0 1").unwrap() > KNOWN_WIN);
+        // The pawn is supported and the king is too far away
+        assert_eq!(score("R7/8/7K/8/8/2pk4/8/8 b - - 0 1"), Some(0));
+        assert_eq!(score("8/8/2PK4/8/7k/8/8/r7 w - - 0 1"), Some(0));
+        // Castling rights keep it out of the bitbase
+        let unclear = score("4k3/8/8/8/3p4/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(unclear > 0 && unclear < KNOWN_WIN);
+    }
+
+    #[test]
+    pub fn kqkp() {
+        assert_eq!(score("7K/8/8/8/6Q1/8/1kp5/8 w - - 0 1"), Some(0));
+        assert!(score("7K/8/8/8/6Q1/8/2kp4/8 w - - 0 1").unwrap() > KNOWN_WIN);
+        assert!(score("7k/8/8/8/6q1/8/2KP4/8 b - - 0 1").unwrap() < -KNOWN_WIN);
+    }
+
+    #[test]
+    pub fn kpkp_scores_whichever_side_wins() {
+        assert!(score("8/8/8/8/5p2/8/6P1/1k5K w - - 0 1").unwrap() > KNOWN_WIN);
+        assert!(score("8/8/8/8/8/8/P6p/K6k w - - 0 1").unwrap() < -KNOWN_WIN);
+        assert_eq!(score("8/8/8/8/5pP1/8/8/1k5K b - g3 0 1"), Some(0));
+    }
+
+    #[test]
+    pub fn lone_minor_pieces_cannot_win() {
+        assert_eq!(score("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), Some(0));
+        assert_eq!(score("4k3/8/8/3n4/8/8/8/4K3 w - - 0 1"), Some(0));
+        assert_eq!(score("4k3/8/8/3n4/8/8/4P3/4K3 w - - 0 1"), None);
+        assert_eq!(scale("4k3/8/8/3n4/8/8/4P3/4K3 w - - 0 1", Colour::Black), SCALE_DRAW);
+    }
+
+    #[test]
+    pub fn drawish_endings_scale_down() {
+        // Rook against minor piece without pawns
+        assert_eq!(scale("4k3/3b4/8/8/8/8/8/R3K3 w - - 0 1", Colour::White), 4);
+        assert_eq!(scale("4k3/3q4/8/8/8/8/8/R3K3 w - - 0 1", Colour::Black), SCALE_NORMAL);
+        // Opposite and same coloured bishops
+        assert_eq!(scale("4k3/5p2/8/3b4/8/8/4PP2/2B1K3 w - - 0 1", Colour::White), 22);
+        assert_eq!(scale("4k3/5p2/8/4b3/8/8/4PP2/2B1K3 w - - 0 1", Colour::White), SCALE_NORMAL);
+        // A rook pawn with a bishop that doesn't cover the queening square
+        assert_eq!(scale("7k/8/8/7P/8/3B4/8/4K3 b - - 0 1", Colour::White), SCALE_DRAW);
+        assert_eq!(scale("7k/8/8/7P/8/4B3/8/4K3 b - - 0 1", Colour::White), SCALE_NORMAL);
+        assert_eq!(scale("8/8/8/8/8/3b4/p4k2/K7 w - - 0 1", Colour::Black), SCALE_DRAW);
+        assert_eq!(scale("8/8/8/8/8/4b3/p4k2/K7 w - - 0 1", Colour::Black), SCALE_NORMAL);
+    }
+}
@@ -5,13 +5,17 @@ use crate::{
         attack_and_defend_lookups::AttackAndDefendTable, board_rep::BoardRep,
         king_position_analysis::ThreatRaycastCollision, see::piece_safety,
     },
-    evaluation::pawn_structure::get_pawn_structure_eval,
-    shared::piece_type::PieceType,
+    evaluation::{
+        endgame_registry::{MaterialKey, SCALE_NORMAL},
+        pawn_structure::{get_pawn_structure_eval, PawnStructureCache},
+    },
+    shared::piece_type::{Colour, PieceType},
 };
 
-use self::{eval_precomputed_data::PHASE_MATERIAL_VALUES, utils::piece_aggregate_score};
-
+pub mod accumulator;
+pub mod bitbase;
 pub mod endgame;
+pub mod endgame_registry;
 mod eval_precomputed_data;
 pub mod opening;
 pub mod pawn_structure;
@@ -35,13 +39,20 @@ pub fn calculate(
     black_in_check: bool,
     black_pins: Vec<ThreatRaycastCollision>,
     white_pins: Vec<ThreatRaycastCollision>,
+    pawn_cache: &mut PawnStructureCache,
 ) -> i16 {
+    let material_key = MaterialKey::from_board(&board);
+    if let Some(score) = endgame_registry::evaluate(&board, material_key) {
+        return score;
+    }
+
     let phase = calculate_game_phase(board) as i32;
     let mut ad_table = AttackAndDefendTable::new();
 
     let piece_safety_results = generate_piece_safety(&mut ad_table, board);
 
     let pawn_structure_eval = get_pawn_structure_eval(
+        pawn_cache,
         board.king_pawn_zorb,
         board.white_occupancy & board.pawn_bitboard,
         board.black_occupancy & board.pawn_bitboard,
@@ -67,6 +78,8 @@ pub fn calculate(
         pawn_structure_eval.open_files,
         &piece_safety_results,
     ) as i32;
+    let strong = Colour::from_is_black(endgame < 0);
+    let endgame = endgame * endgame_registry::scale_factor(&board, material_key, strong) / SCALE_NORMAL;
 
     let result = ((opening * (256 - phase)) + (endgame * phase)) / 256;
 
@@ -79,8 +92,7 @@ pub fn calculate(
 }
 
 pub fn calculate_game_phase(board: BoardRep) -> i16 {
-    let material_score = MAX_PHASE_MATERIAL_SCORE
-        - piece_aggregate_score(board, board.occupancy, PHASE_MATERIAL_VALUES);
+    let material_score = MAX_PHASE_MATERIAL_SCORE - board.eval_accumulator.phase_material;
     return (material_score * 256 + (MAX_PHASE_MATERIAL_SCORE / 2)) / MAX_PHASE_MATERIAL_SCORE;
 }
 
@@ -11,6 +11,7 @@ use crate::{
         see::{piece_safety, square_control},
     },
     move_generation::sliding::queen::generate_queen_moves,
+    r#move::move_list::MoveList,
     shared::{
         board_utils::{
             chebyshev_distance, get_coords_from_index, get_file, get_rank, index_from_coords,
@@ -29,7 +30,7 @@ use super::{
     PieceSafetyInfo,
 };
 
-const MATERIAL_VALUES: PieceValues = [
+pub(super) const MATERIAL_VALUES: PieceValues = [
     110,  // Pawn
     400,  // Knight
     450,  // Bishop
@@ -187,25 +188,24 @@ pub fn calculate(
 }
 
 fn material_score(board: BoardRep) -> i16 {
-    let mut score = 0;
-    score += piece_aggregate_score(board, board.white_occupancy, MATERIAL_VALUES);
-    score -= piece_aggregate_score(board, board.black_occupancy, MATERIAL_VALUES);
+    let accumulator = board.eval_accumulator;
+    let mut score = accumulator.opening_material;
 
     // Double Bishop reward
-    score += if (board.white_occupancy & board.bishop_bitboard).count_ones() == 2 {
+    score += if accumulator.piece_count(PieceType::Bishop, false) == 2 {
         DOUBLE_BISHOP_REWARD
     } else {
         0
     };
-    score -= if (board.black_occupancy & board.bishop_bitboard).count_ones() == 2 {
+    score -= if accumulator.piece_count(PieceType::Bishop, true) == 2 {
         DOUBLE_BISHOP_REWARD
     } else {
         0
     };
 
     // Pawn advantage
-    let pawn_difference = (board.pawn_bitboard & board.white_occupancy).count_ones() as i16
-        - (board.pawn_bitboard & board.black_occupancy).count_ones() as i16;
+    let pawn_difference = accumulator.piece_count(PieceType::Pawn, false) as i16
+        - accumulator.piece_count(PieceType::Pawn, true) as i16;
     let difference_score =
         i16::signum(pawn_difference) * PAWN_DIFFERENCE_SCORE[i16::abs(pawn_difference) as usize];
     score += difference_score;
@@ -222,52 +222,7 @@ fn piece_positioning_score(
 ) -> i16 {
     let mut eval = 0;
     // Square score
-    eval += piece_square_score(
-        board.white_occupancy & board.pawn_bitboard,
-        OPENING_WHITE_PAWN_SQUARE_SCORE,
-    );
-    eval -= piece_square_score(
-        board.black_occupancy & board.pawn_bitboard,
-        OPENING_BLACK_PAWN_SQUARE_SCORE,
-    );
-
-    eval += piece_square_score(
-        board.white_occupancy & board.knight_bitboard,
-        OPENING_WHITE_KNIGHT_SQUARE_SCORE,
-    );
-    eval -= piece_square_score(
-        board.black_occupancy & board.knight_bitboard,
-        OPENING_BLACK_KNIGHT_SQUARE_SCORE,
-    );
-
-    eval += piece_square_score(
-        board.white_occupancy & board.bishop_bitboard,
-        OPENING_WHITE_BISHOP_SQUARE_SCORE,
-    );
-    eval -= piece_square_score(
-        board.black_occupancy & board.bishop_bitboard,
-        OPENING_BLACK_BISHOP_SQUARE_SCORE,
-    );
-
-    eval += piece_square_score(
-        board.white_occupancy & board.rook_bitboard,
-        OPENING_WHITE_ROOK_SQUARE_SCORE,
-    );
-    eval -= piece_square_score(
-        board.black_occupancy & board.rook_bitboard,
-        OPENING_BLACK_ROOK_SQUARE_SCORE,
-    );
-
-    eval += piece_square_score(
-        board.white_occupancy & board.queen_bitboard,
-        OPENING_WHITE_QUEEN_SQUARE_SCORE,
-    );
-    eval -= piece_square_score(
-        board.black_occupancy & board.queen_bitboard,
-        OPENING_BLACK_QUEEN_SQUARE_SCORE,
-    );
-    eval += OPENING_WHITE_KING_SQUARE_SCORE[board.white_king_position as usize];
-    eval -= OPENING_BLACK_KING_SQUARE_SCORE[board.black_king_position as usize];
+    eval += board.eval_accumulator.opening_piece_square;
 
     // Development
     eval += under_developed_penalty(board, board.white_occupancy);
@@ -390,7 +345,8 @@ fn mobility_score(board: BoardRep) -> i16 {
 
 // King openness is a penalty for each square the king could reach if they were a queen
 fn king_openness(king_pos: u8, board: BoardRep, ad_table: &mut AttackAndDefendTable) -> i16 {
-    let possible_queen_moves = generate_queen_moves(
+    let mut possible_queen_moves = MoveList::new();
+    generate_queen_moves(
         king_pos,
         board,
         ad_table,
@@ -399,7 +355,8 @@ fn king_openness(king_pos: u8, board: BoardRep, ad_table: &mut AttackAndDefendTa
         None,
         None,
         None,
-        0
+        0,
+        &mut possible_queen_moves,
     );
     possible_queen_moves.len() as i16
 }
@@ -1,11 +1,14 @@
-use std::{collections::HashMap, sync::RwLock};
+use std::mem::size_of;
 
 use log::error;
-use rand::RngCore;
 
 use crate::{
     board::{bitboard::Bitboard, board_rep::BoardRep, position::Position},
-    shared::board_utils::{get_file, get_rank, reverse_position_orientation},
+    shared::{
+        board_utils::{get_file, get_rank, reverse_position_orientation},
+        cache::slot_count,
+        seeded_rng::SeededRng,
+    },
 };
 
 use super::{shared::{BOARD_FILES, CENTER_FILES}, subcategories::{king::{pawn_distance::get_pawn_distance_penalty, is_on_pawnless_file::is_on_pawnless_file}, pawn::{utils::file_fill, files::open_files}}};
@@ -60,9 +63,16 @@ pub struct PawnZorb {
     king_table: [[u64; 2]; 64],
 }
 
+// Fixed so pawn hash keys, and therefore pawn cache collisions, are the same on every run
+pub const PAWN_ZORB_SEED: u64 = 0x9a3f_7e21_c4d8_0b65;
+
 impl PawnZorb {
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
+        Self::from_seed(PAWN_ZORB_SEED)
+    }
+
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = SeededRng::new(seed);
 
         let mut pawn_table: [[u64; 2]; 48] = [[0; 2]; 48];
         let mut king_table: [[u64; 2]; 64] = [[0; 2]; 64];
@@ -116,12 +126,78 @@ impl PawnZorb {
     }
 }
 
-lazy_static! {
-    static ref PAWN_STRUCTURE_EVAL_HASHMAP: RwLock<HashMap<u64, PawnStructureEval>> =
-        RwLock::new(HashMap::new());
+// Pawn structures repeat a lot so a small table covers most of a search
+const DEFAULT_PAWN_STRUCTURE_CACHE_MB_SIZE: usize = 2;
+
+#[derive(Clone, Copy)]
+struct PawnStructureCacheEntry {
+    zorb_key: u64,
+    eval: PawnStructureEval,
+}
+
+// Fixed size, always replace. Allocated on the first store
+pub struct PawnStructureCache {
+    table: Vec<Option<PawnStructureCacheEntry>>,
+    slots: usize,
+}
+
+impl Default for PawnStructureCache {
+    fn default() -> Self {
+        Self::with_size_mb(DEFAULT_PAWN_STRUCTURE_CACHE_MB_SIZE)
+    }
+}
+
+impl PawnStructureCache {
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        Self {
+            table: Vec::new(),
+            slots: slot_count::<Option<PawnStructureCacheEntry>>(size_mb),
+        }
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        let slots = slot_count::<Option<PawnStructureCacheEntry>>(size_mb);
+        if slots != self.slots {
+            *self = Self::with_size_mb(size_mb);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.table.len() * size_of::<Option<PawnStructureCacheEntry>>()
+    }
+
+    fn lookup(&self, zorb_key: u64, p_count: u8) -> Result<Option<PawnStructureEval>, String> {
+        if self.table.is_empty() {
+            return Ok(None);
+        }
+        match self.table[(zorb_key as usize) % self.slots] {
+            Some(entry) if entry.zorb_key == zorb_key => {
+                if p_count != entry.eval.p_count {
+                    return Err(format!(
+                        "Pawn Zorb_key conflict {zorb_key}. p_count {p_count}!={}",
+                        entry.eval.p_count
+                    ));
+                }
+                Ok(Some(entry.eval))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn store(&mut self, zorb_key: u64, eval: PawnStructureEval) {
+        if self.table.is_empty() {
+            self.table = vec![None; self.slots];
+        }
+        self.table[(zorb_key as usize) % self.slots] = Some(PawnStructureCacheEntry { zorb_key, eval });
+    }
 }
 
 pub fn get_pawn_structure_eval(
+    cache: &mut PawnStructureCache,
     zorb_key: u64,
     w_pawns: u64,
     b_pawns: u64,
@@ -132,12 +208,14 @@ pub fn get_pawn_structure_eval(
     if p_count == 0 {
         return PawnStructureEval::default();
     }
-    match lookup(zorb_key, p_count as u8) {
+    match cache.lookup(zorb_key, p_count as u8) {
         Ok(option) => match option {
             Some(r) => r,
-            None => build_and_store_pawn_structure_eval(
-                zorb_key, w_pawns, b_pawns, w_king, b_king, p_count,
-            ),
+            None => {
+                let eval = build_pawn_pawn_structure_eval(w_pawns, b_pawns, w_king, b_king, p_count);
+                cache.store(zorb_key, eval);
+                eval
+            }
         },
         Err(r) => {
             error!("{r}");
@@ -146,19 +224,6 @@ pub fn get_pawn_structure_eval(
     }
 }
 
-fn build_and_store_pawn_structure_eval(
-    zorb_key: u64,
-    w_pawns: u64,
-    b_pawns: u64,
-    w_king: u8,
-    b_king: u8,
-    p_count: u32,
-) -> PawnStructureEval {
-    let eval = build_pawn_pawn_structure_eval(w_pawns, b_pawns, w_king, b_king, p_count);
-    store(zorb_key, eval);
-    eval
-}
-
 fn build_pawn_pawn_structure_eval(
     w_pawns: u64,
     b_pawns: u64,
@@ -297,31 +362,6 @@ fn get_passed_reward(mut passed_pawns: u64, passed_rewards: [i16;8]) -> i16 {
     r
 }
 
-fn lookup(zorb_key: u64, p_count: u8) -> Result<Option<PawnStructureEval>, String> {
-    let binding = PAWN_STRUCTURE_EVAL_HASHMAP.try_read().unwrap();
-    let r = binding.get(&zorb_key);
-
-    match r {
-        Some(&result) => {
-            if p_count != result.p_count {
-                return Err(format!(
-                    "Pawn Zorb_key conflict {zorb_key}. p_count {p_count}!={}",
-                    result.p_count
-                ));
-            }
-            Ok(Some(result))
-        }
-        None => Ok(None),
-    }
-}
-
-fn store(zorb_key: u64, eval: PawnStructureEval) {
-    PAWN_STRUCTURE_EVAL_HASHMAP
-        .write()
-        .unwrap()
-        .insert(zorb_key, eval);
-}
-
 pub fn calculate_frontspan(mut pawn_occupancy: u64) -> u64 {
     let mut r = 0;
     while pawn_occupancy != 0 {
@@ -471,6 +511,38 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn pawn_zorb_keys_are_reproducible() {
+        let board = BoardRep::from_fen("4k3/pp3ppp/8/3p4/3P4/8/PP3PPP/4K3 w - - 0 1".into());
+        assert_eq!(PawnZorb::new().hash(board), PawnZorb::new().hash(board));
+        assert_eq!(PawnZorb::new().hash(board), PawnZorb::from_seed(PAWN_ZORB_SEED).hash(board));
+        assert_ne!(PawnZorb::new().hash(board), PawnZorb::from_seed(1).hash(board));
+    }
+
+    #[test]
+    fn pawn_structure_cache_replaces_colliding_entries() {
+        let mut cache = PawnStructureCache::with_size_mb(1);
+        assert_eq!(cache.memory_bytes(), 0);
+        let eval = PawnStructureEval {
+            opening: 5,
+            endgame: 7,
+            open_files: 0,
+            p_count: 3,
+        };
+        cache.store(11, eval);
+        assert_eq!(cache.lookup(11, 3).unwrap().unwrap().endgame, 7);
+        assert!(cache.lookup(11, 4).is_err());
+
+        let colliding_key = 11 + cache.slots as u64;
+        cache.store(colliding_key, eval);
+        assert!(cache.lookup(11, 3).unwrap().is_none());
+        assert!(cache.lookup(colliding_key, 3).unwrap().is_some());
+
+        cache.clear();
+        assert!(cache.lookup(colliding_key, 3).unwrap().is_none());
+        assert_eq!(cache.memory_bytes(), cache.slots * size_of::<Option<PawnStructureCacheEntry>>());
+    }
+
     #[test]
     fn calculate_frontspan_single_pawn() {
         let pawn_occupancy = 0.set(8);
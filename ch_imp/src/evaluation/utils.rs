@@ -1,12 +1,12 @@
 use log::trace;
 
 use crate::{
-    board::{bitboard::Bitboard, board_rep::BoardRep, position::Position},
+    board::{bitboard::Bitboard, position::Position},
     shared::board_utils::get_coords_from_index,
 };
 
 use super::{
-    eval_precomputed_data::{PieceValueBoard, PieceValues},
+    eval_precomputed_data::PieceValues,
     PieceSafetyInfo,
 };
 
@@ -17,10 +17,6 @@ pub(super) fn half_board_occupancy_score(pov_occupancy: u64, pov_board: u32, fac
     (pov_occupancy & pov_board as u64).count_ones() as i16 * factor
 }
 
-pub(super) fn board_occupancy_score(occupancy: u64, board: u64, factor: i16) -> i16 {
-    (occupancy & board as u64).count_ones() as i16 * factor
-}
-
 pub(super) fn piece_positional_reward(occupancy: u64, index: u8, factor: i16) -> i16 {
     if occupancy.occupied(index) {
         factor
@@ -29,29 +25,6 @@ pub(super) fn piece_positional_reward(occupancy: u64, index: u8, factor: i16) ->
     }
 }
 
-pub(super) fn piece_aggregate_score(board: BoardRep, occ: u64, piece_value: PieceValues) -> i16 {
-    let mut r = 0;
-
-    r += board_occupancy_score(board.pawn_bitboard, occ, piece_value[0]);
-    r += board_occupancy_score(board.knight_bitboard, occ, piece_value[1]);
-    r += board_occupancy_score(board.bishop_bitboard, occ, piece_value[2]);
-    r += board_occupancy_score(board.rook_bitboard, occ, piece_value[3]);
-    r += board_occupancy_score(board.queen_bitboard, occ, piece_value[4]);
-
-    r
-}
-
-pub(super) fn piece_square_score(piece_bitboard: u64, piece_value_board: PieceValueBoard) -> i16 {
-    let mut bb = piece_bitboard;
-    let mut r = 0;
-    while bb != 0 {
-        let lsb = bb.trailing_zeros() as usize;
-        r += piece_value_board[lsb];
-        bb ^= 1 << lsb;
-    }
-    r
-}
-
 // Calculates the score penalty for hanging pieces
 // For active players turn the penalty is:
 // - 75% of second least valuable piece
@@ -5,12 +5,16 @@ use crate::{
 };
 
 pub mod board;
+pub mod book;
 pub mod engine;
 pub mod evaluation;
 pub mod match_state;
+pub mod pgn;
 pub mod r#move;
 pub mod search;
 pub mod shared;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
 pub mod testing;
 pub mod move_generation;
 pub mod move_ordering;
@@ -1,18 +1,15 @@
 use crate::{
-    board::{bitboard::Bitboard, position::Position},
-    r#move::Move,
-    shared::{
-        board_utils::{get_file, index_from_coords},
-        constants::{
-            MF_BISHOP_CAPTURE_PROMOTION, MF_BISHOP_PROMOTION, MF_CAPTURE, MF_DOUBLE_PAWN_PUSH,
-            MF_EP_CAPTURE, MF_KING_CASTLING, MF_KNIGHT_CAPTURE_PROMOTION, MF_KNIGHT_PROMOTION,
-            MF_QUEEN_CAPTURE_PROMOTION, MF_QUEEN_CASTLING, MF_QUEEN_PROMOTION,
-            MF_ROOK_CAPTURE_PROMOTION, MF_ROOK_PROMOTION,
-        },
-        piece_type::{get_piece_type_from_char, PieceType},
+    board::{
+        board_rep::BoardRep,
+        fen::{FenError, FenFields},
+        position::Position,
     },
+    move_generation::generate_moves_for_board,
+    r#move::{move_list::MoveList, Move},
+    shared::{board_utils::try_index_from_coords, piece_type::PieceType},
 };
 use core::fmt::Debug;
+use std::{error::Error, fmt::Display};
 
 #[repr(u8)]
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -23,14 +20,43 @@ pub enum MatchResultState {
     BlackVictory = 3,
 }
 
+// Why the position on the board ends the game
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum TerminationReason {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveParseError {
+    Malformed(String),
+    InvalidPromotion(char),
+    IllegalMove(String),
+}
+
+impl Display for MoveParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveParseError::Malformed(s) => write!(f, "'{s}' is not a UCI move"),
+            MoveParseError::InvalidPromotion(c) => write!(f, "Can't promote to '{c}'"),
+            MoveParseError::IllegalMove(s) => write!(f, "{s} is not legal in this position"),
+        }
+    }
+}
+
+impl Error for MoveParseError {}
+
 #[derive(Copy, Clone, PartialEq)]
 pub struct GameState {
     pub position: Position,
     pub half_moves: u8,
     pub full_moves: u32,
-    pub result_state: MatchResultState,
     pub entry_move: Move,
-    recent_moves: [Move; 6],
 }
 
 impl GameState {
@@ -51,90 +77,116 @@ impl GameState {
             Some(hm) => hm.parse::<u32>().unwrap(),
             None => 0,
         };
-        let recent_moves = [Move::default(); 6];
-        let result_state = result_state(half_moves, recent_moves, &position);
+        Self::from_position(position, half_moves, full_moves)
+    }
+
+    // Unlike new this rejects malformed or impossible positions instead of panicking, so it's safe to use on
+    // FENs from outside the engine
+    pub fn try_new(fen: &str) -> Result<Self, FenError> {
+        let fields = FenFields::split(fen)?;
+        let position = Position::build(BoardRep::try_from_fields(&fields)?);
+        Ok(Self::from_position(position, fields.half_moves, fields.full_moves))
+    }
+
+    fn from_position(position: Position, half_moves: u8, full_moves: u32) -> Self {
         Self {
             position,
             half_moves,
             full_moves,
-            result_state,
-            recent_moves,
             entry_move: Move::default(),
         }
     }
 
     pub fn make(&self, m: Move) -> Self {
         let (new_zorb, move_segments) = self.position.board.zorb_key_after_move(m);
-
         let new_position = self.position.apply_segments(move_segments, new_zorb);
+        self.after_position(new_position, m).unwrap()
+    }
 
+    pub fn after_position(&self, position: Position, m: Move) -> Option<GameState> {
         let mut half_moves = self.half_moves;
         let mut full_moves = self.full_moves;
 
-        if m.is_capture() || m.piece_type() == PieceType::Pawn {
+        let piece_type = self.position.board.get_piece_type_at_index(m.from());
+        if m.is_capture() || piece_type == PieceType::Pawn {
             half_moves = 0;
         } else {
-            half_moves += 1;
+            half_moves = half_moves.saturating_add(1);
         }
 
         if self.position.board.black_turn {
             full_moves += 1;
         }
 
-        let recent_moves = [
-            m,
-            self.recent_moves[0],
-            self.recent_moves[1],
-            self.recent_moves[2],
-            self.recent_moves[3],
-            self.recent_moves[4],
-        ];
-
-        let result_state = result_state(half_moves, recent_moves, &new_position); // TODO: Might need to add in some extra logic here
-
-        Self {
-            position: new_position,
+        Some(Self {
+            position,
             half_moves,
             full_moves,
-            recent_moves,
-            result_state,
             entry_move: m,
-        }
+        })
     }
 
-    pub fn after_position(&self, position: Position, m: Move) -> Option<GameState> {
-        let mut half_moves = self.half_moves;
-        let mut full_moves = self.full_moves;
+    // How many times this position has been reached, counting this time. `previous_keys` are the zobrist keys of
+    // the positions played before this one, oldest first. Positions before a FEN are unknown so they can't count
+    pub fn repetitions(&self, previous_keys: &[u64]) -> usize {
+        // Only positions since the last capture or pawn move with the same side to move can match
+        let reversible = (self.half_moves as usize).min(previous_keys.len());
+        let zorb_key = self.position.board.zorb_key;
+        1 + previous_keys[previous_keys.len() - reversible..]
+            .iter()
+            .rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == zorb_key)
+            .count()
+    }
 
-        if m.is_capture() || m.piece_type() == PieceType::Pawn {
-            half_moves = 0;
+    // The draws that don't need the legal moves, cheap enough to check at every node of a search. Claimable
+    // draws are included as engines and GUIs treat them as the end of the game
+    pub fn draw_by_rule(&self, previous_keys: &[u64]) -> Option<TerminationReason> {
+        if self.position.board.is_insufficient_material() {
+            return Some(TerminationReason::InsufficientMaterial);
+        }
+        let repetitions = self.repetitions(previous_keys);
+        if repetitions >= 5 {
+            Some(TerminationReason::FivefoldRepetition)
+        } else if self.half_moves >= 150 {
+            Some(TerminationReason::SeventyFiveMoveRule)
+        } else if repetitions >= 3 {
+            Some(TerminationReason::ThreefoldRepetition)
+        } else if self.half_moves >= 100 {
+            Some(TerminationReason::FiftyMoveRule)
         } else {
-            half_moves += 1;
+            None
         }
+    }
 
-        if self.position.board.black_turn {
-            full_moves += 1;
+    // Mate takes priority, a move that mates ends the game even if it also completes a draw by rule
+    pub fn termination(&self, previous_keys: &[u64]) -> Option<TerminationReason> {
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(self.position.board, &mut legal_moves);
+        if legal_moves.is_empty() {
+            let in_check = if self.position.board.black_turn {
+                self.position.black_in_check
+            } else {
+                self.position.white_in_check
+            };
+            return Some(if in_check {
+                TerminationReason::Checkmate
+            } else {
+                TerminationReason::Stalemate
+            });
         }
+        self.draw_by_rule(previous_keys)
+    }
 
-        let recent_moves = [
-            m,
-            self.recent_moves[0],
-            self.recent_moves[1],
-            self.recent_moves[2],
-            self.recent_moves[3],
-            self.recent_moves[4],
-        ];
-
-        let result_state = result_state(half_moves, recent_moves, &position); // TODO: Might need to add in some extra logic here
-
-        Some(Self {
-            position: position,
-            half_moves,
-            full_moves,
-            recent_moves,
-            result_state,
-            entry_move: m,
-        })
+    pub fn result_state(&self, previous_keys: &[u64]) -> MatchResultState {
+        match self.termination(previous_keys) {
+            None => MatchResultState::Active,
+            Some(TerminationReason::Checkmate) if self.position.board.black_turn => MatchResultState::WhiteVictory,
+            Some(TerminationReason::Checkmate) => MatchResultState::BlackVictory,
+            Some(_) => MatchResultState::Draw,
+        }
     }
 
     pub fn to_fen(&self) -> String {
@@ -146,72 +198,35 @@ impl GameState {
         result
     }
 
-    pub fn move_from_uci(&self, move_uci: &str) -> Move {
-        let from = index_from_coords(&move_uci[0..2]);
-        let to = index_from_coords(&move_uci[2..4]);
-
-        let promotion = if move_uci.len() == 5 {
-            get_piece_type_from_char(move_uci.chars().nth(4).unwrap())
-        } else {
-            PieceType::None
+    // Resolves a move like "e2e4" or "e7e8q" against the legal moves of this position, so the result carries the
+    // same flags as the generated move
+    pub fn parse_uci_move(&self, move_uci: &str) -> Result<Move, MoveParseError> {
+        if !move_uci.is_ascii() || move_uci.len() < 4 || move_uci.len() > 5 {
+            return Err(MoveParseError::Malformed(move_uci.into()));
+        }
+        let (from, to) = match (
+            try_index_from_coords(&move_uci[0..2]),
+            try_index_from_coords(&move_uci[2..4]),
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(MoveParseError::Malformed(move_uci.into())),
         };
-
-        let opponent_occupancy = if self.position.board.black_turn {
-            self.position.board.white_occupancy
-        } else {
-            self.position.board.black_occupancy
+        let promotion = match move_uci[4..].chars().next() {
+            None => PieceType::None,
+            Some('n') => PieceType::Knight,
+            Some('b') => PieceType::Bishop,
+            Some('r') => PieceType::Rook,
+            Some('q') => PieceType::Queen,
+            Some(c) => return Err(MoveParseError::InvalidPromotion(c)),
         };
 
-        let mut flags = 0;
-
-        let piece_type = self.position.board.get_piece_type_at_index(from);
-
-        let is_capture = if opponent_occupancy.occupied(to) {
-            flags = MF_CAPTURE;
-            true
-        } else {
-            false
-        };
-
-        match piece_type {
-            PieceType::Pawn => {
-                if from.abs_diff(to) == 16 {
-                    flags = MF_DOUBLE_PAWN_PUSH;
-                } else if self.position.board.ep_index == to {
-                    flags = MF_EP_CAPTURE
-                } else if promotion != PieceType::None {
-                    flags = match (is_capture, promotion) {
-                        (true, PieceType::Knight) => MF_KNIGHT_CAPTURE_PROMOTION,
-                        (false, PieceType::Knight) => MF_KNIGHT_PROMOTION,
-                        (true, PieceType::Bishop) => MF_BISHOP_CAPTURE_PROMOTION,
-                        (false, PieceType::Bishop) => MF_BISHOP_PROMOTION,
-                        (true, PieceType::Rook) => MF_ROOK_CAPTURE_PROMOTION,
-                        (false, PieceType::Rook) => MF_ROOK_PROMOTION,
-                        (true, PieceType::Queen) => MF_QUEEN_CAPTURE_PROMOTION,
-                        (false, PieceType::Queen) => MF_QUEEN_PROMOTION,
-                        _ => panic!(""),
-                    }
-                }
-            }
-            PieceType::King => {
-                let dif = get_file(from) as i8 - get_file(to) as i8;
-                if dif == -2 {
-                    flags = MF_KING_CASTLING;
-                } else if dif == 2 {
-                    flags = MF_QUEEN_CASTLING;
-                }
-            }
-            _ => {}
-        }
-
-        Move::new(
-            from,
-            to,
-            flags,
-            piece_type,
-            self.position.board.black_turn,
-            0,0,
-        ) // See shouldnt be 0 but also theres no real need to calc it
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(self.position.board, &mut legal_moves);
+        legal_moves
+            .iter()
+            .map(|s| s.m)
+            .find(|m| m.from() == from && m.to() == to && m.promotion_piece_type() == promotion)
+            .ok_or(MoveParseError::IllegalMove(move_uci.into()))
     }
 }
 
@@ -219,49 +234,11 @@ impl Debug for GameState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("GameState")
             .field(&self.to_fen())
-            .field(&self.position.eval)
             .field(&self.position.board.zorb_key)
             .finish()
     }
 }
 
-fn result_state(half_moves: u8, recent_moves: [Move; 6], position: &Position) -> MatchResultState {
-    if half_moves >= 50 {
-        return MatchResultState::Draw;
-    }
-
-    if !recent_moves[0].is_empty()
-        && !recent_moves[1].is_empty()
-        && recent_moves[0] == recent_moves[2]
-        && recent_moves[0] == recent_moves[4]
-        && recent_moves[1] == recent_moves[3]
-        && recent_moves[1] == recent_moves[5]
-    {
-        return MatchResultState::Draw;
-    }
-
-    // if position.moves.len() == 0 {
-    //     if position.board.black_turn && position.black_in_check {
-    //         return MatchResultState::WhiteVictory;
-    //     }
-    //     if !position.board.black_turn && position.white_in_check {
-    //         return MatchResultState::BlackVictory;
-    //     }
-
-    //     return MatchResultState::Draw;
-    // }
-    MatchResultState::Active
-}
-
-fn has_player_moves(moves: &Vec<Move>, is_black: bool) -> bool {
-    for m in moves {
-        if m.is_black() == is_black {
-            return true;
-        }
-    }
-    return false;
-}
-
 impl Default for GameState {
     fn default() -> Self {
         Self::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".into())
@@ -269,7 +246,11 @@ impl Default for GameState {
 }
 #[cfg(test)]
 mod test {
-    use crate::shared::constants::{MF_DOUBLE_PAWN_PUSH, MF_KING_CASTLING};
+    use crate::{
+        move_generation::generate_moves_for_board,
+        r#move::move_list::MoveList,
+        shared::constants::{MF_DOUBLE_PAWN_PUSH, MF_KING_CASTLING},
+    };
 
     use super::*;
 
@@ -299,6 +280,20 @@ mod test {
         assert_eq!(result.full_moves, 4);
     }
 
+    #[test]
+    pub fn try_new_reads_counters_and_rejects_invalid_fens() {
+        let result = GameState::try_new("k7/8/8/8/8/8/8/7K b - - 5 25").unwrap();
+        assert_eq!(result.half_moves, 5);
+        assert_eq!(result.full_moves, 25);
+        assert_eq!(GameState::try_new("k7/8/8/8/8/8/8/7K b - -").unwrap().full_moves, 1);
+
+        assert_eq!(
+            GameState::try_new("k6P/8/8/8/8/8/8/7K b - - 0 1").err(),
+            Some(FenError::PawnOnBackRank("h8".into()))
+        );
+        assert!(Position::try_from_fen("k7/8/8/8/8/8/8/7K w - - 0 1 1").is_err());
+    }
+
     #[test]
     pub fn to_fen_startpos() {
         let game_state = GameState::default();
@@ -322,14 +317,7 @@ mod test {
     #[test]
     pub fn make_pawn_e4_opening() {
         let mut game_state = GameState::default();
-        game_state = game_state.make(Move::new(
-            11,
-            27,
-            MF_DOUBLE_PAWN_PUSH,
-            PieceType::Pawn,
-            true,
-            0,0,
-        ));
+        game_state = game_state.make(Move::new(11, 27, MF_DOUBLE_PAWN_PUSH));
         assert_eq!(
             game_state.to_fen(),
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
@@ -341,10 +329,126 @@ mod test {
         let mut game_state = GameState::new(
             "rnbq1rk1/ppp2pbp/3p1np1/4p3/2PPP3/2N2N2/PP2BPPP/R1BQK2R w KQ - 0 2".into(),
         );
-        game_state = game_state.make(Move::new(3, 1, MF_KING_CASTLING, PieceType::King, true, 0,0));
+        game_state = game_state.make(Move::new(3, 1, MF_KING_CASTLING));
         assert_eq!(
             game_state.to_fen(),
             "rnbq1rk1/ppp2pbp/3p1np1/4p3/2PPP3/2N2N2/PP2BPPP/R1BQ1RK1 b - - 1 2"
         );
     }
+
+    // The position after the moves and the keys of the positions before it
+    fn play(game_state: GameState, ucis: &str) -> (GameState, Vec<u64>) {
+        let mut previous_keys = Vec::new();
+        let game_state = ucis.split_whitespace().fold(game_state, |game_state, uci| {
+            previous_keys.push(game_state.position.board.zorb_key);
+            game_state.make(game_state.parse_uci_move(uci).unwrap())
+        });
+        (game_state, previous_keys)
+    }
+
+    fn termination(game_state: GameState, ucis: &str) -> Option<TerminationReason> {
+        let (game_state, previous_keys) = play(game_state, ucis);
+        game_state.termination(&previous_keys)
+    }
+
+    #[test]
+    pub fn termination_checkmate_and_stalemate() {
+        let (game_state, previous_keys) = play(GameState::default(), "f2f3 e7e5 g2g4 d8h4");
+        assert_eq!(game_state.termination(&previous_keys), Some(TerminationReason::Checkmate));
+        assert_eq!(game_state.result_state(&previous_keys), MatchResultState::BlackVictory);
+
+        let game_state = GameState::new("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1".into());
+        assert_eq!(game_state.termination(&[]), Some(TerminationReason::Stalemate));
+        assert_eq!(game_state.result_state(&[]), MatchResultState::Draw);
+
+        let game_state = GameState::new("8/8/4kb2/8/8/3KB3/8/8 w - - 0 1".into());
+        assert_eq!(game_state.termination(&[]), Some(TerminationReason::InsufficientMaterial));
+        assert_eq!(GameState::default().result_state(&[]), MatchResultState::Active);
+    }
+
+    #[test]
+    pub fn termination_repetition() {
+        let shuffle = "g1f3 g8f6 f3g1 f6g8";
+        let (game_state, previous_keys) = play(GameState::default(), shuffle);
+        assert_eq!(game_state.repetitions(&previous_keys), 2);
+        assert_eq!(game_state.termination(&previous_keys), None);
+        let (game_state, previous_keys) = play(GameState::default(), &[shuffle; 2].join(" "));
+        assert_eq!(game_state.repetitions(&previous_keys), 3);
+        assert_eq!(game_state.draw_by_rule(&previous_keys), Some(TerminationReason::ThreefoldRepetition));
+        assert_eq!(
+            termination(GameState::default(), &[shuffle; 4].join(" ")),
+            Some(TerminationReason::FivefoldRepetition)
+        );
+
+        // The same pieces with the other side to move is a different position
+        let (game_state, previous_keys) = play(GameState::default(), "g1f3 g8f6 f3g1 f6g8 b1c3 b8c6 c3b1");
+        assert_eq!(game_state.repetitions(&previous_keys), 1);
+
+        // The count restarts from the position after a pawn move
+        let (game_state, previous_keys) =
+            play(GameState::default(), "g1f3 g8f6 f3g1 f6g8 e2e3 g8f6 g1f3 f6g8 f3g1");
+        assert_eq!(game_state.half_moves, 4);
+        assert_eq!(game_state.repetitions(&previous_keys), 2);
+
+        // Positions before a FEN are unknown
+        let game_state = GameState::new("4k3/8/8/8/8/8/8/4K2R w - - 40 60".into());
+        let (game_state, previous_keys) = play(game_state, "e1d1 e8d8 d1e1 d8e8");
+        assert_eq!(game_state.repetitions(&previous_keys), 2);
+    }
+
+    #[test]
+    pub fn termination_move_rules() {
+        let game_state = GameState::new("7k/8/6K1/8/8/8/8/R7 w - - 98 80".into());
+        assert_eq!(termination(game_state, "a1b1"), None);
+        assert_eq!(termination(game_state, "a1b1 h8g8"), Some(TerminationReason::FiftyMoveRule));
+        let game_state = GameState::new("7k/8/6K1/8/8/8/8/R7 w - - 149 80".into());
+        assert_eq!(termination(game_state, "a1b1"), Some(TerminationReason::SeventyFiveMoveRule));
+
+        // A mate on the last move of the count still wins
+        let game_state = GameState::new("7k/8/6K1/8/8/8/8/R7 w - - 99 80".into());
+        let (game_state, previous_keys) = play(game_state, "a1a8");
+        assert_eq!(game_state.half_moves, 100);
+        assert_eq!(game_state.result_state(&previous_keys), MatchResultState::WhiteVictory);
+        // A pawn move resets the count
+        let game_state = GameState::new("7k/8/6K1/8/8/8/P7/R7 w - - 99 80".into());
+        assert_eq!(termination(game_state, "a2a3"), None);
+    }
+
+    #[test]
+    pub fn parse_uci_move_resolves_the_generated_move() {
+        let game_state = GameState::new(
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into(),
+        );
+        let mut generated = MoveList::new();
+        generate_moves_for_board(game_state.position.board, &mut generated);
+        for uci in ["e1g1", "b7a8q", "b7b8n", "d5e6", "a2a4", "e5f7"] {
+            let m = game_state.parse_uci_move(uci).unwrap();
+            assert!(generated.contains_move(m), "{uci}");
+            assert_eq!(m.uci(), uci);
+        }
+        assert!(game_state.parse_uci_move("e1g1").unwrap().is_king_castling());
+        assert!(game_state.parse_uci_move("a2a4").unwrap().is_double_pawn_push());
+    }
+
+    #[test]
+    pub fn parse_uci_move_rejects_malformed_and_illegal_moves() {
+        let game_state = GameState::new(
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into(),
+        );
+        let cases = [
+            ("", MoveParseError::Malformed("".into())),
+            ("e1g", MoveParseError::Malformed("e1g".into())),
+            ("e9e4", MoveParseError::Malformed("e9e4".into())),
+            ("é2e4", MoveParseError::Malformed("é2e4".into())),
+            ("b7b8k", MoveParseError::InvalidPromotion('k')),
+            ("b7b8Q", MoveParseError::InvalidPromotion('Q')),
+            ("b7b8", MoveParseError::IllegalMove("b7b8".into())),
+            ("a2a4q", MoveParseError::IllegalMove("a2a4q".into())),
+            ("e1e3", MoveParseError::IllegalMove("e1e3".into())),
+            ("a7a5", MoveParseError::IllegalMove("a7a5".into())),
+        ];
+        for (uci, expected) in cases {
+            assert_eq!(game_state.parse_uci_move(uci), Err(expected), "{uci}");
+        }
+    }
 }
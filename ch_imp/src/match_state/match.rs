@@ -1,15 +1,553 @@
-use super::game_state::GameState;
+use std::time::Duration;
 
+use super::game_state::{GameState, MatchResultState, MoveParseError, TerminationReason};
+use crate::{
+    board::fen::FenError,
+    engine::san::SanError,
+    move_generation::generate_moves_for_board,
+    pgn::{PgnError, PgnEval, PgnGame, PgnLine, PgnMove},
+    r#move::{move_list::MoveList, Move},
+};
+
+// Why a finished game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    // Decided by the final position on the board
+    Board(TerminationReason),
+    Resignation,
+    Agreement,
+    TimeForfeit,
+    Adjudication,
+    Abandoned,
+}
+
+impl Termination {
+    // Value for the PGN Termination tag
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Board(_) | Termination::Resignation | Termination::Agreement => "normal",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Adjudication => "adjudication",
+            Termination::Abandoned => "abandoned",
+        }
+    }
+
+    // "normal" is left out as it doesn't say how the game ended
+    pub fn from_pgn_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "time forfeit" => Some(Termination::TimeForfeit),
+            "adjudication" => Some(Termination::Adjudication),
+            "abandoned" => Some(Termination::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchMove {
+    pub m: Move,
+    pub san: String,
+    // Time left on the mover's clock after the move
+    pub clock: Option<Duration>,
+    pub eval: Option<PgnEval>,
+}
+
+#[derive(Clone)]
+struct MatchNode {
+    // Only the root, the start position, has no parent or move
+    parent: Option<usize>,
+    entry: Option<MatchMove>,
+    game_state: GameState,
+    ply: usize,
+    // The first child continues the line, the others are variations of it
+    children: Vec<usize>,
+    // Index into children of the move redo plays
+    selected: usize,
+}
+
+// A game as a tree of moves with a cursor on the current position, so front ends can take moves back, step
+// through the game and explore variations without losing the moves played
+#[derive(Clone)]
 pub struct Match {
-    pub current_game_state: GameState
+    nodes: Vec<MatchNode>,
+    current: usize,
+    pub tags: Vec<(String, String)>,
+    pub result: MatchResultState,
+    pub termination: Option<Termination>,
 }
 
-impl Match {}
+impl Match {
+    pub fn new(start: GameState) -> Self {
+        Self {
+            nodes: vec![MatchNode {
+                parent: None,
+                entry: None,
+                game_state: start,
+                ply: 0,
+                children: Vec::new(),
+                selected: 0,
+            }],
+            current: 0,
+            tags: Vec::new(),
+            result: MatchResultState::Active,
+            termination: None,
+        }
+    }
+
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        Ok(Self::new(GameState::try_new(fen)?))
+    }
+
+    pub fn start_game_state(&self) -> GameState {
+        self.nodes[0].game_state
+    }
+
+    pub fn current_game_state(&self) -> GameState {
+        self.nodes[self.current].game_state
+    }
+
+    // Moves played from the start to reach the current position
+    pub fn ply(&self) -> usize {
+        self.nodes[self.current].ply
+    }
+
+    // The move that led to the current position
+    pub fn last_move(&self) -> Option<&MatchMove> {
+        self.nodes[self.current].entry.as_ref()
+    }
+
+    pub fn last_move_mut(&mut self) -> Option<&mut MatchMove> {
+        self.nodes[self.current].entry.as_mut()
+    }
+
+    // The moves from the start to the current position
+    pub fn moves(&self) -> Vec<&MatchMove> {
+        self.path(self.current)
+            .iter()
+            .filter_map(|&node| self.nodes[node].entry.as_ref())
+            .collect()
+    }
+
+    // Every position from the start up to and including the current one
+    pub fn position_history(&self) -> Vec<GameState> {
+        self.path(self.current)
+            .iter()
+            .map(|&node| self.nodes[node].game_state)
+            .collect()
+    }
+
+    // Zobrist keys of the positions before the current one, for spotting repetitions
+    pub fn previous_keys(&self) -> Vec<u64> {
+        self.previous_keys_at(self.current)
+    }
+
+    pub fn mainline(&self) -> Vec<&MatchMove> {
+        let mut r = Vec::new();
+        let mut node = 0;
+        while let Some(&child) = self.nodes[node].children.first() {
+            r.extend(self.nodes[child].entry.as_ref());
+            node = child;
+        }
+        r
+    }
+
+    // The moves already recorded from the current position, the first continues the current line
+    pub fn next_moves(&self) -> Vec<&MatchMove> {
+        self.nodes[self.current]
+            .children
+            .iter()
+            .filter_map(|&child| self.nodes[child].entry.as_ref())
+            .collect()
+    }
+
+    pub fn is_on_mainline(&self) -> bool {
+        self.on_mainline(self.current)
+    }
+
+    // Moves already recorded from here are followed rather than added again, anything else starts a variation.
+    // The result is only decided by the board at the end of the mainline
+    pub fn play(&mut self, m: Move) -> Result<(), MoveParseError> {
+        let parent = self.current;
+        if let Some(index) = self.nodes[parent]
+            .children
+            .iter()
+            .position(|&child| self.nodes[child].entry.as_ref().is_some_and(|e| e.m == m))
+        {
+            self.nodes[parent].selected = index;
+            self.current = self.nodes[parent].children[index];
+            return Ok(());
+        }
+
+        let game_state = self.current_game_state();
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(game_state.position.board, &mut legal_moves);
+        if !legal_moves.iter().any(|s| s.m == m) {
+            return Err(MoveParseError::IllegalMove(m.uci()));
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(MatchNode {
+            parent: Some(parent),
+            entry: Some(MatchMove {
+                m,
+                san: game_state.to_san(m),
+                clock: None,
+                eval: None,
+            }),
+            game_state: game_state.make(m),
+            ply: self.nodes[parent].ply + 1,
+            children: Vec::new(),
+            selected: 0,
+        });
+        self.nodes[parent].children.push(node);
+        self.nodes[parent].selected = self.nodes[parent].children.len() - 1;
+        self.current = node;
+
+        let game_state = self.nodes[node].game_state;
+        if self.result == MatchResultState::Active && self.on_mainline(node) {
+            let previous_keys = self.previous_keys_at(node);
+            if let Some(reason) = game_state.termination(&previous_keys) {
+                self.end(game_state.result_state(&previous_keys), Termination::Board(reason));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn play_uci(&mut self, move_uci: &str) -> Result<(), MoveParseError> {
+        let m = self.current_game_state().parse_uci_move(move_uci)?;
+        self.play(m)
+    }
+
+    pub fn play_san(&mut self, san: &str) -> Result<(), SanError> {
+        let m = self.current_game_state().parse_san(san)?;
+        self.play(m).map_err(|_| SanError::IllegalMove(san.into()))
+    }
+
+    // Redo goes back to the move that was taken back
+    pub fn undo(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.nodes[parent].selected = self.child_index(self.current);
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let node = &self.nodes[self.current];
+        match node.children.get(node.selected) {
+            Some(&child) => {
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Moves along the current line, which past the current position is the one redo would follow. Returns false
+    // and stays put when the line isn't that long
+    pub fn jump_to_ply(&mut self, ply: usize) -> bool {
+        let start = self.current;
+        while self.ply() > ply {
+            self.undo();
+        }
+        while self.ply() < ply {
+            if !self.redo() {
+                self.current = start;
+                return false;
+            }
+        }
+        true
+    }
+
+    // Makes the line through the current position the mainline, the old mainline becomes a variation
+    pub fn promote_to_mainline(&mut self) {
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            let index = self.child_index(node);
+            let children = &mut self.nodes[parent].children;
+            children.remove(index);
+            children.insert(0, node);
+            self.nodes[parent].selected = 0;
+            node = parent;
+        }
+    }
+
+    pub fn end(&mut self, result: MatchResultState, termination: Termination) {
+        self.result = result;
+        self.termination = Some(termination);
+    }
+
+    pub fn resign(&mut self, black: bool) {
+        let result = if black {
+            MatchResultState::WhiteVictory
+        } else {
+            MatchResultState::BlackVictory
+        };
+        self.end(result, Termination::Resignation);
+    }
+
+    pub fn to_pgn_game(&self) -> PgnGame {
+        let mut game = PgnGame::from_moves(self.start_game_state(), &[]);
+        for (name, value) in &self.tags {
+            game.set_tag(name, value);
+        }
+        if let Some(termination) = self.termination {
+            game.set_tag("Termination", termination.pgn_tag());
+        }
+        if let Some(&first) = self.nodes[0].children.first() {
+            game.mainline = self.pgn_line(first);
+        }
+        game.result = self.result;
+        game
+    }
+
+    // Comments and NAGs aren't kept, the position is left at the end of the mainline
+    pub fn from_pgn_game(game: &PgnGame) -> Result<Self, PgnError> {
+        let mut r = Self::new(game.start_state()?);
+        r.add_pgn_line(&game.mainline)?;
+        r.tags = game.tags.clone();
+        r.result = game.result;
+        r.termination = game.tag("Termination").and_then(Termination::from_pgn_tag);
+        if r.termination.is_none() && r.result != MatchResultState::Active {
+            r.termination = r.current_game_state().termination(&r.previous_keys()).map(Termination::Board);
+        }
+        Ok(r)
+    }
+
+    fn add_pgn_line(&mut self, line: &PgnLine) -> Result<(), PgnError> {
+        let start = self.current;
+        for pgn_move in &line.moves {
+            let before = self.current;
+            self.play(pgn_move.m)
+                .map_err(|_| PgnError::InvalidMove(SanError::IllegalMove(pgn_move.san.clone())))?;
+            if let Some(entry) = self.last_move_mut() {
+                entry.clock = pgn_move.clock;
+                entry.eval = pgn_move.eval;
+            }
+
+            let main = self.current;
+            for variation in &pgn_move.variations {
+                self.current = before;
+                self.add_pgn_line(variation)?;
+            }
+            self.current = main;
+            self.nodes[before].selected = self.child_index(main);
+        }
+        if line.moves.is_empty() {
+            self.current = start;
+        }
+        Ok(())
+    }
+
+    fn pgn_line(&self, first: usize) -> PgnLine {
+        let mut line = PgnLine::default();
+        let mut node = first;
+        loop {
+            let entry = self.nodes[node].entry.as_ref().unwrap();
+            let parent = &self.nodes[self.nodes[node].parent.unwrap()];
+            let mut pgn_move = PgnMove::new(&parent.game_state, entry.m);
+            pgn_move.clock = entry.clock;
+            pgn_move.eval = entry.eval;
+            // Variations hang off the move they replace, which is always the first child
+            if parent.children[0] == node {
+                pgn_move.variations = parent.children[1..]
+                    .iter()
+                    .map(|&child| self.pgn_line(child))
+                    .collect();
+            }
+            line.moves.push(pgn_move);
+
+            match self.nodes[node].children.first() {
+                Some(&child) => node = child,
+                None => return line,
+            }
+        }
+    }
+
+    // Root first
+    fn path(&self, node: usize) -> Vec<usize> {
+        let mut r = vec![node];
+        while let Some(parent) = self.nodes[*r.last().unwrap()].parent {
+            r.push(parent);
+        }
+        r.reverse();
+        r
+    }
+
+    fn previous_keys_at(&self, node: usize) -> Vec<u64> {
+        let path = self.path(node);
+        path[..path.len() - 1]
+            .iter()
+            .map(|&node| self.nodes[node].game_state.position.board.zorb_key)
+            .collect()
+    }
+
+    fn on_mainline(&self, node: usize) -> bool {
+        self.path(node)
+            .windows(2)
+            .all(|pair| self.nodes[pair[0]].children[0] == pair[1])
+    }
+
+    fn child_index(&self, node: usize) -> usize {
+        let parent = self.nodes[node].parent.unwrap();
+        self.nodes[parent]
+            .children
+            .iter()
+            .position(|&child| child == node)
+            .unwrap()
+    }
+}
 
 impl Default for Match {
     fn default() -> Self {
-        Self {
-            current_game_state: Default::default()
+        Self::new(GameState::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Match, Termination};
+    use crate::{
+        match_state::game_state::{GameState, MatchResultState, TerminationReason},
+        pgn::{reader::read_pgn, PgnEval},
+    };
+
+    fn play_sans(game: &mut Match, sans: &[&str]) {
+        for san in sans {
+            game.play_san(san).unwrap();
         }
     }
+
+    fn sans(moves: Vec<&super::MatchMove>) -> Vec<&str> {
+        moves.iter().map(|entry| entry.san.as_str()).collect()
+    }
+
+    #[test]
+    pub fn undo_redo_and_jump_to_ply() {
+        let mut game = Match::default();
+        play_sans(&mut game, &["e4", "e5", "Nf3"]);
+        assert_eq!(game.ply(), 3);
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.ply(), 1);
+        assert_eq!(sans(game.moves()), vec!["e4"]);
+        assert!(game.redo());
+        assert_eq!(game.last_move().unwrap().san, "e5");
+
+        assert!(game.jump_to_ply(3));
+        assert!(!game.redo());
+        assert!(!game.jump_to_ply(5));
+        assert_eq!(game.ply(), 3);
+        assert!(game.jump_to_ply(0));
+        assert!(!game.undo());
+        assert_eq!(game.current_game_state().to_fen(), GameState::default().to_fen());
+
+        let history = game.position_history();
+        assert_eq!(history.len(), 1);
+        game.jump_to_ply(3);
+        let history = game.position_history();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3], game.current_game_state());
+        assert_eq!(history[1], GameState::default().make(game.moves()[0].m));
+    }
+
+    #[test]
+    pub fn playing_a_different_move_starts_a_variation() {
+        let mut game = Match::default();
+        play_sans(&mut game, &["e4", "e5", "Nf3", "Nc6"]);
+        game.jump_to_ply(2);
+        play_sans(&mut game, &["Nc3"]);
+        assert!(!game.is_on_mainline());
+        assert_eq!(sans(game.mainline()), vec!["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(sans(game.moves()), vec!["e4", "e5", "Nc3"]);
+
+        // Replaying a recorded move follows it rather than adding another variation
+        game.undo();
+        assert_eq!(sans(game.next_moves()), vec!["Nf3", "Nc3"]);
+        play_sans(&mut game, &["Nf3"]);
+        assert!(game.is_on_mainline());
+        assert_eq!(game.ply(), 3);
+        game.undo();
+        assert_eq!(game.next_moves().len(), 2);
+
+        play_sans(&mut game, &["Nc3", "Nf6"]);
+        assert_eq!(
+            game.to_pgn_game().to_pgn().lines().last().unwrap(),
+            "1. e4 e5 2. Nf3 (2. Nc3 Nf6) 2... Nc6 *"
+        );
+        game.promote_to_mainline();
+        assert!(game.is_on_mainline());
+        assert_eq!(
+            game.to_pgn_game().to_pgn().lines().last().unwrap(),
+            "1. e4 e5 2. Nc3 (2. Nf3 Nc6) 2... Nf6 *"
+        );
+    }
+
+    #[test]
+    pub fn play_rejects_illegal_moves() {
+        let mut game = Match::default();
+        assert!(game.play_uci("e2e5").is_err());
+        assert!(game.play_san("Ke2").is_err());
+        assert!(game.play_uci("e2e4").is_ok());
+        assert_eq!(game.ply(), 1);
+    }
+
+    #[test]
+    pub fn result_and_termination() {
+        let mut game = Match::try_from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 99 80").unwrap();
+        play_sans(&mut game, &["Kd1"]);
+        assert_eq!(game.result, MatchResultState::Draw);
+        assert_eq!(game.termination, Some(Termination::Board(TerminationReason::FiftyMoveRule)));
+
+        let mut game = Match::default();
+        play_sans(&mut game, &["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(game.result, MatchResultState::BlackVictory);
+        assert_eq!(game.termination, Some(Termination::Board(TerminationReason::Checkmate)));
+
+        // Only the mainline decides the result
+        let mut game = Match::try_from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 99 80").unwrap();
+        play_sans(&mut game, &["e4"]);
+        game.undo();
+        play_sans(&mut game, &["Kd1"]);
+        assert_eq!(game.result, MatchResultState::Active);
+
+        game.resign(false);
+        assert_eq!(game.result, MatchResultState::BlackVictory);
+        let pgn = game.to_pgn_game();
+        assert_eq!(pgn.tag("Termination"), Some("normal"));
+        assert_eq!(pgn.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K3 w - - 99 80"));
+
+        game.end(MatchResultState::WhiteVictory, Termination::TimeForfeit);
+        assert_eq!(game.to_pgn_game().tag("Termination"), Some("time forfeit"));
+    }
+
+    #[test]
+    pub fn pgn_round_trip() {
+        let pgn = concat!(
+            "[Event \"Round trip\"]\n[Termination \"time forfeit\"]\n[Result \"0-1\"]\n\n",
+            "1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) 1... e5 {[%clk 0:01:00]} 2. Nf3 Nc6 ",
+            "{[%eval -1.50]} (2... d6 3. d4) 3. Bb5 0-1\n",
+        );
+        let games = read_pgn(pgn).unwrap();
+        let mut game = Match::from_pgn_game(&games[0]).unwrap();
+        assert_eq!(game.ply(), 5);
+        assert_eq!(game.result, MatchResultState::BlackVictory);
+        assert_eq!(game.termination, Some(Termination::TimeForfeit));
+        game.jump_to_ply(2);
+        assert_eq!(game.last_move().unwrap().clock, Some(Duration::from_secs(60)));
+
+        let written = game.to_pgn_game();
+        assert_eq!(written.mainline, games[0].mainline);
+        assert_eq!(written.tags, games[0].tags);
+
+        game.jump_to_ply(4);
+        assert_eq!(game.last_move().unwrap().eval, Some(PgnEval::Centipawns(-150)));
+        game.last_move_mut().unwrap().eval = None;
+        assert_ne!(game.to_pgn_game().mainline, games[0].mainline);
+    }
 }
@@ -1,2 +1,2 @@
 pub mod game_state;
-mod r#match;
\ No newline at end of file
+pub mod r#match;
\ No newline at end of file
@@ -0,0 +1,150 @@
+use core::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+use super::{scored_move::ScoredMove, Move};
+
+// No legal chess position has more than 218 moves, so a list of 256 can never overflow
+pub const MAX_MOVES: usize = 256;
+
+// A fixed capacity list of generated moves that lives on the stack. Generators push into a list owned by the caller
+// so move generation in the search and perft never touches the heap
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [ScoredMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self {
+            moves: [ScoredMove::default(); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, m: ScoredMove) {
+        debug_assert!(self.len < MAX_MOVES, "Move list overflow");
+        self.moves[self.len] = m;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // Replaces the contents with a copy of `moves`
+    pub fn copy_from(&mut self, moves: &[ScoredMove]) {
+        self.moves[..moves.len()].copy_from_slice(moves);
+        self.len = moves.len();
+    }
+
+    pub fn find(&self, m: Move) -> Option<ScoredMove> {
+        self.iter().find(|s| s.m == m).copied()
+    }
+
+    pub fn contains_move(&self, m: Move) -> bool {
+        self.iter().any(|s| s.m == m)
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [ScoredMove];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl PartialEq for MoveList {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a ScoredMove;
+    type IntoIter = std::slice::Iter<'a, ScoredMove>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = ScoredMove;
+    type IntoIter = MoveListIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MoveListIntoIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+pub struct MoveListIntoIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for MoveListIntoIter {
+    type Item = ScoredMove;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.list.len {
+            return None;
+        }
+        self.index += 1;
+        Some(self.list.moves[self.index - 1])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        r#move::{scored_move::ScoredMove, Move},
+        shared::{constants::MF_CAPTURE, piece_type::PieceType},
+    };
+
+    use super::MoveList;
+
+    #[test]
+    pub fn push_sort_and_find() {
+        let quiet = ScoredMove::new(Move::new(1, 2, 0b0), PieceType::Knight, 0, 5);
+        let capture = ScoredMove::new(Move::new(3, 4, MF_CAPTURE), PieceType::Pawn, 2, 0);
+
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+        moves.push(quiet);
+        moves.push(capture);
+        moves.sort();
+
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0], capture);
+        assert_eq!(moves.find(Move::new(1, 2, 0b0)), Some(quiet));
+        assert!(!moves.contains_move(Move::new(1, 2, MF_CAPTURE)));
+        assert_eq!(moves.into_iter().collect::<Vec<_>>(), vec![capture, quiet]);
+
+        moves.clear();
+        assert!(moves.is_empty());
+        assert_eq!(moves.find(Move::new(3, 4, MF_CAPTURE)), None);
+    }
+}
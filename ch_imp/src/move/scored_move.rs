@@ -0,0 +1,189 @@
+use crate::{
+    board::{attack_and_defend_lookups::AttackedBy, see::see_from_capture},
+    shared::{
+        constants::{MF_CAPTURE, MF_EP_CAPTURE},
+        piece_type::PieceType,
+    },
+};
+use std::cmp::Ordering;
+
+use super::Move;
+
+// A generated move along with the information used to order it. Only lives as long as the move list it was
+// generated into, anything stored (TT, killers, PV) keeps the bare Move
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScoredMove {
+    pub m: Move,
+    pub piece_type: PieceType,
+    pub see: i8,
+    pub square_delta: i16,
+}
+
+impl ScoredMove {
+    pub fn new(m: Move, piece_type: PieceType, see: i8, square_delta: i16) -> Self {
+        Self {
+            m,
+            piece_type,
+            see,
+            square_delta,
+        }
+    }
+
+    pub fn capture(
+        from_index: u8,
+        to_index: u8,
+        attacker_piece_type: PieceType,
+        attacked_piece_type: PieceType,
+        friendly_attacked_by: AttackedBy,
+        opponent_attacked_by: AttackedBy,
+        square_delta: i16,
+    ) -> Self {
+        let see = see_from_capture(
+            attacker_piece_type,
+            friendly_attacked_by,
+            attacked_piece_type,
+            opponent_attacked_by,
+        );
+        Self::new(
+            Move::new(from_index, to_index, MF_CAPTURE),
+            attacker_piece_type,
+            see,
+            square_delta,
+        )
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.see == 0
+    }
+}
+
+impl PartialOrd for ScoredMove {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredMove {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Better SEE
+        let see_result = other.see.cmp(&self.see);
+        if see_result != Ordering::Equal {
+            return see_result;
+        }
+
+        // Captures should be LV first
+        if self.m.is_capture() {
+            if !other.m.is_capture() {
+                return Ordering::Less;
+            }
+            return self.piece_type.cmp(&other.piece_type);
+        }
+
+        if other.m.is_capture() {
+            return Ordering::Greater;
+        }
+
+        // Flag priority (Promotion Captures -> Promotions -> EP Capture -> Captures -> Castling -> DPP -> Quiet)
+        let flags_result = other.m.flags().cmp(&self.m.flags());
+        if flags_result != Ordering::Equal {
+            return flags_result;
+        }
+
+        // Check Pawn captures first
+        if self.piece_type == PieceType::Pawn
+            && (self.m.flags() == MF_CAPTURE || self.m.flags() == MF_EP_CAPTURE)
+        {
+            return Ordering::Less;
+        }
+        if other.piece_type == PieceType::Pawn
+            && (other.m.flags() == MF_CAPTURE || other.m.flags() == MF_EP_CAPTURE)
+        {
+            return Ordering::Less;
+        }
+
+        other.square_delta.cmp(&self.square_delta)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        r#move::Move,
+        shared::{
+            constants::{MF_CAPTURE, MF_DOUBLE_PAWN_PUSH, MF_QUEEN_PROMOTION},
+            piece_type::PieceType,
+        },
+    };
+
+    use super::ScoredMove;
+
+    fn scored(flags: u16, piece_type: PieceType, see: i8, square_delta: i16) -> ScoredMove {
+        ScoredMove::new(Move::new(0, 1, flags), piece_type, see, square_delta)
+    }
+
+    #[test]
+    pub fn order_will_prioritize_greater_valued_flags() {
+        let m1 = ScoredMove::new(Move::new(2, 4, MF_DOUBLE_PAWN_PUSH), PieceType::Queen, 0, 0);
+        let m2 = ScoredMove::new(Move::new(2, 4, MF_QUEEN_PROMOTION), PieceType::Queen, 0, 0);
+
+        let mut vec = vec![m1, m2];
+        vec.sort();
+        assert_eq!(vec[0], m2);
+        assert_eq!(vec[1], m1);
+    }
+
+    #[test]
+    pub fn order_moves_case_capture_over_quiet() {
+        let capture = scored(MF_CAPTURE, PieceType::Pawn, 2, 0);
+        let quiet = scored(0b0, PieceType::Pawn, 0, 0);
+        let mut moves = vec![quiet, capture];
+
+        moves.sort();
+        assert_eq!(moves[0], capture);
+        assert_eq!(moves[1], quiet);
+    }
+
+    #[test]
+    pub fn order_moves_case_capture_with_higher_see_over_lower() {
+        let major_capture = scored(MF_CAPTURE, PieceType::Pawn, 4, 0);
+        let minor_capture = scored(MF_CAPTURE, PieceType::Queen, 1, 0);
+        let mut moves = vec![minor_capture, major_capture];
+
+        moves.sort();
+        assert_eq!(moves[0], major_capture);
+        assert_eq!(moves[1], minor_capture);
+    }
+
+    #[test]
+    pub fn order_moves_case_equal_see_captures_check_least_valuable_piece_first() {
+        let rook_capture = scored(MF_CAPTURE, PieceType::Rook, 4, 0);
+        let queen_capture = scored(MF_CAPTURE, PieceType::Queen, 4, 0);
+        let mut moves = vec![queen_capture, rook_capture];
+
+        moves.sort();
+        assert_eq!(moves[0], rook_capture);
+        assert_eq!(moves[1], queen_capture);
+    }
+
+    #[test]
+    pub fn order_equal_see_capture_over_dpp() {
+        let pawn_capture = scored(MF_CAPTURE, PieceType::Pawn, 0, 0);
+        let dpp = scored(MF_DOUBLE_PAWN_PUSH, PieceType::Pawn, 0, 0);
+        let mut moves = vec![dpp, pawn_capture];
+
+        moves.sort();
+        assert_eq!(moves[0], pawn_capture);
+        assert_eq!(moves[1], dpp);
+    }
+
+    #[test]
+    pub fn order_better_square_delta_when_moves_quiet() {
+        let better_move = scored(0b0, PieceType::Pawn, 0, 23);
+        let worse_move = scored(0b0, PieceType::Queen, 0, 11);
+        let mut moves = vec![worse_move, better_move];
+
+        moves.sort();
+        assert_eq!(moves[0], better_move);
+        assert_eq!(moves[1], worse_move);
+    }
+}
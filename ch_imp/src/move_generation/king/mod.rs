@@ -10,6 +10,8 @@ use crate::{
             KING_CASTLING_CHECK, KING_CASTLING_CLEARANCE, QUEEN_CASTLING_CHECK,
             QUEEN_CASTLING_CLEARANCE,
         },
+        move_list::MoveList,
+        scored_move::ScoredMove,
         Move,
     },
     shared::{
@@ -34,10 +36,11 @@ pub(super) fn generate_king_moves(
     queen_side_castling: bool,
     board: BoardRep,
     ad_table: &mut AttackAndDefendTable,
-    phase: i16
-) -> Vec<Move> {
+    phase: i16,
+    moves: &mut MoveList,
+) {
     let moveboard = get_legal_moveboard(index, ad_table, board, is_black);
-    let mut moves = moveboard_to_moves(
+    moveboard_to_moves(
         index,
         PieceType::King,
         moveboard,
@@ -46,7 +49,8 @@ pub(super) fn generate_king_moves(
         board,
         ad_table,
         None,
-        phase
+        phase,
+        moves,
     );
 
     if !king_analysis.check {
@@ -87,8 +91,6 @@ pub(super) fn generate_king_moves(
             }
         }
     }
-
-    moves
 }
 
 fn get_legal_moveboard(
@@ -120,7 +122,7 @@ fn generate_king_castling_move(
     board: BoardRep,
     ad_table: &AttackAndDefendTable,
     phase: i16
-) -> Option<Move> {
+) -> Option<ScoredMove> {
     if castling_clearance_board & board.occupancy != 0 {
         return None;
     }
@@ -133,14 +135,11 @@ fn generate_king_castling_move(
         castling_check_board = castling_check_board.flip(lsb as u8);
     }
 
-    let m = Move::new(
-        from_index,
-        to_index,
-        castling_flag,
+    let m = ScoredMove::new(
+        Move::new(from_index, to_index, castling_flag),
         PieceType::King,
-        is_black,
         0,
-        square_delta(from_index as usize, to_index as usize, is_black, PieceType::King, phase)
+        square_delta(from_index as usize, to_index as usize, is_black, PieceType::King, phase),
     );
     return Some(m);
 }
@@ -3,7 +3,7 @@ use crate::{
         attack_and_defend_lookups::AttackAndDefendTable, bitboard::Bitboard, board_rep::BoardRep,
     },
     move_generation::king::{is_legal_castling, is_legal_king_move},
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::{board_utils::index_from_coords, constants::MF_QUEEN_CASTLING, piece_type::PieceType},
 };
 
@@ -15,15 +15,7 @@ fn is_legal_king_move_scenario_0() {
         "r1b1k1nr/pp1p1pp1/n2Bp3/7p/1PpPP3/2N5/PP3PPP/R3K1NR b KQkq - 3 10".into(),
     );
 
-    let m = Move::new(
-        index_from_coords("e8"),
-        index_from_coords("f8"),
-        0b0,
-        PieceType::King,
-        true,
-        0,
-        0,
-    );
+    let m = Move::new(index_from_coords("e8"), index_from_coords("f8"), 0b0);
 
     assert!(!is_legal_king_move(
         m,
@@ -37,15 +29,7 @@ fn is_legal_castling_scenario_0() {
     let board =
         BoardRep::from_fen("rn3bnr/ppp3k1/1q1p4/1b4Pp/3PPB2/P4N2/2P3P1/R2NK2R w KQ h6 0 16".into());
 
-    let m = Move::new(
-        index_from_coords("e1"),
-        index_from_coords("c1"),
-        MF_QUEEN_CASTLING,
-        PieceType::King,
-        false,
-        0,
-        0,
-    );
+    let m = Move::new(index_from_coords("e1"), index_from_coords("c1"), MF_QUEEN_CASTLING);
 
     assert!(!is_legal_king_move(
         m,
@@ -59,15 +43,7 @@ fn is_legal_castling_scenario_1() {
     let board =
         BoardRep::from_fen("r3k1nr/1p5p/2n1p3/p5B1/2NP2PP/6b1/PP6/R2K2NR b kq - 0 17".into());
 
-    let m = Move::new(
-        index_from_coords("e8"),
-        index_from_coords("c8"),
-        MF_QUEEN_CASTLING,
-        PieceType::King,
-        true,
-        0,
-        0,
-    );
+    let m = Move::new(index_from_coords("e8"), index_from_coords("c8"), MF_QUEEN_CASTLING);
 
     assert!(!is_legal_king_move(
         m,
@@ -84,7 +60,8 @@ fn generate_moves_illegal_move_scenario_0() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_king_moves(
+    let mut moves = MoveList::new();
+    generate_king_moves(
         board.black_king_position,
         board.white_occupancy,
         board.occupancy,
@@ -94,18 +71,11 @@ fn generate_moves_illegal_move_scenario_0() {
         board.black_queen_side_castling,
         board,
         &mut ad_table,
-        0
-    );
-
-    let illegal_move = Move::new(
-        board.black_king_position,
-        index_from_coords("b8"),
-        0b0,
-        PieceType::King,
-        true,
-        0,
         0,
+        &mut moves,
     );
 
-    assert!(!moves.contains(&illegal_move))
+    let illegal_move = Move::new(board.black_king_position, index_from_coords("b8"), 0b0);
+
+    assert!(!moves.iter().any(|s| s.m == illegal_move))
 }
@@ -5,7 +5,7 @@ use crate::{
         board_rep::BoardRep,
         king_position_analysis::{ThreatRaycastCollision, ThreatSource},
     },
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::piece_type::PieceType,
     MOVE_DATA,
 };
@@ -21,7 +21,8 @@ pub(super) fn generate_knight_moves(
     ad_table: &mut AttackAndDefendTable,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let mut moveboard = MOVE_DATA.knight_moves[index as usize];
 
     if king_threat != None {
@@ -39,7 +40,8 @@ pub(super) fn generate_knight_moves(
         ad_table,
         reveal_attack,
         phase,
-    )
+        moves,
+    );
 }
 
 pub(super) fn is_legal_knight_move(m: Move, board: BoardRep) -> bool {
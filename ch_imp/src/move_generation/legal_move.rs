@@ -22,23 +22,27 @@ use super::{
 
 impl Position {
     pub fn is_legal_move(&self, m: Move) -> bool {
-        // Move for correct colour
-        if self.board.black_turn != m.is_black() {
-            return false;
-        }
-
         let friendly_occupancy = if self.board.black_turn {
             self.board.black_occupancy
         } else {
             self.board.white_occupancy
         };
 
-        // If we dont have a piece of the correct type on the from square for the correct colour this cant be a legal move
-        if !friendly_occupancy.occupied(m.from())
-            || self.board.get_piece_type_at_index(m.from()) != m.piece_type()
+        // If we dont have a piece on the from square for the side to move this cant be a legal move
+        if !friendly_occupancy.occupied(m.from()) {
+            return false;
+        }
+
+        // Only pawns can push twice, capture en passant or promote and only kings can castle
+        let piece_type = self.board.get_piece_type_at_index(m.from());
+        if piece_type != PieceType::Pawn
+            && (m.is_double_pawn_push() || m.is_ep_capture() || m.is_promotion())
         {
             return false;
         }
+        if piece_type != PieceType::King && m.is_castling() {
+            return false;
+        }
 
         // Not a capture but square occupied
         if !m.is_capture() && self.board.occupancy.occupied(m.to()) {
@@ -65,7 +69,7 @@ impl Position {
         }
 
         // Is double check only king moves are legal
-        if self.double_check && m.piece_type() != PieceType::King {
+        if self.double_check && piece_type != PieceType::King {
             return false;
         }
 
@@ -76,7 +80,9 @@ impl Position {
         };
 
         // If in check see if this move removes check
-        if self.current_in_check() && !move_removes_check(m, &king_analysis, self.board) {
+        if self.current_in_check()
+            && !move_removes_check(m, piece_type, &king_analysis, self.board)
+        {
             return false;
         }
 
@@ -105,11 +111,16 @@ impl Position {
 
         // Move is not king move into check
 
-        return self.is_legal_piece_move(m, &king_analysis);
+        return self.is_legal_piece_move(m, piece_type, &king_analysis);
     }
 
-    fn is_legal_piece_move(&self, m: Move, king_analysis: &KingPositionAnalysis) -> bool {
-        match m.piece_type() {
+    fn is_legal_piece_move(
+        &self,
+        m: Move,
+        piece_type: PieceType,
+        king_analysis: &KingPositionAnalysis,
+    ) -> bool {
+        match piece_type {
             PieceType::Pawn => is_legal_pawn_move(m, self.board),
             PieceType::Knight => is_legal_knight_move(m, self.board),
             PieceType::Bishop => is_legal_bishop_move(m, self.board),
@@ -121,7 +132,12 @@ impl Position {
     }
 }
 
-fn move_removes_check(m: Move, king_analysis: &KingPositionAnalysis, board: BoardRep) -> bool {
+fn move_removes_check(
+    m: Move,
+    piece_type: PieceType,
+    king_analysis: &KingPositionAnalysis,
+    board: BoardRep,
+) -> bool {
     let pin = Option::<&ThreatRaycastCollision>::copied(
         king_analysis.pins.iter().find(|p| p.at == m.from()),
     );
@@ -134,7 +150,7 @@ fn move_removes_check(m: Move, king_analysis: &KingPositionAnalysis, board: Boar
                 if m.to() == threat.from {
                     return true;
                 }
-                if m.piece_type() != PieceType::King && (1 << m.to()) & threat.threat_ray_mask != 0
+                if piece_type != PieceType::King && (1 << m.to()) & threat.threat_ray_mask != 0
                 {
                     return true;
                 }
@@ -142,7 +158,7 @@ fn move_removes_check(m: Move, king_analysis: &KingPositionAnalysis, board: Boar
             }
             None =>  {
                 // Must be a double check - oof
-                let attacks_on_to = board.get_attacked_by(m.to(), !m.is_black());
+                let attacks_on_to = board.get_attacked_by(m.to(), !board.black_turn);
                 attacks_on_to.any()
             }
         },
@@ -178,47 +194,24 @@ mod test {
     #[test]
     fn is_not_king_move_in_double_check() {
         let position = Position::from_fen("k7/8/8/8/8/1b2n3/3P4/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("d2"),
-            index_from_coords("e3"),
-            MF_CAPTURE,
-            PieceType::Pawn,
-            false,
-            2,
-            0
-        );
+        let m = Move::new(index_from_coords("d2"), index_from_coords("e3"), MF_CAPTURE);
         assert!(!position.is_legal_move(m));
     }
 
     #[test]
     fn is_not_white_move_during_white_turn() {
         let position = Position::from_fen("k7/8/8/8/b7/5n2/2PP4/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("a8"),
-            index_from_coords("a7"),
-            0b0,
-            PieceType::King,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("a8"), index_from_coords("a7"), 0b0);
         assert!(!position.is_legal_move(m));
     }
 
     #[test]
     fn in_check_moving_piece_but_does_not_block_threat() {
         let position = Position::from_fen("k7/8/8/8/b7/5n2/1N1P4/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("b2"),
-            index_from_coords("d3"),
-            0b0,
-            PieceType::Knight,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("b2"), index_from_coords("d3"), 0b0);
         assert!(!move_removes_check(
             m,
+            PieceType::Knight,
             &position.board.get_white_king_analysis(),
             position.board
         ));
@@ -227,17 +220,10 @@ mod test {
     #[test]
     fn in_check_moving_piece_captures_threat() {
         let position = Position::from_fen("k7/8/8/8/3r4/8/2N5/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c2"),
-            index_from_coords("d4"),
-            MF_CAPTURE,
-            PieceType::Knight,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c2"), index_from_coords("d4"), MF_CAPTURE);
         assert!(move_removes_check(
             m,
+            PieceType::Knight,
             &position.board.get_white_king_analysis(),
             position.board
         ));
@@ -246,17 +232,10 @@ mod test {
     #[test]
     fn in_check_piece_moving_to_block_threat_is_pinned() {
         let position = Position::from_fen("k2r4/8/8/8/b7/8/2N5/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c2"),
-            index_from_coords("d4"),
-            0b0,
-            PieceType::Knight,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c2"), index_from_coords("d4"), 0b0);
         assert!(!move_removes_check(
             m,
+            PieceType::Knight,
             &position.board.get_white_king_analysis(),
             position.board
         ));
@@ -265,17 +244,10 @@ mod test {
     #[test]
     fn in_check_piece_capturing_threat_is_pinned() {
         let position = Position::from_fen("k7/8/8/8/b2r4/8/2N5/3K4 w - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c2"),
-            index_from_coords("d4"),
-            MF_CAPTURE,
-            PieceType::Knight,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c2"), index_from_coords("d4"), MF_CAPTURE);
         assert!(!move_removes_check(
             m,
+            PieceType::Knight,
             &position.board.get_white_king_analysis(),
             position.board
         ));
@@ -284,45 +256,21 @@ mod test {
     #[test]
     fn not_in_check_but_piece_is_pinned_and_moving_off_threat_ray() {
         let position = Position::from_fen("k7/8/2b5/8/8/8/6B1/5K2 b - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c6"),
-            index_from_coords("b5"),
-            0b0,
-            PieceType::Bishop,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c6"), index_from_coords("b5"), 0b0);
         assert!(!position.is_legal_move(m));
     }
 
     #[test]
     fn not_in_check_but_ep_capture_would_result_in_check() {
         let position = Position::from_fen("8/8/8/8/R4pPk/8/8/K7 b - g3 0 1".into());
-        let m = Move::new(
-            index_from_coords("f4"),
-            index_from_coords("g3"),
-            MF_EP_CAPTURE,
-            PieceType::Pawn,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("f4"), index_from_coords("g3"), MF_EP_CAPTURE);
         assert!(!position.is_legal_move(m));
     }
 
     #[test]
     fn is_legal_pinned_piece_move_stays_inside_threat_ray() {
         let position = Position::from_fen("k7/8/2b5/8/8/8/6B1/5K2 b - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c6"),
-            index_from_coords("d5"),
-            0b0,
-            PieceType::Bishop,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c6"), index_from_coords("d5"), 0b0);
         assert!(is_legal_pinned_piece_move(
             m,
             &position.board.get_black_king_analysis()
@@ -332,15 +280,7 @@ mod test {
     #[test]
     fn is_legal_pinned_piece_move_captures_pinning_piece() {
         let position = Position::from_fen("k7/8/2b5/8/8/8/6B1/5K2 b - - 0 1".into());
-        let m = Move::new(
-            index_from_coords("c6"),
-            index_from_coords("g2"),
-            MF_CAPTURE,
-            PieceType::Bishop,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("c6"), index_from_coords("g2"), MF_CAPTURE);
         assert!(is_legal_pinned_piece_move(
             m,
             &position.board.get_black_king_analysis()
@@ -352,31 +292,18 @@ mod test {
         let position = Position::from_fen(
             "rnbqkbnr/ppp1p1p1/8/3p1p1p/2B1P2P/8/PPPP1PP1/RNBQK1NR w KQkq d6 0 4".into(),
         );
-        let m = Move::new(
-            index_from_coords("c4"),
-            index_from_coords("d5"),
-            MF_CAPTURE,
-            PieceType::Pawn,
-            false,
-            0,
-            0,
-        );
+        // Moves dont carry their piece, so pawn only flags on a bishop move have to be rejected
+        let m = Move::new(index_from_coords("c4"), index_from_coords("d6"), MF_EP_CAPTURE);
         assert!(!position.is_legal_move(m));
+        let m = Move::new(index_from_coords("c4"), index_from_coords("d5"), MF_CAPTURE);
+        assert!(position.is_legal_move(m));
     }
 
     #[test]
     fn is_legal_scenario_1() {
         let position =
             Position::from_fen("rnbqkbnr/ppppppp1/8/7p/7P/8/PPPPPPP1/RNBQKBNR w KQkq h6 0 ".into());
-        let m = Move::new(
-            index_from_coords("f1"),
-            index_from_coords("e2"),
-            0b0,
-            PieceType::Bishop,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("f1"), index_from_coords("e2"), 0b0);
         assert!(!position.is_legal_move(m));
     }
 
@@ -385,15 +312,7 @@ mod test {
         let position = Position::from_fen(
             "r1bq1bnr/ppp1kppp/2np4/1B2Q3/4PP2/8/PPPP2PP/RNB1K1NR b KQ - 0 5".into(),
         );
-        let m = Move::new(
-            index_from_coords("e7"),
-            index_from_coords("e6"),
-            0b0,
-            PieceType::King,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("e7"), index_from_coords("e6"), 0b0);
         assert!(!position.is_legal_move(m));
     }
 
@@ -401,15 +320,7 @@ mod test {
     fn is_legal_scenario_3() {
         let position =
             Position::from_fen("rn3b1r/ppp2kpp/3p4/8/P3P1nP/4q3/4K3/4R1NR w - - 6 21".into());
-        let m = Move::new(
-            index_from_coords("e1"),
-            index_from_coords("e3"),
-            MF_CAPTURE,
-            PieceType::Rook,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("e1"), index_from_coords("e3"), MF_CAPTURE);
         assert!(!position.is_legal_move(m));
     }
 
@@ -417,15 +328,7 @@ mod test {
     fn is_legal_scenario_4() {
         let position =
             Position::from_fen("rn3b1r/ppp2kpp/3p1n2/8/P3P1bP/5P2/1BP5/R2Kq1NR w - - 1 15".into());
-        let m = Move::new(
-            index_from_coords("f3"),
-            index_from_coords("g4"),
-            MF_CAPTURE,
-            PieceType::Pawn,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("f3"), index_from_coords("g4"), MF_CAPTURE);
         assert!(!position.is_legal_move(m));
     }
 }
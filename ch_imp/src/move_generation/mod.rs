@@ -9,7 +9,7 @@ use crate::{
         see::piece_safety,
     },
     evaluation::{calculate_game_phase, endgame::*, opening::*},
-    r#move::Move,
+    r#move::{move_list::MoveList, scored_move::ScoredMove, Move},
     shared::piece_type::{self, PieceType},
 };
 
@@ -24,7 +24,8 @@ fn generate_moves(
     king_analysis: &KingPositionAnalysis,
     opponent_king_analysis: &KingPositionAnalysis,
     board: BoardRep,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let mut friendly_occupancy = if board.black_turn {
         board.black_occupancy
     } else {
@@ -54,7 +55,7 @@ fn generate_moves(
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let mut moves = king::generate_king_moves(
+    king::generate_king_moves(
         king_pos,
         opponent_occupancy,
         board.occupancy,
@@ -65,11 +66,12 @@ fn generate_moves(
         board,
         &mut ad_table,
         0,
+        moves,
     );
 
     // In the event of double king check we can only avoid check by moving the king
     if king_analysis.double_check {
-        return moves;
+        return;
     }
 
     // Check if any of our pieces moving is a reveal attack
@@ -82,22 +84,24 @@ fn generate_moves(
 
     while friendly_occupancy != 0 {
         let piece_position = friendly_occupancy.trailing_zeros() as u8;
-        moves.extend(generate_index_moves(
+        generate_index_moves(
             board,
             &mut ad_table,
             piece_position,
             king_analysis,
             &reveal_attacks,
             phase,
-        ));
+            moves,
+        );
         friendly_occupancy ^= 1 << piece_position;
     }
 
     moves.sort();
-    moves
 }
 
-pub fn generate_moves_for_board(board: BoardRep) -> Vec<Move> {
+// Clears `moves` and fills it with the legal moves for the side to move, best first
+pub fn generate_moves_for_board(board: BoardRep, moves: &mut MoveList) {
+    moves.clear();
     let (king_analysis, opponent_king_analysis) = if board.black_turn {
         (
             board.get_black_king_analysis(),
@@ -110,7 +114,7 @@ pub fn generate_moves_for_board(board: BoardRep) -> Vec<Move> {
         )
     };
 
-    generate_moves(&king_analysis, &opponent_king_analysis, board)
+    generate_moves(&king_analysis, &opponent_king_analysis, board, moves)
 }
 
 fn generate_index_moves(
@@ -120,7 +124,8 @@ fn generate_index_moves(
     king_analysis: &KingPositionAnalysis,
     reveal_attacks: &Vec<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let piece_type = board.get_piece_type_at_index(index);
     let opponent_occupancy = if board.black_turn {
         board.white_occupancy
@@ -132,7 +137,7 @@ fn generate_index_moves(
     );
     // If we're pinned but the king is also threatened we can't help
     if pin != None && king_analysis.threat_source != None {
-        return vec![];
+        return;
     }
 
     // If we're a reveal attack we can ignore opponent response in a SEE
@@ -149,9 +154,10 @@ fn generate_index_moves(
             pin,
             reveal_attack,
             phase,
+            moves,
         ),
         piece_type::PieceType::Knight => match pin {
-            Some(_) => vec![],
+            Some(_) => {}
             None => knight::generate_knight_moves(
                 index,
                 opponent_occupancy,
@@ -161,13 +167,14 @@ fn generate_index_moves(
                 ad_table,
                 reveal_attack,
                 phase,
+                moves,
             ),
         },
         piece_type::PieceType::Bishop => {
             match pin {
                 Some(pin) => {
                     if pin.threat_type != ThreatType::DiagonalSlide {
-                        return vec![];
+                        return;
                     } else {
                     }
                 }
@@ -183,13 +190,14 @@ fn generate_index_moves(
                 pin,
                 reveal_attack,
                 phase,
+                moves,
             )
         }
         piece_type::PieceType::Rook => {
             match pin {
                 Some(pin) => {
                     if pin.threat_type != ThreatType::OrthogonalSlide {
-                        return vec![];
+                        return;
                     } else {
                     }
                 }
@@ -205,6 +213,7 @@ fn generate_index_moves(
                 pin,
                 reveal_attack,
                 phase,
+                moves,
             )
         }
         piece_type::PieceType::Queen => sliding::queen::generate_queen_moves(
@@ -217,8 +226,9 @@ fn generate_index_moves(
             pin,
             reveal_attack,
             phase,
+            moves,
         ),
-        piece_type::PieceType::King => Vec::new(),
+        piece_type::PieceType::King => {}
         _ => panic!(
             "Unexpected piece {piece_type:?} at position {index} : {}",
             board.to_fen()
@@ -236,8 +246,8 @@ fn moveboard_to_moves(
     ad_table: &mut AttackAndDefendTable,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
-    let mut generated_moves = Vec::new();
+    generated_moves: &mut MoveList,
+) {
     let mut m_b = moveboard;
     while m_b != 0 {
         let lsb = m_b.trailing_zeros() as u8;
@@ -256,12 +266,11 @@ fn moveboard_to_moves(
         if opponent_occupancy.occupied(lsb) {
             let attacked_piece_type = board.get_piece_type_at_index(lsb);
 
-            generated_moves.push(Move::capture_move(
+            generated_moves.push(ScoredMove::capture(
                 from_index,
                 lsb,
                 piece_type,
                 attacked_piece_type,
-                board.black_turn,
                 friendly,
                 opponent,
                 square_delta(
@@ -273,12 +282,9 @@ fn moveboard_to_moves(
                 ),
             ));
         } else if !occupancy.occupied(lsb) {
-            generated_moves.push(Move::new(
-                from_index,
-                lsb,
-                0b0,
+            generated_moves.push(ScoredMove::new(
+                Move::new(from_index, lsb, 0b0),
                 piece_type,
-                board.black_turn,
                 piece_safety(piece_type, true, opponent, friendly),
                 square_delta(
                     from_index as usize,
@@ -291,8 +297,6 @@ fn moveboard_to_moves(
         };
         m_b ^= 1 << lsb;
     }
-
-    generated_moves
 }
 
 pub fn square_delta(
@@ -26,7 +26,7 @@ fn is_legal_capture(m: Move, board: BoardRep) -> bool {
 
 fn is_legal_ddp(m: Move, board: BoardRep) -> bool {
     let rank = get_rank(m.from());
-    if (!m.is_black() && rank != 1) || (m.is_black() && rank != 6) {
+    if (!board.black_turn && rank != 1) || (board.black_turn && rank != 6) {
         return false
     }
     let offset_file: i8 = if board.black_turn { -1 } else { 1 };
@@ -41,7 +41,7 @@ fn is_legal_ep_capture(m: Move, board: BoardRep) -> bool {
 
 fn is_legal_capture_promotion(m: Move, board: BoardRep) -> bool {
     let rank = get_rank(m.to());
-    if (m.is_black() && rank != 0) || (!m.is_black() && rank != 7) {
+    if (board.black_turn && rank != 0) || (!board.black_turn && rank != 7) {
         return false
     }
     is_legal_capture(m, board)
@@ -49,7 +49,7 @@ fn is_legal_capture_promotion(m: Move, board: BoardRep) -> bool {
 
 fn is_legal_promotion(m: Move, board: BoardRep) -> bool {
     let rank = get_rank(m.to());
-    if (m.is_black() && rank != 0) || (!m.is_black() && rank != 7) {
+    if (board.black_turn && rank != 0) || (!board.black_turn && rank != 7) {
         return false
     }
     is_legal_capture(m, board)
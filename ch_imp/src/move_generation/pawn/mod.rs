@@ -6,7 +6,7 @@ use crate::{
         king_position_analysis::{ThreatRaycastCollision, ThreatSource},
         see::{piece_safety, see_from_capture},
     },
-    r#move::Move,
+    r#move::{move_list::MoveList, scored_move::ScoredMove, Move},
     shared::{
         board_utils::{get_file, get_rank},
         constants::{
@@ -34,10 +34,11 @@ pub(super) fn generate_pawn_moves(
     pin: Option<ThreatRaycastCollision>,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     if king_threat != None {
         let kt = king_threat.unwrap();
-        return generate_pawn_moves_when_threatened(
+        generate_pawn_moves_when_threatened(
             index,
             kt.from,
             kt.threat_ray_mask,
@@ -45,12 +46,14 @@ pub(super) fn generate_pawn_moves(
             ad_table,
             reveal_attack,
             phase,
+            moves,
         );
+        return;
     }
 
     if pin != None && pin.unwrap().reveal_attack == false {
         let pin = pin.unwrap();
-        return generate_pawn_moves_when_threatened(
+        generate_pawn_moves_when_threatened(
             index,
             pin.from,
             pin.threat_ray_mask,
@@ -58,22 +61,20 @@ pub(super) fn generate_pawn_moves(
             ad_table,
             reveal_attack,
             phase,
+            moves,
         );
+        return;
     }
 
-    let mut moves = Vec::new();
     let offset_file: i8 = if board.black_turn { -1 } else { 1 };
     let rank = get_rank(index);
 
     let to = (index as i8 + (8 * offset_file)) as u8;
     if !board.occupancy.occupied(to) {
         if get_rank(to) != 0 && get_rank(to) != 7 {
-            moves.push(Move::new(
-                index,
-                to,
-                0b0,
+            moves.push(ScoredMove::new(
+                Move::new(index, to, 0b0),
                 PieceType::Pawn,
-                board.black_turn,
                 get_see(ad_table, board, to, false, reveal_attack),
                 square_delta(
                     index as usize,
@@ -87,12 +88,9 @@ pub(super) fn generate_pawn_moves(
             if (board.black_turn && rank == 6) || (!board.black_turn && rank == 1) {
                 let dpp = (to as i8 + (8 * offset_file)) as u8;
                 if !board.occupancy.occupied(dpp) {
-                    moves.push(Move::new(
-                        index,
-                        dpp,
-                        MF_DOUBLE_PAWN_PUSH,
+                    moves.push(ScoredMove::new(
+                        Move::new(index, dpp, MF_DOUBLE_PAWN_PUSH),
                         PieceType::Pawn,
-                        board.black_turn,
                         get_see(ad_table, board, dpp, false, reveal_attack),
                         square_delta(
                             index as usize,
@@ -105,14 +103,15 @@ pub(super) fn generate_pawn_moves(
                 }
             }
         } else {
-            moves.extend(generate_pawn_promotion_moves(
+            generate_pawn_promotion_moves(
                 index,
                 to,
                 false,
                 board.black_turn,
                 get_see(ad_table, board, to, false, reveal_attack),
                 phase,
-            ));
+                moves,
+            );
         }
     }
 
@@ -127,25 +126,27 @@ pub(super) fn generate_pawn_moves(
         if !is_ep_capture || !leads_to_ep_check {
             let see = get_see(ad_table, board, capture_a, true, reveal_attack);
             if capture_a_rank == 0 || capture_a_rank == 7 {
-                moves.extend(generate_pawn_promotion_moves(
+                generate_pawn_promotion_moves(
                     index,
                     capture_a,
                     true,
                     board.black_turn,
                     see,
                     phase,
-                ));
+                    moves,
+                );
             } else {
-                moves.push(Move::new(
-                    index,
-                    capture_a,
-                    if capture_a == board.ep_index {
-                        MF_EP_CAPTURE
-                    } else {
-                        MF_CAPTURE
-                    },
+                moves.push(ScoredMove::new(
+                    Move::new(
+                        index,
+                        capture_a,
+                        if capture_a == board.ep_index {
+                            MF_EP_CAPTURE
+                        } else {
+                            MF_CAPTURE
+                        },
+                    ),
                     PieceType::Pawn,
-                    board.black_turn,
                     see,
                     square_delta(
                         index as usize,
@@ -171,25 +172,27 @@ pub(super) fn generate_pawn_moves(
             if !is_ep_capture || !leads_to_ep_check {
                 let see = get_see(ad_table, board, capture_b, true, reveal_attack);
                 if capture_b_rank == 0 || capture_b_rank == 7 {
-                    moves.extend(generate_pawn_promotion_moves(
+                    generate_pawn_promotion_moves(
                         index,
                         capture_b,
                         true,
                         board.black_turn,
                         see,
                         phase,
-                    ));
+                        moves,
+                    );
                 } else {
-                    moves.push(Move::new(
-                        index,
-                        capture_b,
-                        if capture_b == board.ep_index {
-                            MF_EP_CAPTURE
-                        } else {
-                            MF_CAPTURE
-                        },
+                    moves.push(ScoredMove::new(
+                        Move::new(
+                            index,
+                            capture_b,
+                            if capture_b == board.ep_index {
+                                MF_EP_CAPTURE
+                            } else {
+                                MF_CAPTURE
+                            },
+                        ),
                         PieceType::Pawn,
-                        board.black_turn,
                         see,
                         square_delta(
                             index as usize,
@@ -204,7 +207,6 @@ pub(super) fn generate_pawn_moves(
         }
     }
 
-    moves
 }
 
 pub fn ep_leads_to_orthogonal_check(
@@ -238,8 +240,8 @@ fn generate_pawn_moves_when_threatened(
     ad_table: &mut AttackAndDefendTable,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
-    let mut moves = Vec::new();
+    moves: &mut MoveList,
+) {
     let offset_file: i8 = if board.black_turn { -1 } else { 1 };
     let rank = get_rank(index);
 
@@ -248,12 +250,9 @@ fn generate_pawn_moves_when_threatened(
         if (1 << to) & threat_ray_mask != 0 {
             let see = get_see(ad_table, board, to, false, reveal_attack);
             if get_rank(to) != 0 && get_rank(to) != 7 {
-                moves.push(Move::new(
-                    index,
-                    to,
-                    0b0,
+                moves.push(ScoredMove::new(
+                    Move::new(index, to, 0b0),
                     PieceType::Pawn,
-                    board.black_turn,
                     0,
                     square_delta(
                         index as usize,
@@ -264,14 +263,15 @@ fn generate_pawn_moves_when_threatened(
                     ),
                 ));
             } else {
-                moves.extend(generate_pawn_promotion_moves(
+                generate_pawn_promotion_moves(
                     index,
                     to,
                     false,
                     board.black_turn,
                     0,
                     phase,
-                ));
+                    moves,
+                );
             }
         }
 
@@ -281,12 +281,9 @@ fn generate_pawn_moves_when_threatened(
             let dpp = (to as i8 + (8 * offset_file)) as u8;
             if (1 << dpp) & threat_ray_mask != 0 {
                 let see = get_see(ad_table, board, dpp, false, reveal_attack);
-                moves.push(Move::new(
-                    index,
-                    dpp,
-                    MF_DOUBLE_PAWN_PUSH,
+                moves.push(ScoredMove::new(
+                    Move::new(index, dpp, MF_DOUBLE_PAWN_PUSH),
                     PieceType::Pawn,
-                    board.black_turn,
                     see,
                     square_delta(
                         index as usize,
@@ -309,25 +306,27 @@ fn generate_pawn_moves_when_threatened(
         if (rank as i8 + offset_file) as u8 == capture_a_rank {
             let see = get_see(ad_table, board, capture_a, true, reveal_attack);
             if capture_a_rank == 0 || capture_a_rank == 7 {
-                moves.extend(generate_pawn_promotion_moves(
+                generate_pawn_promotion_moves(
                     index,
                     capture_a,
                     true,
                     board.black_turn,
                     see,
                     phase,
-                ));
+                    moves,
+                );
             } else {
-                moves.push(Move::new(
-                    index,
-                    capture_a,
-                    if capture_a == board.ep_index {
-                        MF_EP_CAPTURE
-                    } else {
-                        MF_CAPTURE
-                    },
+                moves.push(ScoredMove::new(
+                    Move::new(
+                        index,
+                        capture_a,
+                        if capture_a == board.ep_index {
+                            MF_EP_CAPTURE
+                        } else {
+                            MF_CAPTURE
+                        },
+                    ),
                     PieceType::Pawn,
-                    board.black_turn,
                     see,
                     square_delta(
                         index as usize,
@@ -350,25 +349,27 @@ fn generate_pawn_moves_when_threatened(
             if (rank as i8 + offset_file) as u8 == capture_b_rank {
                 let see = get_see(ad_table, board, capture_b, true, reveal_attack);
                 if capture_b_rank == 0 || capture_b_rank == 7 {
-                    moves.extend(generate_pawn_promotion_moves(
+                    generate_pawn_promotion_moves(
                         index,
                         capture_b,
                         true,
                         board.black_turn,
                         see,
                         phase,
-                    ));
+                        moves,
+                    );
                 } else {
-                    moves.push(Move::new(
-                        index,
-                        capture_b,
-                        if capture_b == board.ep_index {
-                            MF_EP_CAPTURE
-                        } else {
-                            MF_CAPTURE
-                        },
+                    moves.push(ScoredMove::new(
+                        Move::new(
+                            index,
+                            capture_b,
+                            if capture_b == board.ep_index {
+                                MF_EP_CAPTURE
+                            } else {
+                                MF_CAPTURE
+                            },
+                        ),
                         PieceType::Pawn,
-                        board.black_turn,
                         see,
                         square_delta(
                             index as usize,
@@ -382,7 +383,6 @@ fn generate_pawn_moves_when_threatened(
             }
         }
     }
-    moves
 }
 
 fn generate_pawn_promotion_moves(
@@ -392,7 +392,8 @@ fn generate_pawn_promotion_moves(
     is_black: bool,
     see: i8,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let square_delta = square_delta(
         from_index as usize,
         to_index as usize,
@@ -400,60 +401,29 @@ fn generate_pawn_promotion_moves(
         PieceType::Pawn,
         phase,
     );
-    return vec![
-        Move::new(
-            from_index,
-            to_index,
-            if !is_capture {
-                MF_KNIGHT_PROMOTION
-            } else {
-                MF_KNIGHT_CAPTURE_PROMOTION
-            },
-            PieceType::Pawn,
-            is_black,
-            see,
-            square_delta,
-        ), // Knight
-        Move::new(
-            from_index,
-            to_index,
-            if !is_capture {
-                MF_BISHOP_PROMOTION
-            } else {
-                MF_BISHOP_CAPTURE_PROMOTION
-            },
-            PieceType::Pawn,
-            is_black,
-            see,
-            square_delta,
-        ), // Bishop
-        Move::new(
-            from_index,
-            to_index,
-            if !is_capture {
-                MF_ROOK_PROMOTION
-            } else {
-                MF_ROOK_CAPTURE_PROMOTION
-            },
-            PieceType::Pawn,
-            is_black,
-            see,
-            square_delta,
-        ), // Rook
-        Move::new(
-            from_index,
-            to_index,
-            if !is_capture {
-                MF_QUEEN_PROMOTION
-            } else {
-                MF_QUEEN_CAPTURE_PROMOTION
-            },
+    let flags = if !is_capture {
+        [
+            MF_KNIGHT_PROMOTION,
+            MF_BISHOP_PROMOTION,
+            MF_ROOK_PROMOTION,
+            MF_QUEEN_PROMOTION,
+        ]
+    } else {
+        [
+            MF_KNIGHT_CAPTURE_PROMOTION,
+            MF_BISHOP_CAPTURE_PROMOTION,
+            MF_ROOK_CAPTURE_PROMOTION,
+            MF_QUEEN_CAPTURE_PROMOTION,
+        ]
+    };
+    for flag in flags {
+        moves.push(ScoredMove::new(
+            Move::new(from_index, to_index, flag),
             PieceType::Pawn,
-            is_black,
             see,
             square_delta,
-        ), // Queen
-    ];
+        ));
+    }
 }
 
 fn get_see(
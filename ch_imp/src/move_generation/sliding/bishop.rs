@@ -6,7 +6,7 @@ use crate::{
         king_position_analysis::{ThreatRaycastCollision, ThreatSource},
     },
     move_generation::moveboard_to_moves,
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::piece_type::PieceType,
     MOVE_DATA,
 };
@@ -21,7 +21,8 @@ pub fn generate_bishop_moves(
     pin: Option<ThreatRaycastCollision>,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let mut moveboard = match pin {
         Some(p) => p.threat_ray_mask | (1 << p.from),
         None => MOVE_DATA
@@ -44,7 +45,8 @@ pub fn generate_bishop_moves(
         ad_table,
         reveal_attack,
         phase,
-    )
+        moves,
+    );
 }
 
 pub fn is_legal_bishop_move(m: Move, board: BoardRep) -> bool {
@@ -60,22 +62,14 @@ mod test {
     use crate::{
         board::{attack_and_defend_lookups::AttackAndDefendTable, board_rep::BoardRep},
         move_generation::sliding::bishop::{generate_bishop_moves, is_legal_bishop_move},
-        r#move::Move,
+        r#move::{move_list::MoveList, Move},
         shared::{board_utils::index_from_coords, constants::MF_CAPTURE, piece_type::PieceType},
     };
 
     #[test]
     fn is_legal_bishop_move_not_diagonal() {
         let board = BoardRep::from_fen("8/2p5/3p2b1/KP5r/1R2Pp1k/8/6P1/8 b - e3 0 1".into());
-        let m = Move::new(
-            index_from_coords("g6"),
-            index_from_coords("g2"),
-            MF_CAPTURE,
-            PieceType::Bishop,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("g6"), index_from_coords("g2"), MF_CAPTURE);
 
         assert!(!is_legal_bishop_move(m, board))
     }
@@ -83,15 +77,7 @@ mod test {
     #[test]
     fn is_legal_bishop_move_is_diagonal_and_legal() {
         let board = BoardRep::from_fen("8/2p5/3p2b1/KP5r/1R2Pp1k/8/6P1/8 b - e3 0 1".into());
-        let m = Move::new(
-            index_from_coords("g6"),
-            index_from_coords("e4"),
-            MF_CAPTURE,
-            PieceType::Bishop,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("g6"), index_from_coords("e4"), MF_CAPTURE);
 
         assert!(is_legal_bishop_move(m, board))
     }
@@ -104,7 +90,8 @@ mod test {
 
         let mut ad_table = AttackAndDefendTable::new();
 
-        let moves = generate_bishop_moves(
+        let mut moves = MoveList::new();
+        generate_bishop_moves(
             index_from_coords("g2"),
             board,
             &mut ad_table,
@@ -114,9 +101,10 @@ mod test {
             None,
             None,
             0,
+            &mut moves,
         );
 
-        assert_eq!(moves[0].see(), 0, "{}", moves[0]);
-        assert_eq!(moves[1].see(), 0, "{}", moves[0]);
+        assert_eq!(moves[0].see, 0, "{:?}", moves[0]);
+        assert_eq!(moves[1].see, 0, "{:?}", moves[0]);
     }
 }
@@ -6,7 +6,7 @@ use crate::{
         king_position_analysis::{ThreatRaycastCollision, ThreatSource},
     },
     move_generation::moveboard_to_moves,
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::piece_type::PieceType,
     MOVE_DATA,
 };
@@ -21,7 +21,8 @@ pub(crate) fn generate_queen_moves(
     pin: Option<ThreatRaycastCollision>,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let mut moveboard = match pin {
         Some(p) => p.threat_ray_mask | (1 << p.from),
         None => {
@@ -49,7 +50,8 @@ pub(crate) fn generate_queen_moves(
         ad_table,
         reveal_attack,
         phase,
-    )
+        moves,
+    );
 }
 
 pub fn is_legal_queen_move(m: Move, board: BoardRep) -> bool {
@@ -75,15 +77,7 @@ mod test {
         let board = BoardRep::from_fen(
             "r1bqkbnr/pp1npppp/2p5/8/3PN3/8/PPP2PPP/R1BQKBNR w KQkq - 0 1".into(),
         );
-        let m = Move::new(
-            index_from_coords("d1"),
-            index_from_coords("c3"),
-            0b0,
-            PieceType::Queen,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("d1"), index_from_coords("c3"), 0b0);
 
         assert!(!is_legal_queen_move(m, board))
     }
@@ -93,15 +87,7 @@ mod test {
         let board = BoardRep::from_fen(
             "r1bqkbnr/pp1npppp/2p5/8/3PN3/8/PPP2PPP/R1BQKBNR w KQkq - 0 1".into(),
         );
-        let m = Move::new(
-            index_from_coords("d1"),
-            index_from_coords("f3"),
-            0b0,
-            PieceType::Queen,
-            true,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("d1"), index_from_coords("f3"), 0b0);
 
         assert!(is_legal_queen_move(m, board))
     }
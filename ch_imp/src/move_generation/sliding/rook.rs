@@ -6,7 +6,7 @@ use crate::{
         king_position_analysis::{ThreatRaycastCollision, ThreatSource},
     },
     move_generation::moveboard_to_moves,
-    r#move::Move,
+    r#move::{move_list::MoveList, Move},
     shared::piece_type::PieceType,
     MOVE_DATA,
 };
@@ -21,7 +21,8 @@ pub fn generate_rook_moves(
     pin: Option<ThreatRaycastCollision>,
     reveal_attack: Option<ThreatRaycastCollision>,
     phase: i16,
-) -> Vec<Move> {
+    moves: &mut MoveList,
+) {
     let mut moveboard = match pin {
         Some(p) => p.threat_ray_mask | (1 << p.from),
         None => MOVE_DATA
@@ -44,7 +45,8 @@ pub fn generate_rook_moves(
         ad_table,
         reveal_attack,
         phase,
-    )
+        moves,
+    );
 }
 
 pub fn is_legal_rook_move(m: Move, board: BoardRep) -> bool {
@@ -60,7 +62,7 @@ mod test {
     use crate::{
         board::{attack_and_defend_lookups::AttackAndDefendTable, board_rep::BoardRep},
         move_generation::sliding::rook::{generate_rook_moves, is_legal_rook_move},
-        r#move::Move,
+        r#move::{move_list::MoveList, Move},
         shared::{board_utils::index_from_coords, constants::MF_CAPTURE, piece_type::PieceType},
     };
 
@@ -69,7 +71,8 @@ mod test {
         let board = BoardRep::from_fen("8/3p3k/3pb3/8/4R3/8/2B5/1K6 w - - 0 1".into());
         let reveal_attack = board.get_black_king_analysis().pins[0];
 
-        let mut moves = generate_rook_moves(
+        let mut moves = MoveList::new();
+        generate_rook_moves(
             index_from_coords("e4"),
             board,
             &mut AttackAndDefendTable::new(),
@@ -77,15 +80,16 @@ mod test {
             board.occupancy,
             None,
             None,
-            Some(reveal_attack),0
+            Some(reveal_attack),0,
+            &mut moves,
         );
 
         moves.sort();
 
         println!("{moves:?}");
 
-        assert_eq!(moves[0].to(), index_from_coords("e6"));
-        assert_eq!(moves[0].see(), 3);
+        assert_eq!(moves[0].m.to(), index_from_coords("e6"));
+        assert_eq!(moves[0].see, 3);
     }
 
     #[test]
@@ -93,15 +97,7 @@ mod test {
         let board = BoardRep::from_fen(
             "rnbqk2r/pppp1ppp/4pn2/8/2PP4/P1P5/4PPPP/R1BQKBNR b KQkq - 0 1".into(),
         );
-        let m = Move::new(
-            index_from_coords("a1"),
-            index_from_coords("b2"),
-            0b0,
-            PieceType::Rook,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("a1"), index_from_coords("b2"), 0b0);
 
         assert!(!is_legal_rook_move(m, board))
     }
@@ -111,15 +107,7 @@ mod test {
         let board = BoardRep::from_fen(
             "rnbqk2r/pppp1ppp/4pn2/8/2PP4/P1P5/4PPPP/R1BQKBNR b KQkq - 0 1".into(),
         );
-        let m = Move::new(
-            index_from_coords("a1"),
-            index_from_coords("b1"),
-            0b0,
-            PieceType::Rook,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("a1"), index_from_coords("b1"), 0b0);
 
         assert!(is_legal_rook_move(m, board))
     }
@@ -128,15 +116,7 @@ mod test {
     fn is_legal_rook_move_scenario_0() {
         let board =
             BoardRep::from_fen("rn3b1r/ppp2kpp/3p4/8/P3P1nP/4q3/4K3/4R1NR w - - 6 21".into());
-        let m = Move::new(
-            index_from_coords("e1"),
-            index_from_coords("e3"),
-            MF_CAPTURE,
-            PieceType::Rook,
-            false,
-            0,
-            0,
-        );
+        let m = Move::new(index_from_coords("e1"), index_from_coords("e3"), MF_CAPTURE);
 
         assert!(!is_legal_rook_move(m, board))
     }
@@ -15,10 +15,12 @@ use super::*;
 pub fn startpos_move_generation() {
     let board = BoardRep::default();
     let white_king_analysis = board.get_white_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &white_king_analysis,
         &board.get_black_king_analysis(),
         board,
+        &mut moves,
     );
     assert_eq!(moves.len(), 20);
 }
@@ -28,10 +30,12 @@ pub fn king_double_checked() {
     let board =
         BoardRep::from_fen("rnbqk1nr/pppp1pNp/2Pb4/8/1B6/4Q3/PP1PPPPP/RN2KB1R b KQkq - 0 1".into());
     let black_king_analysis = board.get_black_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &black_king_analysis,
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
     assert!(moves.len() <= 2);
 }
@@ -43,10 +47,12 @@ pub fn move_generation_capture_the_threat_with_knight_or_move_the_king() {
     );
 
     let black_king_analysis = board.get_black_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &black_king_analysis,
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
 
     assert_eq!(moves.len(), 4);
@@ -59,10 +65,12 @@ pub fn move_generation_capture_the_threat_with_bishop_or_move_the_king() {
     );
 
     let black_king_analysis = board.get_black_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &black_king_analysis,
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
 
     assert_eq!(moves.len(), 2);
@@ -74,10 +82,12 @@ pub fn move_generation_capture_the_threat_with_rook_to_avoid_smother() {
         "3nkb1r/p1Npqp2/4pnp1/1b1PN3/1p2P3/5Q1p/PPrBBPPP/R3K2R b KQk - 0 2".into(),
     );
     let black_king_analysis = board.get_black_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &black_king_analysis,
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
 
     assert_eq!(moves.len(), 1);
@@ -89,10 +99,12 @@ pub fn move_generation_block_threat_with_bishop() {
         "r3kb2/pp3ppp/2n2n1r/1Bpp4/4b3/2N1PP2/PPPP2PP/R1B1q1KR w q - 0 11".into(),
     );
     let white_king_analysis = board.get_white_king_analysis();
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &white_king_analysis,
         &board.get_black_king_analysis(),
         board,
+        &mut moves,
     );
 
     assert_eq!(moves.len(), 1);
@@ -103,10 +115,12 @@ pub fn move_generation_capture_the_threat_with_pawn_to_avoid_smother() {
     let board = BoardRep::from_fen(
         "3nkb1r/p1pbnp2/3Np1p1/q3N3/1p2P3/2q2Q1p/PPPBBPPP/R3K2R b KQk - 0 2".into(),
     );
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &board.get_black_king_analysis(),
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
 
     assert_eq!(moves.len(), 1);
@@ -117,10 +131,12 @@ pub fn move_generation_block_with_pawn_or_move_king() {
     let board = BoardRep::from_fen(
         "r3kb2/pp3ppp/2n2n1r/1Bpp4/3qb3/2N2P2/PPPPP1PP/R1B3K1 w q - 0 11".into(),
     );
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &board.get_white_king_analysis(),
         &board.get_black_king_analysis(),
         board,
+        &mut moves,
     );
     assert_eq!(moves.len(), 3);
 }
@@ -130,10 +146,12 @@ pub fn move_generation_block_or_capture_with_bishop() {
     let board = BoardRep::from_fen(
         "r3k2R/p1ppqpb1/bn2pn2/3PN1p1/1p2P3/2N5/PPPBBPPP/R3K3 b Qq - 0 2".into(),
     );
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &board.get_black_king_analysis(),
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
     assert_eq!(moves.len(), 4);
 }
@@ -146,13 +164,15 @@ pub fn pawn_move_gen_threatened_block_with_double_pawn_push() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{:?}", moves);
     assert_eq!(moves.len(), 1);
@@ -164,13 +184,15 @@ pub fn pawn_move_gen_threatened_take_ep() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("b5"),
         &board.get_white_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{:?}", moves);
     assert_eq!(moves.len(), 1);
@@ -182,13 +204,15 @@ pub fn pawn_move_gen_threatened_take_threat() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d6"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{:?}", moves);
     assert_eq!(moves.len(), 2);
@@ -200,20 +224,14 @@ pub fn move_generation_scenario_pawn_wrap_around_king_threat() {
         "r4rk1/p1ppqpb1/bn2pnp1/P2PN3/1p2P3/2N2Q1p/1PPBBPPP/R3K2R b KQ - 0 2".into(),
     );
 
-    let moves = generate_moves(
+    let mut moves = MoveList::new();
+    generate_moves(
         &board.get_black_king_analysis(),
         &board.get_white_king_analysis(),
         board,
+        &mut moves,
     );
-    assert!(moves.contains(&Move::new(
-        index_from_coords("g8"),
-        index_from_coords("h7"),
-        0b0,
-        piece_type::PieceType::King,
-        true,
-        0,
-        0
-    )));
+    assert!(moves.iter().any(|s| s.m == Move::new(index_from_coords("g8"), index_from_coords("h7"), 0b0)));
 }
 
 #[test]
@@ -222,17 +240,10 @@ pub fn generate_moves_queen_check_bishop_can_capture() {
         "rnbqkbnr/pp4pp/2p1Qp2/3pp3/2B1P2P/8/PPPP1PP1/RNB1K1NR b KQkq - 1 5".into(),
     );
 
-    let moves = generate_moves_for_board(board);
+    let mut moves = MoveList::new();
+    generate_moves_for_board(board, &mut moves);
     assert_eq!(moves.len(), 4);
-    assert!(moves.contains(&Move::new(
-        index_from_coords("c8"),
-        index_from_coords("e6"),
-        MF_CAPTURE,
-        PieceType::Bishop,
-        true,
-        PIECE_TYPE_EXCHANGE_VALUE[5],
-        0
-    )));
+    assert!(moves.iter().any(|s| s.m == Move::new(index_from_coords("c8"), index_from_coords("e6"), MF_CAPTURE)));
 }
 
 #[test]
@@ -242,13 +253,15 @@ pub fn generate_pawn_moves_when_pinned_bishop() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     assert_eq!(pawn_moves.len(), 0);
 }
@@ -261,16 +274,18 @@ pub fn generate_pawn_moves_when_pinned_bishop_that_can_be_captured() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     assert_eq!(pawn_moves.len(), 1);
-    assert_eq!(pawn_moves[0].to(), index_from_coords("c6"));
+    assert_eq!(pawn_moves[0].m.to(), index_from_coords("c6"));
 }
 
 #[test]
@@ -281,13 +296,15 @@ pub fn generate_pawn_moves_when_pinned_on_e_file() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     assert_eq!(pawn_moves.len(), 2);
 }
@@ -300,13 +317,15 @@ pub fn generate_pawn_moves_pinned_and_threatened_where_threat_captureable_no_leg
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     assert_eq!(pawn_moves.len(), 0);
 }
@@ -317,13 +336,15 @@ pub fn generate_pawn_moves_pinned_and_right_ep_capture_available_disallow_the_ca
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f4"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     println!("{pawn_moves:?}");
     assert_eq!(pawn_moves.len(), 1);
@@ -335,13 +356,15 @@ pub fn generate_pawn_moves_pinned_and_left_ep_capture_available_disallow_the_cap
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let pawn_moves = generate_index_moves(
+    let mut pawn_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f4"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut pawn_moves,
     );
     println!("{pawn_moves:?}");
     assert_eq!(pawn_moves.len(), 1);
@@ -354,13 +377,15 @@ pub fn generate_knight_moves_when_king_threatened_can_not_block_or_capture() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let knight_moves = generate_index_moves(
+    let mut knight_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("g8"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut knight_moves,
     );
     assert_eq!(knight_moves.len(), 0);
 }
@@ -372,13 +397,15 @@ pub fn generate_knight_moves_when_king_threatened_can_block_or_capture() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let knight_moves = generate_index_moves(
+    let mut knight_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f4"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut knight_moves,
     );
     assert_eq!(knight_moves.len(), 2);
 }
@@ -390,13 +417,15 @@ pub fn generate_knight_moves_when_king_threatened_can_only_block() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let knight_moves = generate_index_moves(
+    let mut knight_moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("g5"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut knight_moves,
     );
     assert_eq!(knight_moves.len(), 1);
 }
@@ -409,13 +438,15 @@ pub fn generate_knight_moves_when_knight_pinned_no_moves() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d5"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 0);
@@ -428,13 +459,15 @@ pub fn generate_bishop_moves_when_bishop_pinned_diagonally_should_include_captur
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e6"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 3);
@@ -448,13 +481,15 @@ pub fn generate_bishop_moves_when_bishop_pinned_diagonally_should_include_captur
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d5"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 4);
@@ -467,13 +502,15 @@ pub fn generate_bishop_moves_when_bishop_pinned_orthogonally_should_return_0() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f5"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 0);
@@ -487,13 +524,15 @@ pub fn generate_rook_moves_when_rook_pinned_orthogonally_should_include_capture_
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e5"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 4);
@@ -506,13 +545,15 @@ pub fn generate_rook_moves_when_rook_pinned_diagonally_should_return_none() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f7"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 0);
@@ -525,13 +566,15 @@ pub fn generate_queen_moves_when_pinned_orthogonally() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e3"),
         &board.get_white_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 5);
@@ -545,13 +588,15 @@ pub fn generate_queen_moves_when_pinned_and_king_threatened_by_capturable_piece(
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("d2"),
         &board.get_black_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 0);
@@ -564,13 +609,15 @@ pub fn generate_queen_moves_when_pinned_orthogonally_and_forked_with_knight() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e3"),
         &board.get_white_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 0);
@@ -583,13 +630,15 @@ pub fn generate_queen_moves_when_pinned_diagonally() {
 
     let mut ad_table = AttackAndDefendTable::new();
 
-    let moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("f4"),
         &board.get_white_king_analysis(),
         &Vec::new(),
         0,
+        &mut moves,
     );
     println!("{moves:?}");
     assert_eq!(moves.len(), 3);
@@ -603,19 +652,21 @@ pub fn generate_sliding_move_when_piece_has_reveal_check_should_ignore_defender_
 
     let reveal_attacks = board.get_black_king_analysis().pins;
 
-    let mut moves = generate_index_moves(
+    let mut moves = MoveList::new();
+    generate_index_moves(
         board,
         &mut ad_table,
         index_from_coords("e5"),
         &board.get_white_king_analysis(),
         &reveal_attacks,
         0,
+        &mut moves,
     );
 
     moves.sort();
     println!("{moves:?}");
-    assert_eq!(moves[0].to(), index_from_coords("e6"));
-    assert_eq!(moves[0].see(), PIECE_TYPE_EXCHANGE_VALUE[3]);
+    assert_eq!(moves[0].m.to(), index_from_coords("e6"));
+    assert_eq!(moves[0].see, PIECE_TYPE_EXCHANGE_VALUE[3]);
 }
 
 #[test]
@@ -623,7 +674,8 @@ pub fn generate_moves_king_in_check_no_blockers_only_retreat_to_f3() {
     let board =
         BoardRep::from_fen("1nb1kbnr/pp1rpppp/8/2p5/4PP2/8/PPPqK1PP/R4BNR w k - 0 1".into());
 
-    let moves = generate_moves_for_board(board);
+    let mut moves = MoveList::new();
+    generate_moves_for_board(board, &mut moves);
     assert_eq!(moves.len(), 1);
-    assert_eq!(moves[0].to(), index_from_coords("f3"));
+    assert_eq!(moves[0].m.to(), index_from_coords("f3"));
 }
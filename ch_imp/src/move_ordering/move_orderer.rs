@@ -1,24 +1,28 @@
-use std::{cell::OnceCell, sync::Mutex};
-
-use crate::{board::position::Position, r#move::Move, shared::cache::MovesCache};
+use crate::{
+    board::position::Position,
+    r#move::{move_list::MoveList, Move},
+};
 
 use super::killer_store::PlyKillers;
 
-lazy_static! {
-    pub static ref MOVE_CACHE: Mutex<MovesCache> = Mutex::<MovesCache>::new(MovesCache::new());
-}
-
-pub struct MoveOrderer {
+pub struct MoveOrderer<'a> {
     index: usize,
     principal_variation: Option<Move>,
     hash_move: Option<Move>,
     ply_killers: PlyKillers,
     position: Position,
-    moves: OnceCell<Vec<Move>>,
+    moves: &'a MoveList,
 }
 
-impl MoveOrderer {
-    pub fn new(pv: Option<&Move>, hm: Option<Move>, position: Position, ply_killers: PlyKillers) -> Self {
+impl<'a> MoveOrderer<'a> {
+    // `moves` are the legal moves of the position, already in generation order
+    pub fn new(
+        pv: Option<&Move>,
+        hm: Option<Move>,
+        position: Position,
+        ply_killers: PlyKillers,
+        moves: &'a MoveList,
+    ) -> Self {
         Self {
             index: 0,
             principal_variation: match pv {
@@ -28,19 +32,12 @@ impl MoveOrderer {
             hash_move: hm,
             ply_killers,
             position: position,
-            moves: OnceCell::new(),
+            moves,
         }
     }
-
-    fn get_moves(&mut self) -> &Vec<Move> {
-        let moves = self
-            .moves
-            .get_or_init(|| MOVE_CACHE.lock().unwrap().get_moves(self.position.board));
-        moves
-    }
 }
 
-impl Iterator for MoveOrderer {
+impl Iterator for MoveOrderer<'_> {
     type Item = Move;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -86,16 +83,16 @@ impl Iterator for MoveOrderer {
                     None => None
                 }
             }
-            // Not the PV or HM so lets generate the moves (if we haven't already) and find the next move that isn't the PV or HM
+            // Not the PV or HM so find the next generated move that isn't the PV or HM
             else {
                 let arr_pos = self.index - 5;
-                let moves = self.get_moves();
+                let moves = self.moves;
 
                 if arr_pos >= moves.len() {
                     break;
                 }
 
-                result = Some(moves[arr_pos]);
+                result = Some(moves[arr_pos].m);
 
                 // Don't repeatedly check the PV or HM
                 if result == self.principal_variation || result == self.hash_move {
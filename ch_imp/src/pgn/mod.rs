@@ -0,0 +1,171 @@
+use std::{error::Error, fmt::Display, time::Duration};
+
+use crate::{
+    board::fen::FenError,
+    engine::san::SanError,
+    match_state::game_state::{GameState, MatchResultState},
+    r#move::Move,
+};
+
+pub mod reader;
+pub mod writer;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    InvalidTag(String),
+    UnterminatedComment,
+    UnterminatedVariation,
+    UnexpectedToken(String),
+    InvalidFen(FenError),
+    InvalidMove(SanError),
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidTag(s) => write!(f, "Malformed tag pair '{s}'"),
+            PgnError::UnterminatedComment => write!(f, "Comment is missing its closing brace"),
+            PgnError::UnterminatedVariation => write!(f, "Variation is missing its closing bracket"),
+            PgnError::UnexpectedToken(s) => write!(f, "Unexpected '{s}' in movetext"),
+            PgnError::InvalidFen(e) => write!(f, "Invalid FEN tag: {e}"),
+            PgnError::InvalidMove(e) => write!(f, "Invalid move: {e}"),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+// Engine evaluation from white's point of view, as written in a [%eval] comment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PgnEval {
+    Centipawns(i32),
+    // Moves until mate, negative when black is the one mating
+    MateIn(i32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub m: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    // Time left on the mover's clock after the move
+    pub clock: Option<Duration>,
+    pub eval: Option<PgnEval>,
+    // Alternatives to this move, each starting from the position before it
+    pub variations: Vec<PgnLine>,
+}
+
+impl PgnMove {
+    pub fn new(game_state: &GameState, m: Move) -> Self {
+        Self {
+            m,
+            san: game_state.to_san(m),
+            nags: Vec::new(),
+            comments: Vec::new(),
+            clock: None,
+            eval: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+// A sequence of moves, with any comments that come before its first move
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnLine {
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    // In the order they were read or set
+    pub tags: Vec<(String, String)>,
+    pub mainline: PgnLine,
+    pub result: MatchResultState,
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            mainline: PgnLine::default(),
+            result: MatchResultState::Active,
+        }
+    }
+}
+
+impl PgnGame {
+    // A game without variations or annotations, the FEN tag is only set when it doesn't start from the usual position
+    pub fn from_moves(start: GameState, moves: &[Move]) -> Self {
+        let mut game = Self::default();
+        let fen = start.to_fen();
+        if fen != START_FEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+
+        let mut game_state = start;
+        let mut previous_keys = Vec::new();
+        for &m in moves {
+            game.mainline.moves.push(PgnMove::new(&game_state, m));
+            previous_keys.push(game_state.position.board.zorb_key);
+            game_state = game_state.make(m);
+        }
+        game.result = game_state.result_state(&previous_keys);
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.into(),
+            None => self.tags.push((name.into(), value.into())),
+        }
+    }
+
+    pub fn start_state(&self) -> Result<GameState, PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => GameState::try_new(fen).map_err(PgnError::InvalidFen),
+            None => Ok(GameState::default()),
+        }
+    }
+
+    // The position after the last mainline move
+    pub fn end_state(&self) -> Result<GameState, PgnError> {
+        Ok(self
+            .mainline
+            .moves
+            .iter()
+            .fold(self.start_state()?, |game_state, pgn_move| {
+                game_state.make(pgn_move.m)
+            }))
+    }
+}
+
+pub(crate) fn result_str(result: MatchResultState) -> &'static str {
+    match result {
+        MatchResultState::Active => "*",
+        MatchResultState::Draw => "1/2-1/2",
+        MatchResultState::WhiteVictory => "1-0",
+        MatchResultState::BlackVictory => "0-1",
+    }
+}
+
+pub(crate) fn result_from_str(s: &str) -> Option<MatchResultState> {
+    match s {
+        "*" => Some(MatchResultState::Active),
+        "1/2-1/2" => Some(MatchResultState::Draw),
+        "1-0" => Some(MatchResultState::WhiteVictory),
+        "0-1" => Some(MatchResultState::BlackVictory),
+        _ => None,
+    }
+}
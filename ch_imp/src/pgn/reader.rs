@@ -0,0 +1,446 @@
+use std::{iter::Peekable, str::Chars, time::Duration};
+
+use crate::match_state::game_state::{GameState, MatchResultState};
+
+use super::{result_from_str, PgnError, PgnEval, PgnGame, PgnLine, PgnMove};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    OpenVariation,
+    CloseVariation,
+    Result(MatchResultState),
+    // A move, possibly with a suffix annotation like !?
+    San(String),
+}
+
+// Reads every game in `pgn`. A game ends at its result, or at the next tag pair if the result is missing
+pub fn read_pgn(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut tokens = tokenize(pgn)?.into_iter().peekable();
+    let mut games = Vec::new();
+
+    while tokens.peek().is_some() {
+        let mut game = PgnGame::default();
+        while let Some(Token::Tag(name, value)) = tokens.peek() {
+            game.set_tag(name, value);
+            tokens.next();
+        }
+
+        let start = game.start_state()?;
+        let (mainline, end) = read_line(&mut tokens, start)?;
+        if end == Some(Token::CloseVariation) {
+            return Err(PgnError::UnexpectedToken(")".into()));
+        }
+        game.mainline = mainline;
+        game.result = match end {
+            Some(Token::Result(result)) => result,
+            _ => game
+                .tag("Result")
+                .and_then(result_from_str)
+                .unwrap_or(MatchResultState::Active),
+        };
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+// Reads each game on its own so a malformed game only loses that game, for large databases where a few bad games
+// are expected. A new game starts where a tag pair follows movetext
+pub fn read_pgn_games(pgn: &str) -> Vec<Result<PgnGame, PgnError>> {
+    let mut r = Vec::new();
+    for text in split_games(pgn) {
+        match read_pgn(text) {
+            Ok(games) => r.extend(games.into_iter().map(Ok)),
+            Err(e) => r.push(Err(e)),
+        }
+    }
+    r
+}
+
+fn split_games(pgn: &str) -> Vec<&str> {
+    let mut r = Vec::new();
+    let mut start = 0;
+    let mut in_movetext = false;
+    let mut offset = 0;
+    for line in pgn.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if in_movetext {
+                r.push(&pgn[start..offset]);
+                start = offset;
+                in_movetext = false;
+            }
+        } else if !trimmed.is_empty() && !line.starts_with('%') {
+            in_movetext = true;
+        }
+        offset += line.len();
+    }
+    if !pgn[start..].trim().is_empty() {
+        r.push(&pgn[start..]);
+    }
+    r
+}
+
+// Reads moves from `start` until the line ends, returning the token that ended it. None means the tokens ran out
+// or the next game's tags were reached
+fn read_line<I: Iterator<Item = Token>>(
+    tokens: &mut Peekable<I>,
+    start: GameState,
+) -> Result<(PgnLine, Option<Token>), PgnError> {
+    let mut line = PgnLine::default();
+    let mut game_state = start;
+    let mut previous_state = start;
+
+    loop {
+        if let Some(Token::Tag(..)) = tokens.peek() {
+            return Ok((line, None));
+        }
+        let token = match tokens.next() {
+            Some(token) => token,
+            None => return Ok((line, None)),
+        };
+
+        match token {
+            Token::San(san) => {
+                let (san, nag) = split_suffix_annotation(&san);
+                let m = game_state.parse_san(san).map_err(PgnError::InvalidMove)?;
+                let mut pgn_move = PgnMove::new(&game_state, m);
+                pgn_move.nags.extend(nag);
+                line.moves.push(pgn_move);
+                previous_state = game_state;
+                game_state = game_state.make(m);
+            }
+            Token::Comment(comment) => match line.moves.last_mut() {
+                Some(pgn_move) => add_move_comment(pgn_move, &comment),
+                None => line.comments.push(comment),
+            },
+            Token::Nag(nag) => match line.moves.last_mut() {
+                Some(pgn_move) => pgn_move.nags.push(nag),
+                None => return Err(PgnError::UnexpectedToken(format!("${nag}"))),
+            },
+            Token::OpenVariation => {
+                if line.moves.is_empty() {
+                    return Err(PgnError::UnexpectedToken("(".into()));
+                }
+                let (variation, end) = read_line(tokens, previous_state)?;
+                if end != Some(Token::CloseVariation) {
+                    return Err(PgnError::UnterminatedVariation);
+                }
+                line.moves.last_mut().unwrap().variations.push(variation);
+            }
+            Token::CloseVariation | Token::Result(_) => return Ok((line, Some(token))),
+            Token::Tag(..) => unreachable!(),
+        }
+    }
+}
+
+// Traditional suffix annotations are stored as their equivalent NAGs
+fn split_suffix_annotation(san: &str) -> (&str, Option<u8>) {
+    let move_end = san.trim_end_matches(['!', '?']).len();
+    let nag = match &san[move_end..] {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    };
+    (&san[..move_end], nag)
+}
+
+// Pulls [%clk] and [%eval] commands out of the comment, anything left over is kept as text
+fn add_move_comment(pgn_move: &mut PgnMove, comment: &str) {
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        text.push_str(&rest[..start]);
+        let mut command = rest[start + 2..end].split_whitespace();
+        match (command.next(), command.next()) {
+            (Some("clk"), Some(clock)) => pgn_move.clock = parse_clock(clock),
+            (Some("eval"), Some(eval)) => pgn_move.eval = parse_eval(eval),
+            _ => text.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = collapse_whitespace(&text);
+    if !text.is_empty() {
+        pgn_move.comments.push(text);
+    }
+}
+
+// Line breaks inside comments are only there for wrapping
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// h:mm:ss with optional fractions of a second
+fn parse_clock(clock: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    for part in clock.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    (seconds >= 0.0).then(|| Duration::from_millis((seconds * 1000.0).round() as u64))
+}
+
+fn parse_eval(eval: &str) -> Option<PgnEval> {
+    match eval.strip_prefix('#') {
+        Some(mate) => mate.parse::<i32>().ok().map(PgnEval::MateIn),
+        None => eval
+            .parse::<f64>()
+            .ok()
+            .map(|pawns| PgnEval::Centipawns((pawns * 100.0).round() as i32)),
+    }
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        // A % in the first column escapes the rest of the line
+        if c == '%' && line_start {
+            skip_line(&mut chars);
+            continue;
+        }
+        line_start = c == '\n';
+
+        match c {
+            c if c.is_whitespace() || c == '.' => {}
+            '[' => tokens.push(read_tag(&mut chars)?),
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(Token::Comment(collapse_whitespace(&comment)));
+            }
+            ';' => {
+                let comment = skip_line(&mut chars);
+                tokens.push(Token::Comment(collapse_whitespace(&comment)));
+            }
+            '(' => tokens.push(Token::OpenVariation),
+            ')' => tokens.push(Token::CloseVariation),
+            '*' => tokens.push(Token::Result(MatchResultState::Active)),
+            '$' => {
+                let digits = take_while(&mut chars, |c| c.is_ascii_digit());
+                let nag = digits
+                    .parse::<u8>()
+                    .map_err(|_| PgnError::UnexpectedToken(format!("${digits}")))?;
+                tokens.push(Token::Nag(nag));
+            }
+            c if c.is_ascii_alphanumeric() => {
+                let symbol = format!("{c}{}", take_while(&mut chars, is_symbol_char));
+                if let Some(result) = result_from_str(&symbol) {
+                    tokens.push(Token::Result(result));
+                } else if !symbol.bytes().all(|b| b.is_ascii_digit()) {
+                    // Anything that isn't a move number
+                    tokens.push(Token::San(symbol));
+                }
+            }
+            c => return Err(PgnError::UnexpectedToken(c.to_string())),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/!?".contains(c)
+}
+
+fn read_tag(chars: &mut Peekable<Chars>) -> Result<Token, PgnError> {
+    let mut raw = String::from("[");
+    let mut in_value = false;
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            None => return Err(PgnError::InvalidTag(raw)),
+        };
+        raw.push(c);
+        match c {
+            '\\' if in_value => raw.extend(chars.next()),
+            '"' => in_value = !in_value,
+            ']' if !in_value => break,
+            _ => {}
+        }
+    }
+    parse_tag(&raw).ok_or(PgnError::InvalidTag(raw))
+}
+
+// [Name "value"], where the value can escape quotes and backslashes with a backslash
+fn parse_tag(raw: &str) -> Option<Token> {
+    let inner = raw.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, rest) = inner.split_at(inner.find(|c: char| !is_symbol_char(c))?);
+    let quoted = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        value.push(if c == '\\' { chars.next()? } else { c });
+    }
+    (!name.is_empty()).then(|| Token::Tag(name.into(), value))
+}
+
+// Returns the skipped text, leaving the newline to be read
+fn skip_line(chars: &mut Peekable<Chars>) -> String {
+    take_while(chars, |c| c != '\n')
+}
+
+fn take_while(chars: &mut Peekable<Chars>, f: impl Fn(char) -> bool) -> String {
+    let mut r = String::new();
+    while let Some(&c) = chars.peek() {
+        if !f(c) {
+            break;
+        }
+        r.push(c);
+        chars.next();
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        match_state::game_state::MatchResultState,
+        pgn::{PgnError, PgnEval},
+    };
+
+    use super::{read_pgn, read_pgn_games};
+
+    const EVERGREEN: &str = r#"[Event "Casual Game"]
+[Site "Berlin GER"]
+[Date "1852.??.??"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Jean Dufresne"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.b4 Bxb4 5.c3 Ba5 6.d4 exd4 7.O-O d3 8.Qb3 Qf6
+9.e5 Qg6 10.Re1 Nge7 11.Ba3 b5 12.Qxb5 Rb8 13.Qa4 Bb6 14.Nbd2 Bb7 15.Ne4 Qf5
+16.Bxd3 Qh5 17.Nf6+ gxf6 18.exf6 Rg8 19.Rad1 Qxf3 20.Rxe7+ Nxe7 21.Qxd7+ Kxd7
+22.Bf5+ Ke8 23.Bd7+ Kf8 24.Bxe7# 1-0
+"#;
+
+    const ANNOTATED: &str = r#"[Event "Annotated"]
+[Result "*"]
+% This line is escaped
+{Before the first move} 1. e4 $1 {Best by test} (1. d4 d5 (1... Nf6 2. c4) 2. c4
+{The Queen's   Gambit}) 1... e5!? 2. Nf3 {[%eval 0.25] [%clk 0:01:30.5] Developing} Nc6
+; The rest of this line is a comment
+3. Bb5 {[%eval #-3]} *
+
+[Event "From a position"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 1/2-1/2
+"#;
+
+    #[test]
+    pub fn read_pgn_replays_a_full_game() {
+        let games = read_pgn(EVERGREEN).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(game.tag("Date"), Some("1852.??.??"));
+        assert_eq!(game.result, MatchResultState::WhiteVictory);
+        assert_eq!(game.mainline.moves.len(), 47);
+        assert_eq!(game.mainline.moves[12].san, "O-O");
+        assert_eq!(game.mainline.moves[46].san, "Bxe7#");
+        assert_eq!(
+            game.end_state().unwrap().to_fen(),
+            "1r3kr1/pbpBBp1p/1b3P2/8/8/2P2q2/P4PPP/3R2K1 b - - 0 24"
+        );
+    }
+
+    #[test]
+    pub fn read_pgn_keeps_comments_nags_and_variations() {
+        let games = read_pgn(ANNOTATED).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        assert_eq!(game.result, MatchResultState::Active);
+        assert_eq!(game.mainline.comments, vec!["Before the first move"]);
+        let moves = &game.mainline.moves;
+        let sans: Vec<&str> = moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+
+        assert_eq!(moves[0].nags, vec![1]);
+        assert_eq!(moves[0].comments, vec!["Best by test"]);
+        assert_eq!(moves[0].variations.len(), 1);
+        let variation = &moves[0].variations[0];
+        let sans: Vec<&str> = variation.moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, vec!["d4", "d5", "c4"]);
+        assert_eq!(variation.moves[2].comments, vec!["The Queen's Gambit"]);
+        assert_eq!(variation.moves[1].variations[0].moves[0].san, "Nf6");
+        assert_eq!(variation.moves[1].variations[0].moves[1].san, "c4");
+
+        assert_eq!(moves[1].nags, vec![5]);
+        assert_eq!(moves[2].comments, vec!["Developing"]);
+        assert_eq!(moves[2].eval, Some(PgnEval::Centipawns(25)));
+        assert_eq!(moves[2].clock, Some(Duration::from_millis(90_500)));
+        assert_eq!(moves[3].comments, vec!["The rest of this line is a comment"]);
+        assert_eq!(moves[4].eval, Some(PgnEval::MateIn(-3)));
+        assert!(moves[4].comments.is_empty());
+
+        let game = &games[1];
+        assert_eq!(game.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert_eq!(game.result, MatchResultState::Draw);
+        assert_eq!(game.mainline.moves[1].san, "Kd7");
+    }
+
+    #[test]
+    pub fn read_pgn_reports_bad_input() {
+        let cases = [
+            ("1. e4 {unclosed", PgnError::UnterminatedComment),
+            ("1. e4 (1. d4", PgnError::UnterminatedVariation),
+            ("1. e4 ) *", PgnError::UnexpectedToken(")".into())),
+            ("( 1. e4 ) *", PgnError::UnexpectedToken("(".into())),
+            ("[Event Casual]\n1. e4 *", PgnError::InvalidTag("[Event Casual]".into())),
+            ("1. e4 & *", PgnError::UnexpectedToken("&".into())),
+        ];
+        for (pgn, expected) in cases {
+            assert_eq!(read_pgn(pgn), Err(expected), "{pgn}");
+        }
+
+        assert!(matches!(read_pgn("1. e5 *"), Err(PgnError::InvalidMove(_))));
+        assert!(matches!(read_pgn(&format!("1. e5 *\n\n{EVERGREEN}")), Err(PgnError::InvalidMove(_))));
+        assert!(matches!(
+            read_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*"),
+            Err(PgnError::InvalidFen(_))
+        ));
+    }
+
+    #[test]
+    pub fn read_pgn_games_keeps_the_games_around_a_bad_one() {
+        let pgn = format!("{EVERGREEN}\n[Event \"Bad\"]\n\n1. e4 & *\n\n[Event \"Illegal\"]\n1. e5 *\n{ANNOTATED}");
+        let games = read_pgn_games(&pgn);
+        assert_eq!(games.len(), 5);
+        assert_eq!(games[0].as_ref().unwrap().mainline.moves.len(), 47);
+        assert_eq!(games[1], Err(PgnError::UnexpectedToken("&".into())));
+        assert!(matches!(games[2], Err(PgnError::InvalidMove(_))));
+        assert_eq!(games[3].as_ref().unwrap().tag("Event"), Some("Annotated"));
+        assert_eq!(games[4].as_ref().unwrap().result, MatchResultState::Draw);
+
+        // Games without tags that follow each other are still read separately
+        let games = read_pgn_games("1. e4 e5 1-0 1. d4 d5 0-1\n");
+        assert_eq!(games.len(), 2);
+    }
+}
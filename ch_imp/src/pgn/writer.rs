@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use super::{result_str, PgnEval, PgnGame, PgnLine, PgnMove};
+
+const MAX_LINE_LENGTH: usize = 79;
+
+// Written first and in this order, with these values when they haven't been set
+const SEVEN_TAG_ROSTER: [(&str, &str); 6] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+];
+
+pub fn write_pgn(games: &[PgnGame]) -> String {
+    games
+        .iter()
+        .map(|game| game.to_pgn())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl PgnGame {
+    // Export format: the seven tag roster, any other tags, then the movetext wrapped to fit in 80 columns
+    pub fn to_pgn(&self) -> String {
+        let mut r = String::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            write_tag(&mut r, name, self.tag(name).unwrap_or(default));
+        }
+        write_tag(&mut r, "Result", result_str(self.result));
+        for (name, value) in &self.tags {
+            if name != "Result" && !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) {
+                write_tag(&mut r, name, value);
+            }
+        }
+        r.push('\n');
+
+        // Only the move numbers come from the start position, so a bad FEN tag still gets written out
+        let start = self.start_state().unwrap_or_default();
+        let start_ply = (start.full_moves.max(1) as usize - 1) * 2
+            + start.position.board.black_turn as usize;
+        let mut tokens = Vec::new();
+        line_tokens(&self.mainline, start_ply, &mut tokens);
+        tokens.push(result_str(self.result).into());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.chars().count() > MAX_LINE_LENGTH {
+                r.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                r.push(' ');
+                line_length += 1;
+            }
+            line_length += token.chars().count();
+            r.push_str(&token);
+        }
+        r.push('\n');
+        r
+    }
+}
+
+fn write_tag(r: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    r.push_str(&format!("[{name} \"{value}\"]\n"));
+}
+
+// Black moves only get a number at the start of a line or after a comment or variation interrupts the moves
+fn line_tokens(line: &PgnLine, start_ply: usize, tokens: &mut Vec<String>) {
+    for comment in &line.comments {
+        comment_tokens(comment, tokens);
+    }
+
+    let mut needs_number = true;
+    for (i, pgn_move) in line.moves.iter().enumerate() {
+        let ply = start_ply + i;
+        // Numbers share a token with their move so wrapping never separates them
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}. {}", ply / 2 + 1, pgn_move.san));
+        } else if needs_number {
+            tokens.push(format!("{}... {}", ply / 2 + 1, pgn_move.san));
+        } else {
+            tokens.push(pgn_move.san.clone());
+        }
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${nag}")));
+
+        let comments = move_comments(pgn_move);
+        needs_number = !comments.is_empty() || !pgn_move.variations.is_empty();
+        for comment in comments {
+            comment_tokens(&comment, tokens);
+        }
+
+        for variation in &pgn_move.variations {
+            let mut variation_tokens = Vec::new();
+            line_tokens(variation, ply, &mut variation_tokens);
+            match variation_tokens.len() {
+                0 => tokens.push("()".into()),
+                len => {
+                    variation_tokens[0].insert(0, '(');
+                    variation_tokens[len - 1].push(')');
+                    tokens.append(&mut variation_tokens);
+                }
+            }
+        }
+    }
+}
+
+// The eval and clock commands go in the first comment, ahead of its text
+fn move_comments(pgn_move: &PgnMove) -> Vec<String> {
+    let mut commands = Vec::new();
+    if let Some(eval) = pgn_move.eval {
+        commands.push(format!("[%eval {}]", format_eval(eval)));
+    }
+    if let Some(clock) = pgn_move.clock {
+        commands.push(format!("[%clk {}]", format_clock(clock)));
+    }
+
+    let mut comments = pgn_move.comments.clone();
+    if !commands.is_empty() {
+        match comments.first_mut() {
+            Some(first) => commands.push(first.clone()),
+            None => comments.push(String::new()),
+        }
+        comments[0] = commands.join(" ");
+    }
+    comments
+}
+
+// Split into words so long comments can be wrapped
+fn comment_tokens(comment: &str, tokens: &mut Vec<String>) {
+    let mut words: Vec<String> = comment.split_whitespace().map(String::from).collect();
+    match words.len() {
+        0 => tokens.push("{}".into()),
+        len => {
+            words[0].insert(0, '{');
+            words[len - 1].push('}');
+            tokens.append(&mut words);
+        }
+    }
+}
+
+fn format_eval(eval: PgnEval) -> String {
+    match eval {
+        PgnEval::Centipawns(centipawns) => format!("{:.2}", centipawns as f64 / 100.0),
+        PgnEval::MateIn(moves) => format!("#{moves}"),
+    }
+}
+
+// h:mm:ss, with tenths of a second when there are any
+fn format_clock(clock: Duration) -> String {
+    let tenths = clock.as_millis() / 100;
+    let seconds = tenths / 10;
+    let r = format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match tenths % 10 {
+        0 => r,
+        tenth => format!("{r}.{tenth}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        match_state::game_state::{GameState, MatchResultState},
+        pgn::{reader::read_pgn, PgnEval, PgnGame},
+        r#move::Move,
+    };
+
+    fn moves_from_san(game_state: GameState, sans: &[&str]) -> Vec<Move> {
+        let mut game_state = game_state;
+        sans.iter()
+            .map(|san| {
+                let m = game_state.parse_san(san).unwrap();
+                game_state = game_state.make(m);
+                m
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn to_pgn_writes_export_format() {
+        let start = GameState::default();
+        let moves = moves_from_san(start, &["e4", "e5", "Nf3", "Nc6"]);
+        let mut game = PgnGame::from_moves(start, &moves);
+        game.set_tag("White", "chimp");
+        game.set_tag("Black", "Say \"hi\" \\ bye");
+        game.mainline.moves[1].eval = Some(PgnEval::Centipawns(-30));
+        game.mainline.moves[1].clock = Some(Duration::from_secs(300));
+        game.mainline.moves[2].comments.push("Developing".into());
+        game.mainline.moves[2].nags.push(1);
+
+        assert_eq!(
+            game.to_pgn(),
+            concat!(
+                "[Event \"?\"]\n",
+                "[Site \"?\"]\n",
+                "[Date \"????.??.??\"]\n",
+                "[Round \"?\"]\n",
+                "[White \"chimp\"]\n",
+                "[Black \"Say \\\"hi\\\" \\\\ bye\"]\n",
+                "[Result \"*\"]\n",
+                "\n",
+                "1. e4 e5 {[%eval -0.30] [%clk 0:05:00]} 2. Nf3 $1 {Developing} 2... Nc6 *\n",
+            )
+        );
+
+        let start = GameState::new("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1".into());
+        let mut game = PgnGame::from_moves(start, &moves_from_san(start, &["Kd7", "e4"]));
+        game.result = MatchResultState::Draw;
+        assert!(game.to_pgn().ends_with(
+            "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1... Kd7 2. e4 1/2-1/2\n"
+        ));
+    }
+
+    #[test]
+    pub fn to_pgn_round_trips_and_wraps_long_lines() {
+        let pgn = concat!(
+            "[Event \"Round trip\"]\n[Result \"1-0\"]\n\n",
+            "{A long comment before the first move, long enough that it has to be wrapped over more than one line} ",
+            "1. e4 (1. d4 d5 (1... Nf6 2. c4 {[%clk 1:02:03.4]}) 2. c4) 1... e5 2. Nf3 $3 $14 Nc6 ",
+            "3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 ",
+            "{[%eval #4] [%clk 0:00:09]} 11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 1-0\n",
+        );
+        let games = read_pgn(pgn).unwrap();
+        let written = games[0].to_pgn();
+        assert!(written.lines().all(|line| line.chars().count() <= 79), "{written}");
+        assert!(written.lines().count() > 10);
+        // Reading back only adds the default seven tag roster values
+        let reread = read_pgn(&written).unwrap();
+        assert_eq!(reread[0].mainline, games[0].mainline);
+        assert_eq!(reread[0].result, games[0].result);
+        assert_eq!(reread[0].to_pgn(), written);
+
+        let both = super::write_pgn(&[reread[0].clone(), PgnGame::from_moves(GameState::default(), &[])]);
+        let games = read_pgn(&both).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(super::write_pgn(&games), both);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod polyglot;
+pub mod polyglot_random;
 pub mod zorb_set;
 pub mod zorb_set_precomputed;
\ No newline at end of file
@@ -0,0 +1,83 @@
+use crate::{
+    board::board_rep::BoardRep,
+    shared::{
+        board_utils::{get_file, get_index_from_file_and_rank},
+        piece_type::{Colour, PieceType},
+    },
+};
+
+use super::polyglot_random::POLYGLOT_RANDOM;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+// Key of `board` in Polyglot books. Unlike ZorbSet the keys are fixed by the format, so books from other programs
+// can be read
+pub fn polyglot_key(board: &BoardRep) -> u64 {
+    let mut r = 0;
+    for index in 0..64 {
+        if let Some((piece_type, colour)) = board.piece_at(index) {
+            // Pieces go black pawn, white pawn, black knight... and squares a1, b1... h8
+            let kind = (piece_type as usize - 1) * 2 + (colour == Colour::White) as usize;
+            let square = (index / 8) as usize * 8 + get_file(index) as usize;
+            r ^= POLYGLOT_RANDOM[kind * 64 + square];
+        }
+    }
+
+    let castling = [
+        board.white_king_side_castling,
+        board.white_queen_side_castling,
+        board.black_king_side_castling,
+        board.black_queen_side_castling,
+    ];
+    for (i, _) in castling.iter().enumerate().filter(|(_, &right)| right) {
+        r ^= POLYGLOT_RANDOM[CASTLING_OFFSET + i];
+    }
+
+    // The en passant file only counts when a pawn is next to the one that just moved and could take it
+    if board.ep_index != u8::MAX {
+        let file = get_file(board.ep_index);
+        let (rank, colour) = if board.black_turn {
+            (3, Colour::Black)
+        } else {
+            (4, Colour::White)
+        };
+        let can_capture = [file.wrapping_sub(1), file + 1]
+            .into_iter()
+            .filter(|&f| f < 8)
+            .any(|f| board.piece_at(get_index_from_file_and_rank(f, rank)) == Some((PieceType::Pawn, colour)));
+        if can_capture {
+            r ^= POLYGLOT_RANDOM[EN_PASSANT_OFFSET + file as usize];
+        }
+    }
+
+    if !board.black_turn {
+        r ^= POLYGLOT_RANDOM[TURN_OFFSET];
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::polyglot_key;
+    use crate::board::board_rep::BoardRep;
+
+    #[test]
+    pub fn polyglot_key_matches_the_reference_values() {
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", 0x463b96181691fc9c),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3", 0x823c9b50fd114196),
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6", 0x0756b94461c50fb0),
+            ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq -", 0x662fafb965db29d4),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6", 0x22a48b5a8e47ff78),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq -", 0x652a607ca3f242c1),
+            ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - -", 0x00fdd303c946bdd9),
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq -", 0x5c3f9b829b279560),
+        ];
+        for (fen, key) in cases {
+            assert_eq!(polyglot_key(&BoardRep::from_fen(fen.into())), key, "{fen}");
+        }
+    }
+}
@@ -1,9 +1,7 @@
-use rand::Rng;
-
 use crate::{
     board::{position::{MoveSegmentArray, Position}, bitboard::Bitboard, board_rep::BoardRep},
     r#move::move_segment::{MoveSegment, MoveSegmentType},
-    shared::{board_utils::get_file, piece_type::PieceType},
+    shared::{board_utils::get_file, piece_type::PieceType, seeded_rng::SeededRng},
 };
 
 const WHITE_PAWN_ID: usize = 0;
@@ -30,27 +28,34 @@ pub struct ZorbSet {
     pub bqc: u64,
 }
 
+// Seed used when generating a fresh set of keys
+pub const ZORB_SET_SEED: u64 = 0x5a0b_c0de_0000_0001;
+
 // https://en.wikipedia.org/wiki/Zobrist_hashing
 impl ZorbSet {
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
+        Self::from_seed(ZORB_SET_SEED)
+    }
+
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = SeededRng::new(seed);
 
         let mut arr = [[0; 12]; 64];
         for i in 0..64 {
             for j in 0..12 {
-                arr[i][j] = rng.gen();
+                arr[i][j] = rng.next_u64();
             }
         }
 
-        let mut ep_table: [u64; 8] = [rng.gen(); 8];
+        let mut ep_table: [u64; 8] = [0; 8];
         for i in 0..8 {
-            ep_table[i] = rng.gen();
+            ep_table[i] = rng.next_u64();
         }
-        let black_turn = rng.gen();
-        let wkc = rng.gen();
-        let wqc = rng.gen();
-        let bkc = rng.gen();
-        let bqc = rng.gen();
+        let black_turn = rng.next_u64();
+        let wkc = rng.next_u64();
+        let wqc = rng.next_u64();
+        let bkc = rng.next_u64();
+        let bqc = rng.next_u64();
         Self {
             table: arr,
             black_turn,
@@ -116,23 +121,6 @@ impl ZorbSet {
                     + if move_segment.black_piece { 1 } else { 0 };
                 zorb ^ self.table[move_segment.index as usize][piece_zorb_id]
             }
-            MoveSegmentType::ClearCastling => {
-                if move_segment.black_piece {
-                    match move_segment.index {
-                        56 => zorb ^ self.bkc,
-                        63 => zorb ^ self.bqc,
-                        59 => zorb ^ self.bkc ^ self.bqc,
-                        _ => zorb,
-                    }
-                } else {
-                    match move_segment.index {
-                        0 => zorb ^ self.wkc,
-                        7 => zorb ^ self.wqc,
-                        3 => zorb ^ self.wkc ^ self.wqc,
-                        _ => zorb,
-                    }
-                }
-            }
             MoveSegmentType::DoublePawnPush => {
                 zorb ^ self.ep_table[get_file(move_segment.index) as usize]
             }
@@ -156,9 +144,44 @@ impl ZorbSet {
         output
     }
 
+    // Castling depends on the rights still held rather than the move, so it's hashed from the rights themselves
+    pub fn castling(&self, wqc: bool, wkc: bool, bqc: bool, bkc: bool) -> u64 {
+        let mut r = 0;
+        if wqc {
+            r ^= self.wqc;
+        }
+        if wkc {
+            r ^= self.wkc;
+        }
+        if bqc {
+            r ^= self.bqc;
+        }
+        if bkc {
+            r ^= self.bkc;
+        }
+        r
+    }
+
     pub fn colour_shift(&self, zorb: u64) -> u64 {
         zorb ^ self.black_turn
     }
+
+    // Identifies this set of keys so data keyed by them (e.g. a saved transposition table) can be rejected when
+    // the keys change. Order dependent so swapped keys still produce a different fingerprint
+    pub fn fingerprint(&self) -> u64 {
+        let flags = [self.black_turn, self.wkc, self.wqc, self.bkc, self.bqc];
+        let keys = self
+            .table
+            .iter()
+            .flatten()
+            .chain(self.ep_table.iter())
+            .chain(flags.iter());
+        let mut r: u64 = 0xcbf29ce484222325;
+        for key in keys {
+            r = (r ^ key).rotate_left(23).wrapping_mul(0x100000001b3);
+        }
+        r
+    }
 }
 
 #[cfg(test)]
@@ -172,6 +195,13 @@ mod test {
 
     // TODO: Update these
 
+    #[test]
+    pub fn seeded_sets_are_reproducible() {
+        assert_eq!(ZorbSet::from_seed(5).fingerprint(), ZorbSet::from_seed(5).fingerprint());
+        assert_eq!(ZorbSet::new().fingerprint(), ZorbSet::from_seed(ZORB_SET_SEED).fingerprint());
+        assert_ne!(ZorbSet::from_seed(5).fingerprint(), ZorbSet::from_seed(6).fingerprint());
+    }
+
     #[test]
     pub fn hash_should_differ_based_on_ep_position() {
         let board_rep = BoardRep::default();
@@ -9,6 +9,17 @@ pub fn index_from_coords(coords: &str) -> u8 {
     get_index_from_file_and_rank(file as u8, rank as u8)
 }
 
+// Same as index_from_coords but None unless `coords` is exactly a square like "e4"
+pub fn try_index_from_coords(coords: &str) -> Option<u8> {
+    let mut chars = coords.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(file @ 'a'..='h'), Some(rank @ '1'..='8'), None) => Some(
+            get_index_from_file_and_rank(file as u8 - b'a', rank as u8 - b'1'),
+        ),
+        _ => None,
+    }
+}
+
 pub fn get_rank(index: u8) -> u8 {
     index / 0b1000u8
 }
@@ -1,133 +1,297 @@
 use std::mem::size_of;
 
-use log::info;
-
 use crate::{
     board::{board_rep::BoardRep, position::Position},
     move_generation::generate_moves_for_board,
-    r#move::Move,
+    r#move::{move_list::MoveList, scored_move::ScoredMove},
 };
 
-const POSITION_CACHE_MB_SIZE: usize = 256;
-const MOVES_CACHE_MB_SIZE: usize = 64;
-
-const POSITION_CACHE_SIZE: usize =
-    (POSITION_CACHE_MB_SIZE * 1204 * 1204) / size_of::<Option<Position>>();
-const MOVES_CACHE_SIZE: usize =
-    (MOVES_CACHE_MB_SIZE * 1204 * 1204) / size_of::<Option<MoveCacheEntry>>();
+// Positions with more moves than this aren't cached. Few positions in a game come close, and keeping the moves in
+// the slot itself means recording never allocates and the slots are all the memory the cache uses
+const MAX_CACHED_MOVES: usize = 64;
 
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Copy)]
 pub struct MoveCacheEntry {
     zorb_key: u64,
-    moves: Vec<Move>,
+    len: u8,
+    moves: [ScoredMove; MAX_CACHED_MOVES],
 }
 
+// Both caches are sized up front but only allocated on their first record, so engines that never search cost
+// nothing. Each has a fixed number of slots and a new entry always replaces whatever was in its slot
 pub struct PositionCache {
-    table: Box<[Option<Position>; POSITION_CACHE_SIZE]>,
+    table: Vec<Option<Position>>,
+    slots: usize,
     pub hits: usize,
     pub misses: usize,
 }
 
 impl PositionCache {
-    pub fn new() -> Self {
-        // https://stackoverflow.com/a/56426372
-        let table = {
-            let mut v: Vec<Option<Position>> = Vec::with_capacity(POSITION_CACHE_SIZE);
-            unsafe {
-                v.set_len(POSITION_CACHE_SIZE);
-            };
-            let mut slice = v.into_boxed_slice();
-            for i in &mut slice[..] {
-                *i = None;
-            }
-            let raw_slice = Box::into_raw(slice);
-            // Using `from_raw` is safe as long as the pointer is
-            // retrieved using `into_raw`.
-            unsafe { Box::from_raw(raw_slice as *mut [Option<Position>; POSITION_CACHE_SIZE]) }
-        };
+    pub fn with_size_mb(size_mb: usize) -> Self {
         Self {
-            table,
+            table: Vec::new(),
+            slots: slot_count::<Option<Position>>(size_mb),
             hits: 0,
             misses: 0,
         }
     }
 
+    // Drops the current table if the size changed, the new one is allocated on the next record
+    pub fn resize(&mut self, size_mb: usize) {
+        let slots = slot_count::<Option<Position>>(size_mb);
+        if slots != self.slots {
+            *self = Self::with_size_mb(size_mb);
+        }
+    }
+
     pub fn lookup(&mut self, zorb_key: u64) -> Option<Position> {
-        let index = (zorb_key as usize) % POSITION_CACHE_SIZE;
-        let e = self.table[index];
-        if e != None && e.unwrap().board.zorb_key == zorb_key {
-            self.hits += 1;
-            return e;
+        if !self.table.is_empty() {
+            if let Some(e) = self.table[(zorb_key as usize) % self.slots] {
+                if e.board.zorb_key == zorb_key {
+                    self.hits += 1;
+                    return Some(e);
+                }
+            }
         }
         self.misses += 1;
         None
     }
 
     pub fn record(&mut self, zorb_key: u64, e: Position) {
-        let index = (zorb_key as usize) % POSITION_CACHE_SIZE;
-        self.table[index] = Some(e)
+        if self.table.is_empty() {
+            self.table = vec![None; self.slots];
+        }
+        self.table[(zorb_key as usize) % self.slots] = Some(e)
+    }
+
+    // Keeps the allocation so the next game reuses it
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.table.len() * size_of::<Option<Position>>()
     }
 }
 
 pub struct MovesCache {
-    table: Box<[Option<MoveCacheEntry>; MOVES_CACHE_SIZE]>,
+    table: Vec<Option<MoveCacheEntry>>,
+    slots: usize,
     pub hits: usize,
     pub misses: usize,
 }
+
 impl MovesCache {
-    pub fn new() -> Self {
-        // https://stackoverflow.com/a/56426372
-        let table = {
-            let mut v: Vec<Option<MoveCacheEntry>> = Vec::with_capacity(MOVES_CACHE_SIZE);
-            unsafe {
-                v.set_len(MOVES_CACHE_SIZE);
-            };
-            let mut slice = v.into_boxed_slice();
-            for i in &mut slice[..] {
-                *i = None;
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        Self {
+            table: Vec::new(),
+            slots: slot_count::<Option<MoveCacheEntry>>(size_mb),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // Drops the current table if the size changed, the new one is allocated on the next record
+    pub fn resize(&mut self, size_mb: usize) {
+        let slots = slot_count::<Option<MoveCacheEntry>>(size_mb);
+        if slots != self.slots {
+            *self = Self::with_size_mb(size_mb);
+        }
+    }
+
+    // Keeps the allocation so the next game reuses it
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.table.len() * size_of::<Option<MoveCacheEntry>>()
+    }
+
+    // Copies a hit into `moves` and returns whether there was one
+    pub fn lookup(&mut self, zorb_key: u64, moves: &mut MoveList) -> bool {
+        if !self.table.is_empty() {
+            if let Some(cache_entry) = &self.table[(zorb_key as usize) % self.slots] {
+                if cache_entry.zorb_key == zorb_key {
+                    self.hits += 1;
+                    moves.copy_from(&cache_entry.moves[..cache_entry.len as usize]);
+                    return true;
+                }
             }
-            let raw_slice = Box::into_raw(slice);
-            // Using `from_raw` is safe as long as the pointer is
-            // retrieved using `into_raw`.
-            unsafe { Box::from_raw(raw_slice as *mut [Option<MoveCacheEntry>; MOVES_CACHE_SIZE]) }
+        }
+        self.misses += 1;
+        false
+    }
+
+    pub fn record(&mut self, zorb_key: u64, moves: &MoveList) {
+        if moves.len() > MAX_CACHED_MOVES {
+            return;
+        }
+        if self.table.is_empty() {
+            self.table = vec![None; self.slots];
+        }
+        let mut cache_entry = MoveCacheEntry {
+            zorb_key,
+            len: moves.len() as u8,
+            moves: [ScoredMove::default(); MAX_CACHED_MOVES],
         };
+        cache_entry.moves[..moves.len()].copy_from_slice(moves);
+        self.table[(zorb_key as usize) % self.slots] = Some(cache_entry);
+    }
+
+    pub fn get_moves(&mut self, board: BoardRep, moves: &mut MoveList) {
+        if !self.lookup(board.zorb_key, moves) {
+            generate_moves_for_board(board, moves);
+            self.record(board.zorb_key, moves);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct EvalCacheEntry {
+    zorb_key: u64,
+    eval: i16,
+}
+
+// Static evaluations keyed by zobrist key, so transpositions and repeated quiescence visits are only evaluated once
+pub struct EvalCache {
+    table: Vec<Option<EvalCacheEntry>>,
+    slots: usize,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl EvalCache {
+    pub fn with_size_mb(size_mb: usize) -> Self {
         Self {
-            table,
+            table: Vec::new(),
+            slots: slot_count::<Option<EvalCacheEntry>>(size_mb),
             hits: 0,
             misses: 0,
         }
     }
 
-    pub fn lookup(&mut self, zorb_key: u64) -> Option<Vec<Move>> {
-        let index = (zorb_key as usize) % MOVES_CACHE_SIZE;
-        let options = &self.table[index];
-        match options {
-            Some(cache_entry) => {
-                if cache_entry.zorb_key == zorb_key {
+    // Drops the current table if the size changed, the new one is allocated on the next record
+    pub fn resize(&mut self, size_mb: usize) {
+        let slots = slot_count::<Option<EvalCacheEntry>>(size_mb);
+        if slots != self.slots {
+            *self = Self::with_size_mb(size_mb);
+        }
+    }
+
+    // Keeps the allocation so the next game reuses it
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.table.len() * size_of::<Option<EvalCacheEntry>>()
+    }
+
+    pub fn lookup(&mut self, zorb_key: u64) -> Option<i16> {
+        if !self.table.is_empty() {
+            if let Some(e) = self.table[(zorb_key as usize) % self.slots] {
+                if e.zorb_key == zorb_key {
                     self.hits += 1;
-                    return Some(cache_entry.moves.clone());
+                    return Some(e.eval);
                 }
             }
-            None => {}
         }
         self.misses += 1;
         None
     }
-    pub fn record(&mut self, zorb_key: u64, e: Vec<Move>) {
-        let index = (zorb_key as usize) % MOVES_CACHE_SIZE;
-        self.table[index] = Some(MoveCacheEntry { zorb_key, moves: e })
+
+    pub fn record(&mut self, zorb_key: u64, eval: i16) {
+        if self.table.is_empty() {
+            self.table = vec![None; self.slots];
+        }
+        self.table[(zorb_key as usize) % self.slots] = Some(EvalCacheEntry { zorb_key, eval })
     }
+}
 
-    pub fn get_moves(&mut self, board: BoardRep) -> Vec<Move> {
-        let lookup_result = self.lookup(board.zorb_key);
-        let mut moves = match lookup_result {
-            Some(r) => r,
-            None => {
-                let moves = generate_moves_for_board(board);
-                self.record(board.zorb_key, moves.clone());
-                moves
-            }
-        };
-        moves
+pub(crate) fn slot_count<T>(size_mb: usize) -> usize {
+    usize::max(1, (size_mb * 1024 * 1024) / size_of::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn caches_allocate_on_first_record_and_reuse_after_clear() {
+        let board = BoardRep::default();
+        let mut cache = MovesCache::with_size_mb(1);
+        assert_eq!(cache.memory_bytes(), 0);
+        let mut moves = MoveList::new();
+        assert!(!cache.lookup(board.zorb_key, &mut moves));
+
+        cache.get_moves(board, &mut moves);
+        assert_eq!(moves.len(), 20);
+        assert!(cache.memory_bytes() >= 1024 * 1024 - size_of::<Option<MoveCacheEntry>>());
+        let mut cached = MoveList::new();
+        assert!(cache.lookup(board.zorb_key, &mut cached));
+        assert_eq!(cached, moves);
+
+        let allocated = cache.table.len();
+        cache.clear();
+        assert_eq!(cache.table.len(), allocated);
+        assert!(!cache.lookup(board.zorb_key, &mut cached));
+
+        cache.resize(1);
+        assert_eq!(cache.table.len(), allocated);
+        cache.resize(2);
+        assert_eq!(cache.memory_bytes(), 0);
+
+        let mut positions = PositionCache::with_size_mb(1);
+        assert_eq!(positions.memory_bytes(), 0);
+        let position = Position::default();
+        positions.record(position.board.zorb_key, position);
+        assert!(positions.memory_bytes() > 0);
+        assert!(positions.lookup(position.board.zorb_key).is_some());
+        assert!(positions.lookup(42).is_none());
+    }
+
+    #[test]
+    pub fn moves_cache_stays_within_its_size_when_full() {
+        let board = BoardRep::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".into());
+        let mut moves = MoveList::new();
+        generate_moves_for_board(board, &mut moves);
+        assert_eq!(moves.len(), 48);
+
+        let mut cache = MovesCache::with_size_mb(1);
+        for zorb_key in 0..cache.slots as u64 {
+            cache.record(zorb_key, &moves);
+        }
+        assert!(cache.table.iter().all(|e| e.is_some()));
+        assert!(cache.memory_bytes() <= 1024 * 1024);
+        let mut cached = MoveList::new();
+        assert!(cache.lookup(cache.slots as u64 - 1, &mut cached));
+        assert_eq!(cached, moves);
+
+        // Lists too long to fit aren't cached
+        let mut long = MoveList::new();
+        for &m in moves.iter().chain(moves.iter()) {
+            long.push(m);
+        }
+        cache.record(cache.slots as u64, &long);
+        assert!(!cache.lookup(cache.slots as u64, &mut cached));
+    }
+
+    #[test]
+    pub fn eval_cache_round_trip() {
+        let mut cache = EvalCache::with_size_mb(1);
+        assert_eq!(cache.lookup(7), None);
+        cache.record(7, -35);
+        assert_eq!(cache.lookup(7), Some(-35));
+        assert_eq!(cache.lookup(7 + cache.slots as u64), None);
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+        cache.clear();
+        assert_eq!(cache.lookup(7), None);
     }
 }
@@ -0,0 +1,88 @@
+use super::transposition_table::MAX_TRANSPOSITION_TABLE_MB_SIZE;
+
+// The transposition table is sized by the UCI Hash option on its own, as GUIs expect. The other caches share the
+// Cache option between them
+pub const DEFAULT_HASH_MB: usize = 64;
+pub const MIN_HASH_MB: usize = 1;
+pub const MAX_HASH_MB: usize = MAX_TRANSPOSITION_TABLE_MB_SIZE;
+pub const DEFAULT_CACHE_MB: usize = 64;
+pub const MIN_CACHE_MB: usize = 4;
+pub const MAX_CACHE_MB: usize = 32768;
+
+// Share of the cache budget, in percent, given to each of the caches besides the transposition table
+const POSITION_CACHE_SHARE: usize = 40;
+const MOVES_CACHE_SHARE: usize = 40;
+const EVAL_CACHE_SHARE: usize = 10;
+
+// How much memory each of the engine's caches may use
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryBudget {
+    pub transposition_table_mb: usize,
+    pub position_cache_mb: usize,
+    pub moves_cache_mb: usize,
+    pub eval_cache_mb: usize,
+    pub pawn_cache_mb: usize,
+}
+
+impl MemoryBudget {
+    pub fn new(hash_mb: usize, cache_mb: usize) -> Self {
+        let cache_mb = cache_mb.clamp(MIN_CACHE_MB, MAX_CACHE_MB);
+        let share = |percent: usize| usize::max(1, cache_mb * percent / 100);
+        let position_cache_mb = share(POSITION_CACHE_SHARE);
+        let moves_cache_mb = share(MOVES_CACHE_SHARE);
+        let eval_cache_mb = share(EVAL_CACHE_SHARE);
+        // The pawn cache takes whatever is left so the parts always add up to the cache budget
+        let pawn_cache_mb = usize::max(1, cache_mb - position_cache_mb - moves_cache_mb - eval_cache_mb);
+        Self {
+            transposition_table_mb: hash_mb.clamp(MIN_HASH_MB, MAX_HASH_MB),
+            position_cache_mb,
+            moves_cache_mb,
+            eval_cache_mb,
+            pawn_cache_mb,
+        }
+    }
+
+    // Everything besides the transposition table
+    pub fn cache_mb(&self) -> usize {
+        self.position_cache_mb + self.moves_cache_mb + self.eval_cache_mb + self.pawn_cache_mb
+    }
+
+    pub fn total_mb(&self) -> usize {
+        self.transposition_table_mb + self.cache_mb()
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB, DEFAULT_CACHE_MB)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn cache_parts_add_up_to_the_cache_budget() {
+        for cache in [MIN_CACHE_MB, 9, 64, DEFAULT_CACHE_MB, 1000, 4096] {
+            let budget = MemoryBudget::new(DEFAULT_HASH_MB, cache);
+            assert_eq!(budget.cache_mb(), cache, "{budget:?}");
+            assert_eq!(budget.total_mb(), DEFAULT_HASH_MB + cache, "{budget:?}");
+            assert!(budget.pawn_cache_mb >= 1 && budget.moves_cache_mb >= 1, "{budget:?}");
+        }
+        let budget = MemoryBudget::new(16, 64);
+        assert_eq!(budget.transposition_table_mb, 16);
+        assert_eq!(budget.position_cache_mb, 25);
+        assert_eq!(budget.moves_cache_mb, 25);
+        assert_eq!(budget.eval_cache_mb, 6);
+        assert_eq!(budget.pawn_cache_mb, 8);
+    }
+
+    #[test]
+    pub fn budget_is_clamped() {
+        let budget = MemoryBudget::new(0, 0);
+        assert_eq!((budget.transposition_table_mb, budget.cache_mb()), (MIN_HASH_MB, MIN_CACHE_MB));
+        let budget = MemoryBudget::new(usize::MAX, usize::MAX);
+        assert_eq!((budget.transposition_table_mb, budget.cache_mb()), (MAX_HASH_MB, MAX_CACHE_MB));
+    }
+}
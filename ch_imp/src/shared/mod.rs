@@ -1,5 +1,7 @@
 pub mod board_utils;
 pub(crate) mod constants;
-pub(crate) mod piece_type;
+pub mod piece_type;
 pub(crate) mod transposition_table;
-pub mod cache;
\ No newline at end of file
+pub mod cache;
+pub mod memory_budget;
+pub mod seeded_rng;
\ No newline at end of file
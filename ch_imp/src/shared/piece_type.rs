@@ -23,6 +23,47 @@ impl Default for PieceType {
     }
 }
 
+impl PieceType {
+    pub fn from_u8(value: u8) -> PieceType {
+        match value {
+            1 => PieceType::Pawn,
+            2 => PieceType::Knight,
+            3 => PieceType::Bishop,
+            4 => PieceType::Rook,
+            5 => PieceType::Queen,
+            6 => PieceType::King,
+            _ => PieceType::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colour {
+    White,
+    Black,
+}
+
+impl Colour {
+    pub fn from_is_black(is_black: bool) -> Colour {
+        if is_black {
+            Colour::Black
+        } else {
+            Colour::White
+        }
+    }
+
+    pub fn is_black(&self) -> bool {
+        *self == Colour::Black
+    }
+
+    pub fn opposite(&self) -> Colour {
+        match self {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        }
+    }
+}
+
 pub fn get_piece_char(piece_type: PieceType, black_turn: bool) -> char {
     match (piece_type, black_turn) {
         (PieceType::Pawn, false) => 'P',
@@ -1,11 +1,37 @@
-use std::mem::size_of;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    mem::size_of,
+    path::Path,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+};
 
-use crate::{r#move::Move, engine::search::{AB_MAX, AB_MIN}};
+use crate::{
+    board::board_rep::BoardRep,
+    engine::search::{AB_MAX, AB_MIN},
+    r#move::Move,
+    search::zorb_set_precomputed::ZORB_SET,
+};
 
-const TRANSPOSITION_TABLE_MB_SIZE: usize = 64;
+pub const MAX_TRANSPOSITION_TABLE_MB_SIZE: usize = 65536;
 
-const TRANSPOSITION_TABLE_SIZE: usize =
-    (TRANSPOSITION_TABLE_MB_SIZE * 1204 * 1204) / size_of::<Option<TransTableEntry>>();
+const BUCKET_SIZE: usize = 4;
+
+// Number of buckets sampled when estimating how full the table is
+const HASHFULL_SAMPLE_SIZE: usize = 1000;
+
+// Saved table files start with the magic followed by the format version and the fingerprint of the zobrist keys
+// the entries were hashed with. Bump the version whenever the entry packing or the way positions are hashed from
+// the keys changes. Version 2 stopped toggling the keys of castling rights that had already been lost
+const FILE_MAGIC: &[u8; 8] = b"CHIMPTT\0";
+const FILE_VERSION: u32 = 2;
+
+// Layout of the packed data word:
+// bits 0-15 move, 16-31 value, 32-39 depth, 40-41 node type (0 = empty), 48-55 age
+const VALUE_SHIFT: u64 = 16;
+const DEPTH_SHIFT: u64 = 32;
+const NODE_TYPE_SHIFT: u64 = 40;
+const AGE_SHIFT: u64 = 48;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum NodeType {
@@ -14,105 +40,621 @@ pub enum NodeType {
     AllNode,
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct TransTableEntry {
+impl NodeType {
+    fn pack(self) -> u64 {
+        match self {
+            NodeType::PVNode => 1,
+            NodeType::CutNode => 2,
+            NodeType::AllNode => 3,
+        }
+    }
+
+    fn unpack(bits: u64) -> Option<Self> {
+        match bits & 0b11 {
+            1 => Some(NodeType::PVNode),
+            2 => Some(NodeType::CutNode),
+            3 => Some(NodeType::AllNode),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TransTableEntry {
     zorb_key: u64,
     depth: u8,
     value: i16,
     t: NodeType,
-    m: Option<Move>,
+    // Move stored as from << 10 | to << 4 | flags, 0 when there is no move
+    m: u16,
+    age: u8,
+}
+
+impl TransTableEntry {
+    fn pack(&self) -> u64 {
+        self.m as u64
+            | (self.value as u16 as u64) << VALUE_SHIFT
+            | (self.depth as u64) << DEPTH_SHIFT
+            | self.t.pack() << NODE_TYPE_SHIFT
+            | (self.age as u64) << AGE_SHIFT
+    }
+
+    fn unpack(zorb_key: u64, data: u64) -> Option<Self> {
+        Some(Self {
+            zorb_key,
+            m: data as u16,
+            value: (data >> VALUE_SHIFT) as u16 as i16,
+            depth: (data >> DEPTH_SHIFT) as u8,
+            t: NodeType::unpack(data >> NODE_TYPE_SHIFT)?,
+            age: (data >> AGE_SHIFT) as u8,
+        })
+    }
+}
+
+// The key is stored xor'd with the data so a torn write from another thread (key from one record, data from
+// another) fails validation rather than returning the wrong position's data
+#[derive(Default)]
+struct PackedEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl PackedEntry {
+    fn load(&self) -> Option<TransTableEntry> {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+        TransTableEntry::unpack(key, data)
+    }
+
+    fn store(&self, entry: TransTableEntry) {
+        let data = entry.pack();
+        self.key.store(entry.zorb_key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
 }
 
+// A bucket fills exactly one cache line
+#[derive(Default)]
+#[repr(align(64))]
+struct TransTableBucket {
+    entries: [PackedEntry; BUCKET_SIZE],
+}
+
+// All lookups and writes take &self so the table can be shared between search threads without locking. A table
+// created with `unallocated` holds no memory until `allocate` is called, until then every lookup misses
 pub struct TranspositionTable {
-    table: Box<[Option<TransTableEntry>; TRANSPOSITION_TABLE_SIZE]>,
+    table: Box<[TransTableBucket]>,
+    size_mb: usize,
+    // Bumped at the start of every search so entries from earlier searches can be replaced first
+    generation: AtomicU8,
 }
 
 impl TranspositionTable {
-    pub fn new() -> Self {
-        // https://stackoverflow.com/a/56426372
-        let table = {
-            let mut v: Vec<Option<TransTableEntry>> = Vec::with_capacity(TRANSPOSITION_TABLE_SIZE);
-            unsafe {
-                v.set_len(TRANSPOSITION_TABLE_SIZE);
-            };
-            let mut slice = v.into_boxed_slice();
-            for i in &mut slice[..] {
-                *i = None;
+    pub fn unallocated(size_mb: usize) -> Self {
+        Self {
+            table: Box::new([]),
+            size_mb: size_mb.clamp(1, MAX_TRANSPOSITION_TABLE_MB_SIZE),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        let mut tt = Self::unallocated(size_mb);
+        tt.allocate();
+        tt
+    }
+
+    pub fn allocate(&mut self) {
+        if self.table.is_empty() {
+            self.table = (0..bucket_count(self.size_mb))
+                .map(|_| TransTableBucket::default())
+                .collect();
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.table.len() * size_of::<TransTableBucket>()
+    }
+
+    // Frees the table if the size changed, the new one is allocated before the next search
+    pub fn resize(&mut self, size_mb: usize) {
+        if size_mb.clamp(1, MAX_TRANSPOSITION_TABLE_MB_SIZE) != self.size_mb {
+            *self = Self::unallocated(size_mb);
+        }
+    }
+
+    pub fn clear(&self) {
+        for bucket in self.table.iter() {
+            for entry in &bucket.entries {
+                entry.clear();
             }
-            let raw_slice = Box::into_raw(slice);
-            // Using `from_raw` is safe as long as the pointer is
-            // retrieved using `into_raw`.
-            unsafe {
-                Box::from_raw(raw_slice as *mut [Option<TransTableEntry>; TRANSPOSITION_TABLE_SIZE])
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Permille of sampled entries that were written during the current search
+    pub fn hashfull(&self) -> u16 {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = usize::min(HASHFULL_SAMPLE_SIZE, self.table.len());
+        if sample == 0 {
+            return 0;
+        }
+        let mut used = 0;
+        for bucket in &self.table[..sample] {
+            for entry in bucket.entries.iter().filter_map(|e| e.load()) {
+                if entry.age == generation {
+                    used += 1;
+                }
             }
-        };
-        Self { table }
+        }
+        (used * 1000 / (sample * BUCKET_SIZE)) as u16
     }
 
-    pub fn lookup(&self, zorb_key: u64, depth: u8, alpha: i16, beta: i16) -> Option<(i16, Move)> {
-        let index = (zorb_key as usize) % TRANSPOSITION_TABLE_SIZE;
-        let option = self.table[index];
-        match option {
+    // Hint the CPU to start loading the bucket for a position we're about to search
+    #[inline]
+    pub fn prefetch(&self, zorb_key: u64) {
+        #[cfg(target_arch = "x86_64")]
+        if !self.table.is_empty() {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket: *const TransTableBucket = &self.table[self.index(zorb_key)];
+            unsafe { _mm_prefetch(bucket as *const i8, _MM_HINT_T0) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = zorb_key;
+    }
+
+    fn find(&self, zorb_key: u64) -> Option<TransTableEntry> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let bucket = &self.table[self.index(zorb_key)];
+        bucket
+            .entries
+            .iter()
+            .filter_map(|e| e.load())
+            .find(|e| e.zorb_key == zorb_key)
+    }
+
+    pub fn lookup(
+        &self,
+        board: &BoardRep,
+        depth: u8,
+        alpha: i16,
+        beta: i16,
+    ) -> Option<(i16, Option<Move>)> {
+        match self.find(board.zorb_key) {
             Some(entry) => {
-                if entry.zorb_key == zorb_key {
-                    if entry.depth >= depth {
-                        match entry.t {
-                            // Exact value known - return the value + move
-                            NodeType::PVNode => return Some((entry.value, entry.m.unwrap())),
-                            // This was a fail low node - this score is the upper bound of all searched nodes and the
-                            // real value may be less. As a result if the upper bound is less than current alpha we know that none of these
-                            // nodes are going to improve our alpha and thus aren't worth considering. Returning alpha allows us to prune this search branch
-                            NodeType::AllNode => {
-                                if entry.value <= alpha {
-                                    return Some((alpha, Move::default()));
-                                }
+                if entry.depth >= depth {
+                    match entry.t {
+                        // Exact value known - return the value + move
+                        NodeType::PVNode => return Some((entry.value, decode_move(entry.m))),
+                        // This was a fail low node - this score is the upper bound of all searched nodes and the
+                        // real value may be less. As a result if the upper bound is less than current alpha we know that none of these
+                        // nodes are going to improve our alpha and thus aren't worth considering. Returning alpha allows us to prune this search branch
+                        NodeType::AllNode => {
+                            if entry.value <= alpha {
+                                return Some((alpha, None));
                             }
-                            // This was a fail high node - this score is the lower bound of the searched nodes and the real value may be
-                            // higher. As a result if the lower bound is higher than current beta we know that we would trim all these
-                            // nodes due to beta cutoff. Returning beta here allows us to prune this search branch
-                            NodeType::CutNode => {
-                                if entry.value >= beta {
-                                    return Some((beta, Move::default()));
-                                }
+                        }
+                        // This was a fail high node - this score is the lower bound of the searched nodes and the real value may be
+                        // higher. As a result if the lower bound is higher than current beta we know that we would trim all these
+                        // nodes due to beta cutoff. Returning beta here allows us to prune this search branch
+                        NodeType::CutNode => {
+                            if entry.value >= beta {
+                                return Some((beta, None));
                             }
                         }
                     }
                 }
-                return None;
+                None
             }
             None => None,
         }
     }
 
-    pub fn get_move(&self, zorb_key: u64) -> Option<Move> {
-        let index = (zorb_key as usize) % TRANSPOSITION_TABLE_SIZE;
-        let option = self.table[index];
-        match option {
-            Some(entry) => {
-                if entry.zorb_key == zorb_key {
-                    entry.m
-                } else {
-                    None
-                }
-            }
+    pub fn get_move(&self, board: &BoardRep) -> Option<Move> {
+        match self.find(board.zorb_key) {
+            Some(entry) => decode_move(entry.m),
             None => None,
         }
     }
 
-    pub fn record(&mut self, zorb_key: u64, depth: u8, ply: u8, mut value: i16, t: NodeType, m: Option<Move>) {
-        let index = (zorb_key as usize) % TRANSPOSITION_TABLE_SIZE;
+    pub fn record(&self, zorb_key: u64, depth: u8, ply: u8, mut value: i16, t: NodeType, m: Option<Move>) {
         if value == AB_MAX {
             value -= ply as i16;
         }
         if value == AB_MIN {
             value += ply as i16;
         }
-        self.table[index] = Some(TransTableEntry {
+        if self.table.is_empty() {
+            return;
+        }
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = &self.table[self.index(zorb_key)];
+
+        let slot = replacement_slot(bucket, zorb_key, generation);
+        let m = match (m, bucket.entries[slot].load()) {
+            (Some(m), _) => encode_move(m),
+            // Keep the previous best move for this position if the new result doesn't have one
+            (None, Some(previous)) if previous.zorb_key == zorb_key => previous.m,
+            (None, _) => 0,
+        };
+        bucket.entries[slot].store(TransTableEntry {
             zorb_key,
             depth,
             value,
             t,
             m,
+            age: generation,
         })
     }
+
+    fn index(&self, zorb_key: u64) -> usize {
+        (zorb_key as usize) % self.table.len()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    // Tables saved at a different size than `size_mb` are rejected before anything is allocated, so loading can't
+    // go over the memory the table was given
+    pub fn load<P: AsRef<Path>>(path: P, size_mb: usize) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?), size_mb)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&ZORB_SET.fingerprint().to_le_bytes())?;
+        writer.write_all(&(self.size_mb as u64).to_le_bytes())?;
+        writer.write_all(&(bucket_count(self.size_mb) as u64).to_le_bytes())?;
+        writer.write_all(&[self.generation.load(Ordering::Relaxed)])?;
+        if self.table.is_empty() {
+            // Never allocated so every entry is empty
+            for _ in 0..bucket_count(self.size_mb) * BUCKET_SIZE {
+                writer.write_all(&[0; 16])?;
+            }
+        }
+        for bucket in self.table.iter() {
+            for entry in &bucket.entries {
+                writer.write_all(&entry.key.load(Ordering::Relaxed).to_le_bytes())?;
+                writer.write_all(&entry.data.load(Ordering::Relaxed).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R, expected_size_mb: usize) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid_data("not a chimp transposition table file".into()));
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != FILE_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported transposition table version {version}, expected {FILE_VERSION}"
+            )));
+        }
+        if read_u64(reader)? != ZORB_SET.fingerprint() {
+            return Err(invalid_data(
+                "transposition table was saved with different zobrist keys".into(),
+            ));
+        }
+        let size_mb = read_u64(reader)? as usize;
+        let bucket_len = read_u64(reader)? as usize;
+        if size_mb == 0 || size_mb > MAX_TRANSPOSITION_TABLE_MB_SIZE || bucket_len != bucket_count(size_mb) {
+            return Err(invalid_data(format!(
+                "transposition table has {bucket_len} buckets which doesn't match its {size_mb}MB size"
+            )));
+        }
+        if size_mb != expected_size_mb.clamp(1, MAX_TRANSPOSITION_TABLE_MB_SIZE) {
+            return Err(invalid_data(format!(
+                "transposition table was saved at {size_mb}MB but the table is set to {expected_size_mb}MB"
+            )));
+        }
+        let mut generation = [0; 1];
+        reader.read_exact(&mut generation)?;
+
+        let tt = Self::with_size_mb(size_mb);
+        tt.generation.store(generation[0], Ordering::Relaxed);
+        for bucket in tt.table.iter() {
+            for entry in &bucket.entries {
+                entry.key.store(read_u64(reader)?, Ordering::Relaxed);
+                entry.data.store(read_u64(reader)?, Ordering::Relaxed);
+            }
+        }
+        Ok(tt)
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn bucket_count(size_mb: usize) -> usize {
+    usize::max(1, (size_mb * 1024 * 1024) / size_of::<TransTableBucket>())
+}
+
+fn encode_move(m: Move) -> u16 {
+    m.as_u16()
+}
+
+fn decode_move(m: u16) -> Option<Move> {
+    if m == 0 {
+        return None;
+    }
+    Some(Move::from_u16(m))
+}
+
+// Prefer the slot already holding this position, then an empty slot, and otherwise the entry with the lowest
+// depth once it has been penalised for how many searches ago it was written
+fn replacement_slot(bucket: &TransTableBucket, zorb_key: u64, generation: u8) -> usize {
+    let mut worst_slot = 0;
+    let mut worst_score = i32::MAX;
+    for (slot, entry) in bucket.entries.iter().enumerate() {
+        match entry.load() {
+            None => return slot,
+            Some(entry) => {
+                if entry.zorb_key == zorb_key {
+                    return slot;
+                }
+                let age = generation.wrapping_sub(entry.age) as i32;
+                let score = entry.depth as i32 - (8 * age);
+                if score < worst_score {
+                    worst_score = score;
+                    worst_slot = slot;
+                }
+            }
+        }
+    }
+    worst_slot
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use crate::shared::constants::MF_DOUBLE_PAWN_PUSH;
+
+    use super::*;
+
+    fn test_move() -> Move {
+        Move::new(11, 27, MF_DOUBLE_PAWN_PUSH)
+    }
+
+    fn board_with_key(zorb_key: u64) -> BoardRep {
+        BoardRep {
+            zorb_key,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn entries_are_compact() {
+        assert_eq!(size_of::<PackedEntry>(), 16);
+        assert_eq!(size_of::<TransTableBucket>(), 64);
+    }
+
+    #[test]
+    pub fn packed_entry_round_trip() {
+        let entry = TransTableEntry {
+            zorb_key: 0xDEAD_BEEF_1234_5678,
+            depth: 13,
+            value: -1234,
+            t: NodeType::CutNode,
+            m: encode_move(test_move()),
+            age: 200,
+        };
+        let packed = PackedEntry::default();
+        assert_eq!(packed.load(), None);
+        packed.store(entry);
+        assert_eq!(packed.load(), Some(entry));
+    }
+
+    #[test]
+    pub fn stored_move_round_trips() {
+        let tt = TranspositionTable::with_size_mb(1);
+        let board = board_with_key(42);
+        tt.record(42, 3, 0, 10, NodeType::PVNode, Some(test_move()));
+        let m = tt.get_move(&board).unwrap();
+        assert_eq!(m, test_move());
+        assert_eq!(tt.lookup(&board, 3, -100, 100), Some((10, Some(test_move()))));
+        assert_eq!(tt.lookup(&board, 4, -100, 100), None);
+    }
+
+    #[test]
+    pub fn unallocated_table_misses_until_allocated() {
+        let mut tt = TranspositionTable::unallocated(1);
+        assert_eq!(tt.memory_bytes(), 0);
+        tt.record(42, 3, 0, 10, NodeType::PVNode, Some(test_move()));
+        tt.prefetch(42);
+        assert_eq!(tt.get_move(&board_with_key(42)), None);
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.allocate();
+        assert_eq!(tt.memory_bytes(), 1024 * 1024);
+        tt.record(42, 3, 0, 10, NodeType::PVNode, Some(test_move()));
+        assert!(tt.get_move(&board_with_key(42)).is_some());
+
+        tt.resize(1);
+        assert!(tt.get_move(&board_with_key(42)).is_some());
+        tt.resize(2);
+        assert_eq!(tt.memory_bytes(), 0);
+    }
+
+    #[test]
+    pub fn table_size_follows_configured_megabytes() {
+        let tt = TranspositionTable::with_size_mb(2);
+        assert_eq!(tt.table.len() * size_of::<TransTableBucket>(), 2 * 1024 * 1024);
+        let tt = TranspositionTable::with_size_mb(16);
+        assert_eq!(tt.table.len(), bucket_count(16));
+    }
+
+    #[test]
+    pub fn colliding_keys_share_a_bucket() {
+        let tt = TranspositionTable::with_size_mb(1);
+        let len = tt.table.len() as u64;
+        for i in 0..BUCKET_SIZE as u64 {
+            tt.record(5 + (i * len), 3, 0, 10, NodeType::PVNode, Some(test_move()));
+        }
+        for i in 0..BUCKET_SIZE as u64 {
+            let board = board_with_key(5 + (i * len));
+            assert_eq!(tt.lookup(&board, 3, -100, 100), Some((10, Some(test_move()))));
+        }
+    }
+
+    #[test]
+    pub fn replaces_shallowest_entry_when_bucket_full() {
+        let tt = TranspositionTable::with_size_mb(1);
+        let len = tt.table.len() as u64;
+        for i in 0..BUCKET_SIZE as u64 {
+            tt.record(7 + (i * len), 4 + i as u8, 0, 10, NodeType::PVNode, Some(test_move()));
+        }
+        let new_key = 7 + (BUCKET_SIZE as u64 * len);
+        tt.record(new_key, 6, 0, 10, NodeType::PVNode, Some(test_move()));
+
+        assert!(tt.get_move(&board_with_key(new_key)).is_some());
+        assert!(tt.get_move(&board_with_key(7)).is_none());
+        assert!(tt.get_move(&board_with_key(7 + len)).is_some());
+    }
+
+    #[test]
+    pub fn prefers_replacing_entries_from_old_searches() {
+        let tt = TranspositionTable::with_size_mb(1);
+        let len = tt.table.len() as u64;
+        tt.record(9, 10, 0, 10, NodeType::PVNode, Some(test_move()));
+        tt.new_search();
+        tt.new_search();
+        for i in 1..BUCKET_SIZE as u64 {
+            tt.record(9 + (i * len), 2, 0, 10, NodeType::PVNode, Some(test_move()));
+        }
+        let new_key = 9 + (BUCKET_SIZE as u64 * len);
+        tt.record(new_key, 2, 0, 10, NodeType::PVNode, Some(test_move()));
+
+        assert!(tt.get_move(&board_with_key(9)).is_none());
+        assert!(tt.get_move(&board_with_key(new_key)).is_some());
+    }
+
+    #[test]
+    pub fn keeps_previous_move_when_new_result_has_none() {
+        let tt = TranspositionTable::with_size_mb(1);
+        tt.record(11, 2, 0, 10, NodeType::PVNode, Some(test_move()));
+        tt.record(11, 3, 0, -5, NodeType::AllNode, None);
+        assert_eq!(tt.get_move(&board_with_key(11)), Some(test_move()));
+    }
+
+    #[test]
+    pub fn clear_and_hashfull() {
+        let tt = TranspositionTable::with_size_mb(1);
+        assert_eq!(tt.hashfull(), 0);
+        for key in 0..(tt.table.len() as u64) {
+            tt.record(key, 1, 0, 0, NodeType::PVNode, Some(test_move()));
+        }
+        assert_eq!(tt.hashfull(), 250);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        tt.clear();
+        assert!(tt.get_move(&board_with_key(1)).is_none());
+    }
+
+    #[test]
+    pub fn save_and_load_round_trip() {
+        let tt = TranspositionTable::with_size_mb(1);
+        tt.new_search();
+        tt.record(42, 7, 0, -25, NodeType::CutNode, Some(test_move()));
+        tt.record(43, 3, 0, 10, NodeType::PVNode, Some(test_move()));
+
+        let path = std::env::temp_dir().join("chimp_tt_round_trip.bin");
+        tt.save(&path).unwrap();
+        let loaded = TranspositionTable::load(&path, 1).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.size_mb(), 1);
+        assert_eq!(loaded.hashfull(), tt.hashfull());
+        assert_eq!(loaded.lookup(&board_with_key(42), 7, -100, -30), Some((-30, None)));
+        assert_eq!(loaded.lookup(&board_with_key(43), 3, -100, 100), Some((10, Some(test_move()))));
+    }
+
+    #[test]
+    pub fn load_rejects_mismatched_files() {
+        let tt = TranspositionTable::with_size_mb(1);
+        let mut bytes = Vec::new();
+        tt.write_to(&mut bytes).unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[8] += 1;
+        let err = TranspositionTable::read_from(&mut wrong_version.as_slice(), 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Saved by a build that hashed castling rights differently
+        let mut old_version = bytes.clone();
+        old_version[8..12].copy_from_slice(&1u32.to_le_bytes());
+        let err = TranspositionTable::read_from(&mut old_version.as_slice(), 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut wrong_keys = bytes.clone();
+        wrong_keys[12] ^= 1;
+        let err = TranspositionTable::read_from(&mut wrong_keys.as_slice(), 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = TranspositionTable::read_from(&mut &b"not a table"[..], 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = TranspositionTable::read_from(&mut &bytes[..100], 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let err = TranspositionTable::read_from(&mut bytes.as_slice(), 2).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    pub fn concurrent_readers_and_writers_never_see_torn_entries() {
+        let tt = Arc::new(TranspositionTable::with_size_mb(1));
+        let len = tt.table.len() as u64;
+        let handles: Vec<_> = (0..4u64)
+            .map(|t| {
+                let tt = tt.clone();
+                thread::spawn(move || {
+                    for i in 0..20000u64 {
+                        // Every thread hammers the same few buckets with its own values
+                        let key = (i % 8) * len + (i % 3);
+                        let value = (key % 1000) as i16;
+                        tt.record(key, (t + 1) as u8, 0, value, NodeType::PVNode, Some(test_move()));
+                        if let Some((v, m)) = tt.lookup(&board_with_key(key), 0, -100, 100) {
+                            assert_eq!(v, value);
+                            assert_eq!(m, Some(test_move()));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
 }
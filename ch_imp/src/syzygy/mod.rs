@@ -0,0 +1,184 @@
+use std::{ffi::OsStr, io, num::NonZeroU32};
+
+use shakmaty::{
+    Board, CastlingMode, Chess, Color, FromSetup, Piece, Position as _, PositionError, Role, Setup, Square,
+};
+use shakmaty_syzygy::{AmbiguousWdl, Tablebase};
+
+pub use shakmaty_syzygy::Wdl;
+
+use crate::{
+    board::board_rep::BoardRep,
+    match_state::game_state::GameState,
+    move_generation::generate_moves_for_board,
+    r#move::{move_list::MoveList, Move},
+    shared::{
+        board_utils::{get_file, get_rank},
+        piece_type::{Colour, PieceType},
+    },
+};
+
+// Syzygy WDL and DTZ tables. The files are only opened the first time a position needs them
+pub struct SyzygyTablebase {
+    tables: Tablebase<Chess>,
+}
+
+impl SyzygyTablebase {
+    // `paths` is a list of directories in the platform's PATH format, as Syzygy paths usually are
+    pub fn load<P: AsRef<OsStr>>(paths: P) -> io::Result<Self> {
+        let mut tables = Tablebase::new();
+        for path in std::env::split_paths(&paths) {
+            tables.add_directory(path)?;
+        }
+        if tables.max_pieces() == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no tables found"));
+        }
+        Ok(Self { tables })
+    }
+
+    // Positions with more pieces than this, kings included, are never in the tables
+    pub fn max_pieces(&self) -> u32 {
+        self.tables.max_pieces() as u32
+    }
+
+    // The result for the side to move assuming the last move was a capture or pawn move, so the 50 move counter
+    // doesn't need to be known. Only needs the WDL tables
+    pub fn probe_wdl(&self, board: &BoardRep) -> Option<Wdl> {
+        self.tables.probe_wdl_after_zeroing(&to_chess(board, 0)?).ok()
+    }
+
+    // The moves that keep the best result the tables allow with the game's 50 move counter, along with that
+    // result. Needs the DTZ tables as well
+    pub fn root_moves(&self, game_state: &GameState) -> Option<(Vec<Move>, Wdl)> {
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(game_state.position.board, &mut legal_moves);
+
+        let mut results = Vec::new();
+        for m in legal_moves.iter().map(|s| s.m) {
+            let after = game_state.make(m);
+            let chess = to_chess(&after.position.board, after.half_moves)?;
+            // The result is from the opponent's point of view, so the best moves have the lowest result
+            let result = if chess.is_checkmate() {
+                AmbiguousWdl::Loss
+            } else if chess.is_stalemate() {
+                AmbiguousWdl::Draw
+            } else {
+                let dtz = self.tables.probe_dtz(&chess).ok()?;
+                AmbiguousWdl::from_dtz_and_halfmoves(dtz, after.half_moves as u32)
+            };
+            results.push((m, result));
+        }
+
+        let best = results.iter().map(|(_, result)| *result).min()?;
+        let moves = results.into_iter().filter(|(_, result)| *result == best).map(|(m, _)| m).collect();
+        Some((moves, -best.after_zeroing()))
+    }
+}
+
+// Syzygy tables have no positions with castling rights
+fn to_chess(board: &BoardRep, half_moves: u8) -> Option<Chess> {
+    if board.white_king_side_castling
+        || board.white_queen_side_castling
+        || board.black_king_side_castling
+        || board.black_queen_side_castling
+    {
+        return None;
+    }
+
+    let square = |index: u8| Square::new((get_rank(index) * 8 + get_file(index)) as u32);
+    let mut pieces = Board::empty();
+    for index in 0..64 {
+        if let Some((piece_type, colour)) = board.piece_at(index) {
+            let role = match piece_type {
+                PieceType::Pawn => Role::Pawn,
+                PieceType::Knight => Role::Knight,
+                PieceType::Bishop => Role::Bishop,
+                PieceType::Rook => Role::Rook,
+                PieceType::Queen => Role::Queen,
+                PieceType::King => Role::King,
+                PieceType::None => continue,
+            };
+            let color = if colour == Colour::White { Color::White } else { Color::Black };
+            pieces.set_piece_at(square(index), Piece { color, role });
+        }
+    }
+
+    let setup = Setup {
+        board: pieces,
+        turn: if board.black_turn { Color::Black } else { Color::White },
+        ep_square: (board.ep_index != u8::MAX).then(|| square(board.ep_index)),
+        halfmoves: half_moves as u32,
+        fullmoves: NonZeroU32::MIN,
+        ..Setup::empty()
+    };
+    // An en passant square nobody can capture on is dropped rather than rejecting the position
+    Chess::from_setup(setup, CastlingMode::Standard)
+        .or_else(PositionError::ignore_invalid_ep_square)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use shakmaty::{fen::Fen, EnPassantMode};
+
+    use super::{to_chess, SyzygyTablebase, Wdl};
+    use crate::{
+        match_state::game_state::GameState, move_generation::generate_moves_for_board, r#move::move_list::MoveList,
+    };
+
+    // KQvK and KRvK, see test-tables/README.md
+    const TEST_TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test-tables");
+
+    fn chess_fen(fen: &str, half_moves: u8) -> Option<String> {
+        let game_state = GameState::new(fen.into());
+        let chess = to_chess(&game_state.position.board, half_moves)?;
+        Some(Fen::from_position(&chess, EnPassantMode::Legal).to_string())
+    }
+
+    #[test]
+    pub fn boards_convert_to_the_same_position() {
+        let fen = "8/2k5/8/3pP3/8/8/5K2/4R3 w - d6 0 40";
+        assert_eq!(chess_fen(fen, 7).unwrap(), "8/2k5/8/3pP3/8/8/5K2/4R3 w - d6 7 1");
+        let fen = "4k3/8/8/8/3p4/8/4P3/1N2K3 b - - 0 1";
+        assert_eq!(chess_fen(fen, 0).unwrap(), "4k3/8/8/8/3p4/8/4P3/1N2K3 b - - 0 1");
+        assert!(chess_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1", 0).is_none());
+    }
+
+    #[test]
+    pub fn load_needs_tables() {
+        let dir = std::env::temp_dir().join("chimp_empty_syzygy");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(SyzygyTablebase::load(&dir).is_err());
+        assert!(SyzygyTablebase::load("/nonexistent/syzygy").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK tables in test-tables"]
+    pub fn probe_wdl_reads_the_tables() {
+        let tablebase = SyzygyTablebase::load(TEST_TABLES).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+        let probe = |fen: &str| tablebase.probe_wdl(&GameState::new(fen.into()).position.board);
+        assert_eq!(probe("8/8/8/4k3/8/8/4K3/4Q3 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/4K3/4Q3 b - - 0 1"), Some(Wdl::Loss));
+        assert_eq!(probe("8/8/8/4k3/8/8/4K3/R7 w - - 0 1"), Some(Wdl::Win));
+        // Black takes the undefended rook
+        assert_eq!(probe("8/8/8/8/8/3k4/3R4/7K b - - 0 1"), Some(Wdl::Draw));
+    }
+
+    #[test]
+    #[ignore = "needs the KQvK and KRvK tables in test-tables"]
+    pub fn root_moves_drop_the_moves_that_give_up_the_win() {
+        let tablebase = SyzygyTablebase::load(TEST_TABLES).unwrap();
+        let game_state = GameState::new("8/8/8/8/3k4/8/4Q3/K7 w - - 0 1".into());
+        let (moves, wdl) = tablebase.root_moves(&game_state).unwrap();
+        assert_eq!(wdl, Wdl::Win);
+
+        // Every queen move next to the black king loses the queen, everything else still wins
+        let mut legal_moves = MoveList::new();
+        generate_moves_for_board(game_state.position.board, &mut legal_moves);
+        assert_eq!(moves.len(), legal_moves.iter().count() - 5);
+        for uci in ["e2e3", "e2e4", "e2e5", "e2d3", "e2c4"] {
+            assert!(!moves.contains(&game_state.parse_uci_move(uci).unwrap()));
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::fs::read_to_string;
 use colored::Colorize;
 
-use crate::{engine::ChimpEngine, match_state::game_state::GameState};
+use crate::{engine::ChimpEngine, match_state::game_state::GameState, r#move::Move};
 
 // const TEST_SUITES: [(&str, usize); 3] = [ ("AH_Endgames-250.epd", 5000),("bk_test.txt", 5000),("KaufmanTestSuite.txt", 5000)];
 const TEST_SUITES: [(&str, usize); 1] = [("KaufmanTestSuite.txt", 20000)];
@@ -52,11 +52,11 @@ fn perform_test(
 
     let (bestmove, ponder) = engine.go(0, 0, timeout as i32, timeout as i32);
 
-    let san = engine.current_game_state.to_san(bestmove);
+    let san = engine.current_game_state().to_san(bestmove);
 
     let result = match command {
-        CommandType::bm => san == command_str,
-        CommandType::am => san != command_str,
+        CommandType::bm => is_san_move(&engine.current_game_state(), &command_str, bestmove),
+        CommandType::am => !is_san_move(&engine.current_game_state(), &command_str, bestmove),
     };
 
     println!(
@@ -80,6 +80,11 @@ fn get_command_pos(line: &str) -> (CommandType, usize) {
     }
 }
 
+// EPD operands can list several moves, any of them counts
+fn is_san_move(game_state: &GameState, sans: &str, m: Move) -> bool {
+    sans.split_whitespace().any(|san| game_state.parse_san(san) == Ok(m))
+}
+
 fn trim_command(cmd: String) -> String {
     let space_pos = cmd.find(" ").unwrap() + 1;
     let len = cmd.len();
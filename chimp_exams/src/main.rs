@@ -3,7 +3,7 @@ use std::{
     time::{Duration, Instant, SystemTime},
 };
 
-use ch_imp::engine::ChimpEngine;
+use ch_imp::{engine::ChimpEngine, match_state::game_state::GameState, r#move::Move};
 use log::{info, LevelFilter};
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
@@ -137,14 +137,18 @@ fn read_file(theme: &str, file_name: &str) -> TestSuite {
 fn run_suite(test_suite: TestSuite, timems: i32) -> usize {
     let mut score = 0;
     info!("===== {} =====", test_suite.name);
+    // One engine for the whole suite so its tables are allocated once and only cleared between tests
+    let mut engine = ChimpEngine::new();
     for test in test_suite.tests {
-        let mut engine = ChimpEngine::from_position(test.fen);
+        engine.new_game();
+        engine.load_fen(test.fen);
         let timeout = Instant::now()
             .checked_add(Duration::from_millis(timems as u64))
             .unwrap();
         let result = engine.iterative_deepening(&|| Instant::now() > timeout, vec![]);
-        let san = engine.current_game_state.to_san(result[0]);
-        let score_change = handle_result(&test.result_scores, &san);
+        let san = engine.current_game_state().to_san(result[0]);
+        let score_change =
+            handle_result(&engine.current_game_state(), &test.result_scores, result[0]);
         info!(
             "'{}': {score_change}/10 M:{} BM:{} ({:?})",
             test.name, san, test.result_scores[0].m, result[0]
@@ -155,10 +159,15 @@ fn run_suite(test_suite: TestSuite, timems: i32) -> usize {
     score
 }
 
-fn handle_result(result_scores: &Vec<ResultScore>, bestmove: &String) -> usize {
+// Compares moves rather than SAN strings so differences in check marks or disambiguation don't matter
+fn handle_result(
+    game_state: &GameState,
+    result_scores: &Vec<ResultScore>,
+    bestmove: Move,
+) -> usize {
     let mut score = 0;
     for result_score in result_scores {
-        if bestmove.eq(&result_score.m) {
+        if game_state.parse_san(&result_score.m) == Ok(bestmove) {
             score = result_score.score;
         }
     }
@@ -6,16 +6,22 @@ use std::{
 
 use ch_imp::{
     board::{bitboard::Bitboard, position::Position, board_rep::BoardRep},
+    book::builder::BookBuilder,
     engine::{
         perft::perft,
-        san::build_san,
         search::{AB_MAX, AB_MIN},
         ChimpEngine,
     },
-    match_state::game_state::{self, GameState, MatchResultState},
+    match_state::{
+        game_state::{self, GameState, MatchResultState},
+        r#match::{Match, Termination},
+    },
     move_generation::generate_moves_for_board,
     r#move::move_data::MoveData,
-    shared::board_utils::{get_index_from_file_and_rank, index_from_coords},
+    shared::{
+        board_utils::{get_index_from_file_and_rank, index_from_coords},
+        piece_type::Colour,
+    },
     testing::test_engine,
     MOVE_DATA,
 };
@@ -27,6 +33,12 @@ use log4rs::{
 };
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("build-book") {
+        build_book(&args[2..]);
+        return;
+    }
+
     // let mut bb = 0;
     // for i in 0..8 {
     //     bb = bb.flip(get_index_from_file_and_rank(7-i,i));
@@ -275,7 +287,7 @@ fn test_ab_search(fen: String, depth: u8) {
             .unwrap();
         let cutoff = || Instant::now() > timeout;
         let (eval, moves) = engine.alpha_beta_search(
-            engine.current_game_state,
+            engine.current_game_state(),
             &cutoff,
             i,
             0,
@@ -307,7 +319,8 @@ fn test_it_deep_search(fen: String, ms: u64) {
     let _handle = log4rs::init_config(config).unwrap();
 
     let mut engine = ChimpEngine::from_position(fen);
-    println!("Eval before: {}", engine.current_game_state.position.eval);
+    let position = engine.current_game_state().position;
+    println!("Eval before: {}", engine.evaluate(&position));
     let timeout = Instant::now().checked_add(Duration::from_millis(ms)).unwrap();
     let cutoff = || Instant::now() > timeout;
     let o = engine.iterative_deepening(&cutoff, vec![]);
@@ -329,7 +342,8 @@ fn debug_evals(fen_1: String, fen_2: String) {
     let p1 = Position::from_fen(fen_1);
     let p2 = Position::from_fen(fen_2);
 
-    println!("{} vs {}", p1.eval, p2.eval)
+    let mut engine = ChimpEngine::new();
+    println!("{} vs {}", engine.evaluate(&p1), engine.evaluate(&p2))
 }
 
 // fn timed_depth_test() {
@@ -439,7 +453,7 @@ fn park_table() {
     let mut w_engine: ChimpEngine = ChimpEngine::from_position(position.clone());
     let mut b_engine: ChimpEngine = ChimpEngine::from_position(position.clone());
     let mut white_turn = !w_engine.black_turn();
-    let mut moves = Vec::new();
+    let mut game = Match::default();
     let mut move_ucis = Vec::new();
     let mut white_ms = 5000;
     let mut black_ms = 5000;
@@ -484,27 +498,113 @@ fn park_table() {
                 black_ms -= delay;
                 if black_ms < 0 {
                     info!("Black out of time!");
+                    game.end(MatchResultState::WhiteVictory, Termination::TimeForfeit);
                     break;
                 }
             } else {
                 white_ms -= delay;
                 if white_ms < 0 {
                     info!("White out of time!");
+                    game.end(MatchResultState::BlackVictory, Termination::TimeForfeit);
                     break;
                 }
             }
         }
+        let (clock_ms, engine) = if white_turn {
+            (white_ms, &w_engine)
+        } else {
+            (black_ms, &b_engine)
+        };
+        let eval = engine.last_search_info.pgn_eval(!white_turn);
+        game.play(m).unwrap();
+        let entry = game.last_move_mut().unwrap();
+        entry.clock = Some(Duration::from_millis(clock_ms.max(0) as u64));
+        entry.eval = Some(eval);
         white_turn = !white_turn;
-        moves.push(m);
-        if b_engine.current_game_state.result_state != MatchResultState::Active {
+        if game.result != MatchResultState::Active {
             break;
         }
     }
     let duration = start.elapsed();
-    info!("Result: {:?}", b_engine.current_game_state.result_state);
+    info!("Result: {:?} {:?}", game.result, game.termination);
     info!("Runtime: {:?}", duration);
-    info!("SAN: {}", build_san(moves, position));
-    info!("Final state: {:?}", b_engine.current_game_state);
+    game.tags.push(("Event".into(), "Park Table".into()));
+    game.tags.push(("White".into(), "chimp".into()));
+    game.tags.push(("Black".into(), "chimp".into()));
+    info!("PGN:\n{}", game.to_pgn_game().to_pgn());
+    info!("Final state: {:?}", game.current_game_state());
+}
+
+const BUILD_BOOK_USAGE: &str = "usage: chimp_university build-book <book.bin> <games.pgn>... [--min-games N] \
+[--max-ply N] [--side white|black] [--player NAME] [--results 1-0,1/2-1/2,0-1]";
+
+// Makes a Polyglot book from the mainlines of the games in some PGN files
+fn build_book(args: &[String]) {
+    let usage = |message: String| -> ! {
+        eprintln!("{message}\n{BUILD_BOOK_USAGE}");
+        std::process::exit(1)
+    };
+
+    let mut builder = BookBuilder::default();
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            files.push(arg.clone());
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => usage(format!("{arg} needs a value")),
+        };
+        match arg.as_str() {
+            "--min-games" => match value.parse() {
+                Ok(n) => builder.min_games = n,
+                Err(_) => usage(format!("bad --min-games '{value}'")),
+            },
+            "--max-ply" => match value.parse() {
+                Ok(n) => builder.max_ply = n,
+                Err(_) => usage(format!("bad --max-ply '{value}'")),
+            },
+            "--side" => match value.as_str() {
+                "white" => builder.side = Some(Colour::White),
+                "black" => builder.side = Some(Colour::Black),
+                _ => usage(format!("bad --side '{value}'")),
+            },
+            "--player" => builder.player = Some(value.clone()),
+            "--results" => {
+                builder.results = value
+                    .split(',')
+                    .map(|result| match result {
+                        "1-0" => MatchResultState::WhiteVictory,
+                        "1/2-1/2" => MatchResultState::Draw,
+                        "0-1" => MatchResultState::BlackVictory,
+                        _ => usage(format!("bad result '{result}'")),
+                    })
+                    .collect()
+            }
+            _ => usage(format!("unknown option {arg}")),
+        }
+    }
+    if files.len() < 2 {
+        usage("need a book and at least one PGN file".into());
+    }
+
+    let book_path = files.remove(0);
+    for path in files {
+        let pgn = match std::fs::read_to_string(&path) {
+            Ok(pgn) => pgn,
+            Err(e) => usage(format!("can't read {path}: {e}")),
+        };
+        let (games, skipped) = builder.add_pgn(&pgn);
+        println!("{path}: {games} games used, {skipped} unreadable games skipped");
+    }
+
+    let bytes = builder.to_bytes();
+    if let Err(e) = std::fs::write(&book_path, &bytes) {
+        usage(format!("can't write {book_path}: {e}"));
+    }
+    println!("{book_path}: {} moves from {} games", bytes.len() / 16, builder.games());
 }
 
 fn get_moves_string(moves: &Vec<String>) -> String {
@@ -2,6 +2,7 @@ use std::thread::JoinHandle;
 use std::time::{Instant, SystemTime};
 use std::{default, panic};
 
+use ch_imp::engine::options::{parse_setoption, EngineOptions};
 use ch_imp::engine::*;
 use ch_imp::r#move::Move;
 use log::{debug, info, LevelFilter};
@@ -62,15 +63,47 @@ fn run() -> bool {
         let first_word = split_string.next();
         match first_word {
             Some(word) => match word {
-                "uci" => println!("uciok"),
-                "isready" => println!("readyok"),
+                "uci" => {
+                    println!("id name chimp");
+                    for option in EngineOptions::uci_options() {
+                        println!("{option}");
+                    }
+                    println!("uciok")
+                }
+                "setoption" => match parse_setoption(&trimmed["setoption".len()..]) {
+                    Some((name, value)) => {
+                        if !engine.set_option(&name, value.as_deref()) {
+                            info!("Unknown or invalid option {}", trimmed);
+                        }
+                    }
+                    None => info!("Malformed setoption {}", trimmed),
+                },
+                "isready" => {
+                    engine.prepare();
+                    println!("readyok")
+                }
                 "ucinewgame" => {
-                    engine = ChimpEngine::new();
+                    log_game(&engine);
+                    engine.new_game();
                 }
                 "position" => {
                     engine.position(split_string);
                 }
                 "go" => {
+                    let mut args = split_string.clone();
+                    let fixed_limit = match (args.next(), args.next().map(|v| v.parse::<u64>())) {
+                        (Some("depth"), Some(Ok(depth))) => {
+                            Some(engine.go_depth(depth.min(u8::MAX as u64) as u8))
+                        }
+                        (Some("nodes"), Some(Ok(nodes))) => Some(engine.go_nodes(nodes)),
+                        _ => None,
+                    };
+                    if let Some((bestmove, ponder)) = fixed_limit {
+                        handle_search_info(&engine.last_search_info);
+                        handle_go_result(bestmove, ponder);
+                        input = String::new();
+                        continue;
+                    }
                     let (should_ponder, time_info) = get_go_params(split_string);
                     last_time_info = time_info;
                     // if should_ponder {
@@ -82,6 +115,7 @@ fn run() -> bool {
                             last_time_info.winc,
                             last_time_info.binc,
                         );
+                        handle_search_info(&engine.last_search_info);
                         handle_go_result(bestmove, ponder);
                     // }
                 }
@@ -114,7 +148,10 @@ fn run() -> bool {
                     // handle_go_result(_ponder_result[0], None);
                     // ponder_handler = None;
                 }
-                "quit" => break,
+                "quit" => {
+                    log_game(&engine);
+                    break;
+                }
                 _ => {
                     info!("Unknown command {}", input);
                     println!("Unknown command {}", input);
@@ -129,6 +166,30 @@ fn run() -> bool {
     true
 }
 
+// Keeps a PGN of each game in the log so games played through a GUI can be reviewed later
+fn log_game(engine: &ChimpEngine) {
+    let game = engine.pgn_game();
+    if !game.mainline.moves.is_empty() {
+        info!("Game PGN:\n{}", game.to_pgn());
+    }
+}
+
+fn handle_search_info(search_info: &SearchInfo) {
+    let pv: Vec<String> = search_info.pv.iter().map(|m| m.uci()).collect();
+    let message = format!(
+        "info depth {} score cp {} nodes {} hashfull {} tbhits {} pv {}",
+        search_info.depth,
+        search_info.score,
+        search_info.nodes,
+        search_info.hashfull,
+        search_info.tb_hits,
+        pv.join(" ")
+    );
+    info!("{}", message);
+    println!("{}", message);
+    println!("info string draw score {}", search_info.draw_score);
+}
+
 fn handle_go_result(bestmove: Move, ponder: Option<Move>) {
     if bestmove.is_empty() {
         println!("ff")